@@ -1,5 +1,6 @@
 use std::{fmt, io};
 use std::convert::From;
+use sourcepos::SourcePos;
 use std::path::PathBuf;
 use std::string::FromUtf8Error;
 use value::Value;
@@ -12,7 +13,21 @@ pub enum Error {
     Encoding(FromUtf8Error),
     BadValue(String),
     BadArguments(String),
-    S(String),
+    /// The input could not be parsed.
+    ///
+    /// `imported` is the positions of the `@import`s leading to the
+    /// file of `pos`, innermost first.
+    Parse {
+        msg: String,
+        pos: SourcePos,
+        imported: Vec<SourcePos>,
+    },
+    /// The input was parsed, but could not be evaluated.
+    Eval {
+        msg: String,
+        pos: SourcePos,
+        imported: Vec<SourcePos>,
+    },
 }
 
 impl Error {
@@ -33,6 +48,48 @@ impl Error {
                                     actual))
     }
 
+    /// Failed to parse the input at `pos`.
+    pub fn parse(msg: &str, pos: SourcePos) -> Error {
+        Error::Parse { msg: msg.into(), pos: pos, imported: vec![] }
+    }
+
+    /// Failed to evaluate the input at `pos`.
+    pub fn eval(msg: &str, pos: &SourcePos) -> Error {
+        Error::Eval { msg: msg.into(), pos: pos.clone(), imported: vec![] }
+    }
+
+    /// Give this error the position `pos`, unless it already has a
+    /// known position.
+    ///
+    /// This is for errors from evaluating values, where the position
+    /// is only known by the item containing the value.
+    pub fn at(self, pos: &SourcePos) -> Error {
+        match self {
+            Error::BadValue(msg) |
+            Error::BadArguments(msg) => Error::eval(&msg, pos),
+            Error::Eval { msg, pos: ref p, imported } if !p.is_known() => {
+                Error::Eval { msg: msg, pos: pos.clone(), imported: imported }
+            }
+            e => e,
+        }
+    }
+
+    /// Note that the file where this error occured was imported at
+    /// `pos`.
+    pub fn imported_from(self, pos: &SourcePos) -> Error {
+        match self {
+            Error::Parse { msg, pos: p, mut imported } => {
+                imported.push(pos.clone());
+                Error::Parse { msg: msg, pos: p, imported: imported }
+            }
+            Error::Eval { msg, pos: p, mut imported } => {
+                imported.push(pos.clone());
+                Error::Eval { msg: msg, pos: p, imported: imported }
+            }
+            e => e,
+        }
+    }
+
     /// Multiple-argument variant of `badarg`.
     pub fn badargs(expected: &[&str], actual: &[&Value]) -> Error {
         // TODO Better message!
@@ -45,16 +102,77 @@ impl Error {
 impl fmt::Display for Error {
     fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::S(ref s) => write!(out, "{}", s),
+            Error::Parse { ref msg, ref pos, ref imported } |
+            Error::Eval { ref msg, ref pos, ref imported } => {
+                write!(out, "{}", msg)?;
+                if pos.is_known() {
+                    write_snippet(out, pos, imported)?;
+                }
+                Ok(())
+            }
             Error::Input(ref p, ref e) => {
                 write!(out, "Failed to read {:?}: {}", p, e)
             }
-            // fallback
-            ref x => write!(out, "{:?}", x),
+            Error::BadValue(ref msg) |
+            Error::BadArguments(ref msg) => write!(out, "{}", msg),
+            Error::IoError(ref e) => write!(out, "{}", e),
+            Error::Encoding(ref e) => write!(out, "{}", e),
         }
     }
 }
 
+/// Write the line of `pos` with the position marked, followed by the
+/// position and the import stack, in the same format as dart-sass.
+fn write_snippet(out: &mut fmt::Formatter,
+                 pos: &SourcePos,
+                 imported: &[SourcePos])
+                 -> fmt::Result {
+    let line = pos.line.to_string();
+    let gutter = " ".repeat(line.len() + 1);
+    let marker = pos.line_text
+        .chars()
+        .take(pos.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    write!(out,
+           "\n{0}\u{2577}\n{1} \u{2502} {2}\n{0}\u{2502} {3}^\n{0}\u{2575}",
+           gutter,
+           line,
+           pos.line_text,
+           marker)?;
+    let stack = Some(pos)
+        .into_iter()
+        .chain(imported.iter())
+        .map(|p| format!("{} {}:{}", p.file, p.line, p.column))
+        .collect::<Vec<_>>();
+    let width = stack.iter().map(|s| s.len()).max().unwrap_or(0);
+    for (i, place) in stack.iter().enumerate() {
+        let what = if i + 1 < stack.len() {
+            rule_at(&imported[i])
+        } else {
+            "root stylesheet"
+        };
+        write!(out, "\n  {:2$}  {}", place, what, width)?;
+    }
+    Ok(())
+}
+
+/// The name of the rule loading a file at `pos`, as seen in the
+/// source.
+fn rule_at(pos: &SourcePos) -> &'static str {
+    let rule = pos.line_text
+        .chars()
+        .skip(pos.column.saturating_sub(1))
+        .collect::<String>();
+    if rule.starts_with("@use") {
+        "@use"
+    } else if rule.starts_with("@forward") {
+        "@forward"
+    } else {
+        "@import"
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
         Error::IoError(e)
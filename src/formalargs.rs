@@ -1,7 +1,10 @@
+use error::Error;
+use output_style::{Format, Formatted};
 use parseutil::{ignore_comments, name, opt_spacelike};
 use std::default::Default;
 use std::fmt;
-use value::{ListSeparator, Value, extended_literal, space_list};
+use std::str::from_utf8;
+use value::{Quotes, Value, extended_literal, space_list};
 use variablescope::{Scope, ScopeImpl};
 
 /// The declared arguments of a mixin or function declaration.
@@ -16,7 +19,17 @@ impl FormalArgs {
         FormalArgs(a, is_varargs)
     }
 
-    pub fn eval<'a>(&self, scope: &'a Scope, args: &CallArgs) -> ScopeImpl<'a> {
+    pub fn eval<'a>(&self,
+                    scope: &'a dyn Scope,
+                    args: &CallArgs)
+                    -> Result<ScopeImpl<'a>, Error> {
+        let expanded;
+        let args = if args.1 {
+            expanded = args.expand_rest(scope)?;
+            &expanded
+        } else {
+            args
+        };
         let mut argscope = ScopeImpl::sub(scope);
         let n = self.0.len();
         for (i, &(ref name, ref default)) in self.0.iter().enumerate() {
@@ -24,19 +37,37 @@ impl FormalArgs {
                    .iter()
                    .find(|&&(ref k, ref _v)| k.as_ref() == Some(name))
                    .map(|&(ref _k, ref v)| v) {
-                argscope.define(name, value);
-            } else if self.1 && i + 1 == n && args.0.len() > n {
-                let args =
-                    args.0[i..].iter().map(|&(_, ref v)| v.clone()).collect();
-                argscope.define(name, &Value::List(args, ListSeparator::Comma));
+                argscope.define(name, value)?;
+            } else if self.1 && i + 1 == n {
+                let positional = args.0
+                    .iter()
+                    .skip(i)
+                    .filter(|&&(ref k, _)| k.is_none())
+                    .map(|&(_, ref v)| v.clone())
+                    .collect();
+                let keywords = args.0
+                    .iter()
+                    .filter_map(|&(ref k, ref v)| match *k {
+                        Some(ref k) if !self.has_name(k) => {
+                            Some((Value::Literal(k.clone(), Quotes::None),
+                                  v.clone()))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                argscope.define(name, &Value::ArgList(positional, keywords))?;
             } else {
                 argscope.define(name, match args.0.get(i) {
                     Some(&(None, ref v)) => v,
                     _ => default,
-                });
+                })?;
             }
         }
-        argscope
+        Ok(argscope)
+    }
+
+    fn has_name(&self, name: &str) -> bool {
+        self.0.iter().any(|&(ref n, _)| n == name)
     }
 }
 
@@ -50,49 +81,108 @@ impl Default for FormalArgs {
 ///
 /// Each argument has a Value.  Arguments may be named.
 /// If the optional name is None, the argument is positional.
+/// The flag is true if the last argument is a rest argument, as in
+/// `f($args...)`.
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct CallArgs(Vec<(Option<String>, Value)>);
+pub struct CallArgs(Vec<(Option<String>, Value)>, bool);
 
 impl CallArgs {
-    #[cfg(test)]
     pub fn new(v: Vec<(Option<String>, Value)>) -> Self {
-        CallArgs(v)
+        CallArgs(v, false)
     }
 
+    /// The arguments contained in an argument list.
+    ///
+    /// Any other value is a single positional argument.
     pub fn from_value(v: Value) -> Self {
         match v {
-            Value::List(v, _) => {
-                CallArgs(v.into_iter().map(|v| (None, v)).collect())
+            Value::ArgList(v, kw) => {
+                let kw = kw.into_iter().map(|(k, v)| match k {
+                                                Value::Literal(k, _) => {
+                                                    (Some(k), v)
+                                                }
+                                                k => (Some(k.to_string()), v),
+                                            });
+                CallArgs::new(v.into_iter()
+                                  .map(|v| (None, v))
+                                  .chain(kw)
+                                  .collect())
             }
-            v => CallArgs(vec![(None, v)]),
+            v => CallArgs::new(vec![(None, v)]),
         }
     }
 
-    pub fn xyzzy(&self, scope: &Scope) -> Self {
-        CallArgs(self.0
-                     .iter()
-                     .map(|&(ref n, ref v)| (n.clone(), v.evaluate(scope)))
-                     .collect())
+    pub fn xyzzy(&self, scope: &dyn Scope) -> Result<Self, Error> {
+        let args = self.0
+            .iter()
+            .map(|&(ref n, ref v)| Ok((n.clone(), v.evaluate(scope)?)))
+            .collect::<Result<_, Error>>()?;
+        CallArgs(args, self.1).expand_rest(scope)
+    }
+
+    /// Replace a rest argument by the arguments it contains.
+    ///
+    /// A list gives positional arguments and a map gives keyword
+    /// arguments.
+    fn expand_rest(&self, scope: &dyn Scope) -> Result<Self, Error> {
+        let mut args = self.0.clone();
+        if self.1 {
+            if let Some((_, rest)) = args.pop() {
+                match rest.evaluate(scope)? {
+                    Value::List(v, ..) => {
+                        args.extend(v.into_iter().map(|v| (None, v)))
+                    }
+                    Value::Map(map) => {
+                        args.extend(map.into_iter().map(|(k, v)| {
+                            let k = match k {
+                                Value::Literal(k, _) => k,
+                                k => k.to_string(),
+                            };
+                            (Some(k.replace('-', "_")), v)
+                        }))
+                    }
+                    v => args.extend(CallArgs::from_value(v).0),
+                }
+            }
+        }
+        Ok(CallArgs::new(args))
+    }
+
+    /// Get these arguments with a `Format` to write them in.
+    pub fn format<'a>(&'a self, format: Format) -> Formatted<'a, CallArgs> {
+        Formatted {
+            value: self,
+            format: format,
+        }
     }
 }
 
 impl Default for CallArgs {
     fn default() -> Self {
-        CallArgs(vec![])
+        CallArgs::new(vec![])
     }
 }
 
 impl fmt::Display for CallArgs {
     fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
-        let t = self.0
+        self.format(Format::default()).fmt(out)
+    }
+}
+
+impl<'a> fmt::Display for Formatted<'a, CallArgs> {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        let t = self.value
+            .0
             .iter()
             .map(|kv| match *kv {
-                     (Some(ref k), ref v) => format!("${}: {}", k, v),
-                     (None, ref v) => format!("{}", v),
+                     (Some(ref k), ref v) => {
+                         format!("${}: {}", k, v.format(self.format))
+                     }
+                     (None, ref v) => format!("{}", v.format(self.format)),
                  })
             .collect::<Vec<_>>()
             .join(", ");
-        write!(out, "{}", t)
+        write!(out, "{}{}", t, if self.value.1 { "..." } else { "" })
     }
 }
 
@@ -113,19 +203,28 @@ named!(pub formal_args<FormalArgs>,
                  (FormalArgs(v, va.is_some()))));
 
 named!(pub call_args<CallArgs>,
-       delimited!(
-           tag!("("),
-           map!(separated_list!(
+       do_parse!(
+           tag!("(") >>
+           args: separated_list!(
                preceded!(tag!(","), opt_spacelike),
                pair!(opt!(delimited!(
                         tag!("$"),
                         map!(name, |n: String| n.replace("-", "_")),
                         preceded!(ignore_comments,
                                   tag!(":")))),
-                     alt!(space_list |
+                     alt!(ms_filter_arg |
+                          space_list |
                           extended_literal |
                           delimited!(ignore_comments,
                                      space_list,
-                                     ignore_comments)))),
-                |args| CallArgs(args)),
-           tag!(")")));
+                                     ignore_comments)))) >>
+           rest: opt!(terminated!(tag!("..."), opt_spacelike)) >>
+           tag!(")") >>
+           (CallArgs(args, rest.is_some()))));
+
+// An old IE filter argument, as in `alpha(opacity=50)`.
+named!(ms_filter_arg<Value>,
+       do_parse!(n: name >> tag!("=") >> not!(tag!("=")) >>
+                 v: is_not!(",)") >>
+                 (Value::Literal(format!("{}={}", n, from_utf8(v).unwrap()),
+                                 Quotes::None))));
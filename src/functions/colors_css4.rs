@@ -0,0 +1,685 @@
+//! Colors in the css color level 4 color spaces.
+//!
+//! The legacy color functions (that work on rgb colors) are wrapped
+//! to handle these colors as well, see `wrap_legacy`.
+use super::{Error, FuncImpl, SassFunction};
+use super::colors_hsl::{hwba_to_rgba, turns};
+use formalargs::CallArgs;
+use num_rational::Rational;
+use num_traits::{One, Signed, Zero};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use unit::{Unit, UnitSet};
+use value::{ColorSpace, ListSeparator, Quotes, SafeArith, Value, big,
+            big_to_f64, from_big, from_f64, from_floats, to_f64, to_floats};
+use variablescope::{Scope, ScopeImpl};
+
+pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
+    def!(f, lab(channels), |s| {
+        css4_color(ColorSpace::Lab, "lab", s.get("channels"))
+    });
+    def!(f, lch(channels), |s| {
+        css4_color(ColorSpace::Lch, "lch", s.get("channels"))
+    });
+    def!(f, oklab(channels), |s| {
+        css4_color(ColorSpace::Oklab, "oklab", s.get("channels"))
+    });
+    def!(f, oklch(channels), |s| {
+        css4_color(ColorSpace::Oklch, "oklch", s.get("channels"))
+    });
+    def!(f, color(description), |s| {
+        let description = s.get("description");
+        if let Some((mut channels, alpha)) = channels(&description) {
+            if !channels.is_empty() {
+                if let Value::Literal(ref name, Quotes::None) = channels[0] {
+                    if name == "display-p3" {
+                        channels.remove(0);
+                        let c = Value::List(channels,
+                                            ListSeparator::Space,
+                                            false);
+                        let c = match alpha {
+                            Some(alpha) => {
+                                Value::List(vec![c, alpha],
+                                            ListSeparator::Slash,
+                                            false)
+                            }
+                            None => c,
+                        };
+                        return css4_color(ColorSpace::DisplayP3, "color", c);
+                    }
+                }
+            }
+        }
+        Ok(css_call("color", vec![description]))
+    });
+    def!(f, hwb(hue, whiteness, blackness, alpha), |s| {
+        let (c, alpha) = if s.get("whiteness").is_null() {
+            let args = s.get("hue");
+            match channels(&args) {
+                Some((c, a)) => (c, a),
+                None => return Ok(css_call("hwb", vec![args])),
+            }
+        } else {
+            let alpha = s.get("alpha");
+            (vec![s.get("hue"), s.get("whiteness"), s.get("blackness")],
+             if alpha.is_null() { None } else { Some(alpha) })
+        };
+        if c.len() != 3 {
+            return Err(Error::BadArguments("$channels: The hwb color space \
+                                            has exactly 3 channels."
+                                                   .into()));
+        }
+        let hue = hue_value(&c[0], "hue")?;
+        let w = channel_value(&c[1], "whiteness", 100.)?;
+        let b = channel_value(&c[2], "blackness", 100.)?;
+        let a = match alpha {
+            Some(ref alpha) => alpha_value(alpha)?,
+            None => Some(Rational::one()),
+        };
+        match (hue, w, b, a) {
+            (Some(hue), Some(w), Some(b), Some(a)) => {
+                let hundred = Rational::from_integer(100);
+                hwba_to_rgba(turns(hue),
+                             cap(w, &hundred).safe_div(&hundred)?,
+                             cap(b, &hundred).safe_div(&hundred)?,
+                             a)
+            }
+            _ if s.get("whiteness").is_null() => {
+                Ok(css_call("hwb", vec![s.get("hue")]))
+            }
+            _ => {
+                let mut args = c;
+                args.extend(alpha);
+                Ok(css_call("hwb", args))
+            }
+        }
+    });
+}
+
+/// Make the legacy color functions handle css color level 4 colors.
+///
+/// Lightness, alpha and mixing are handled in the space of the color,
+/// hue and saturation in its polar form.
+/// Other functions get the color mapped to srgb, and a color result
+/// is converted back to the original space.
+pub fn wrap_legacy(f: &mut BTreeMap<&'static str, SassFunction>) {
+    for name in &["adjust_color",
+                  "adjust_hue",
+                  "alpha",
+                  "blue",
+                  "change_color",
+                  "complement",
+                  "darken",
+                  "desaturate",
+                  "fade_in",
+                  "fade_out",
+                  "grayscale",
+                  "green",
+                  "hue",
+                  "ie_hex_str",
+                  "invert",
+                  "lighten",
+                  "lightness",
+                  "mix",
+                  "opacify",
+                  "opacity",
+                  "red",
+                  "rgba",
+                  "saturate",
+                  "saturation",
+                  "scale_color",
+                  "transparentize"] {
+        let name: &'static str = name;
+        if let Some(func) = f.get_mut(name) {
+            let legacy = match func.body {
+                FuncImpl::Builtin(ref body) => body.clone(),
+                FuncImpl::UserDefined(_) => continue,
+            };
+            func.body = FuncImpl::Builtin(Arc::new(move |s: &dyn Scope| {
+                if let Some(result) = native(name, s)? {
+                    return Ok(result);
+                }
+                let mut sub = ScopeImpl::sub(s);
+                let mut space = None;
+                for arg in &["color", "color1", "color2", "red"] {
+                    if let Value::Color4(sp, ref c, a) = s.get(arg) {
+                        space = space.or(Some(sp));
+                        sub.define(arg, &to_rgb(sp, c, a))?;
+                    }
+                }
+                match (space, legacy(&sub)?) {
+                    (Some(space), Value::Color(r, g, b, a, _)) => {
+                        Ok(from_rgb(space, &r, &g, &b, a))
+                    }
+                    (_, result) => Ok(result),
+                }
+            }));
+        }
+    }
+}
+
+/// Handle a legacy color function natively for a css color level 4
+/// color, if possible.
+fn native(name: &str, s: &dyn Scope) -> Result<Option<Value>, Error> {
+    if name == "mix" {
+        return mix(s);
+    }
+    let color = match name {
+        "rgba" => {
+            let red = s.get("red");
+            if red.is_null() { s.get("color") } else { red }
+        }
+        _ => s.get("color"),
+    };
+    let (space, c, a) = match color {
+        Value::Color4(space, c, a) => (space, c, a),
+        _ => return Ok(None),
+    };
+    let with_alpha = |a: Rational| {
+        Ok(Some(Value::Color4(space, c, cap(a, &Rational::one()))))
+    };
+    match name {
+        "alpha" | "opacity" => {
+            Ok(Some(Value::Numeric(big(&a), UnitSet::scalar(), false, true)))
+        }
+        "rgba" => {
+            let alpha = s.get("alpha");
+            let alpha = if alpha.is_null() { s.get("green") } else { alpha };
+            match alpha_value(&alpha)? {
+                Some(alpha) => with_alpha(alpha),
+                None => Err(Error::badarg("number", &alpha)),
+            }
+        }
+        "fade_in" | "opacify" => with_alpha(a.safe_add(&fade_amount(s)?)),
+        "fade_out" | "transparentize" => {
+            with_alpha(a.safe_sub(&fade_amount(s)?))
+        }
+        "lighten" | "darken" => {
+            let amount = match s.get("amount") {
+                Value::Numeric(ref v, ..) => {
+                    from_big(v).safe_div(&Rational::from_integer(100))?
+                }
+                v => return Err(Error::badarg("number", &v)),
+            };
+            let amount = if name == "darken" { -amount } else { amount };
+            let (lspace, c) = match space.lightness() {
+                Some(_) => (space, c),
+                None => {
+                    let oklch = space.convert(ColorSpace::Oklch,
+                                              to_floats(&c));
+                    (ColorSpace::Oklch, from_floats(oklch))
+                }
+            };
+            let mut c = c;
+            if let Some((i, max)) = lspace.lightness() {
+                let max = from_f64(max).unwrap_or_else(Rational::one);
+                c[i] = cap(c[i].safe_add(&amount.safe_mul(&max)), &max);
+            }
+            let c = lspace.convert(space, to_floats(&c));
+            Ok(Some(Value::Color4(space, from_floats(c), a)))
+        }
+        "adjust_hue" | "complement" | "saturate" | "desaturate" => {
+            adjust_polar(name, s, space, c, a).map(Some)
+        }
+        "adjust_color" | "change_color" | "scale_color" => {
+            adjust_lightness(name, s, space, c, a)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Rotate the hue or change the chroma of a color in a polar space.
+///
+/// Colors in lch and oklch are adjusted in their own space, lab and
+/// oklab colors in their polar form, and other colors in oklch, so
+/// the lightness is kept.
+/// The `$amount` of `saturate` and `desaturate` is a percentage of the
+/// reference range of the chroma.
+fn adjust_polar(name: &str,
+                s: &dyn Scope,
+                space: ColorSpace,
+                c: [Rational; 3],
+                a: Rational)
+                -> Result<Value, Error> {
+    let pspace = match space {
+        ColorSpace::Lab | ColorSpace::Lch => ColorSpace::Lch,
+        _ => ColorSpace::Oklch,
+    };
+    let mut c = match space {
+        ColorSpace::Lch | ColorSpace::Oklch => c,
+        ColorSpace::Lab | ColorSpace::Oklab => {
+            let c = to_floats(&c);
+            from_floats([c[0], c[1].hypot(c[2]), c[2].atan2(c[1]).to_degrees()])
+        }
+        _ => from_floats(space.convert(pspace, to_floats(&c))),
+    };
+    match name {
+        "complement" => c[2] = c[2].safe_add(&Rational::from_integer(180)),
+        "adjust_hue" => {
+            let degrees = s.get("degrees");
+            match hue_value(&degrees, "degrees")? {
+                Some(degrees) => c[2] = c[2].safe_add(&degrees),
+                None => return Err(Error::badarg("number", &degrees)),
+            }
+        }
+        _ => {
+            let max = from_f64(pspace.percent_ref()[1])
+                .unwrap_or_else(Rational::one);
+            let amount = match s.get("amount") {
+                Value::Numeric(ref v, ..) => {
+                    from_big(v).safe_div(&Rational::from_integer(100))?
+                }
+                v => return Err(Error::badarg("number", &v)),
+            };
+            let amount = amount.safe_mul(&max);
+            let amount = if name == "desaturate" { -amount } else { amount };
+            c[1] = cap(c[1].safe_add(&amount), &max);
+        }
+    }
+    let turn = Rational::from_integer(360);
+    c[2] %= turn;
+    if c[2].is_negative() {
+        c[2] += turn;
+    }
+    let c = match space {
+        ColorSpace::Lch | ColorSpace::Oklch => c,
+        ColorSpace::Lab | ColorSpace::Oklab => {
+            let c = to_floats(&c);
+            let h = c[2].to_radians();
+            from_floats([c[0], c[1] * h.cos(), c[1] * h.sin()])
+        }
+        _ => from_floats(pspace.convert(space, to_floats(&c))),
+    };
+    Ok(Value::Color4(space, c, a))
+}
+
+/// Adjust, change or scale the lightness and alpha of a color in its
+/// own space.
+///
+/// Returns `None` if the space has no lightness channel or if any
+/// other channel is given, so the color is handled as srgb.
+fn adjust_lightness(name: &str,
+                    s: &dyn Scope,
+                    space: ColorSpace,
+                    mut c: [Rational; 3],
+                    a: Rational)
+                    -> Result<Option<Value>, Error> {
+    let (i, max) = match space.lightness() {
+        Some((i, max)) => (i, from_f64(max).unwrap_or_else(Rational::one)),
+        None => return Ok(None),
+    };
+    let others = ["red", "green", "blue", "hue", "saturation", "whiteness",
+                  "blackness"];
+    if others.iter().any(|n| !s.get(n).is_null()) {
+        return Ok(None);
+    }
+    let update = |v: Rational, arg: &str, max: &Rational| {
+        let arg_value = s.get(arg);
+        if arg_value.is_null() {
+            return Ok(v);
+        }
+        let amount = match channel_value(&arg_value, arg, to_f64(max))? {
+            Some(amount) => amount,
+            None => return Err(Error::badarg("number", &arg_value)),
+        };
+        Ok(match name {
+            "adjust_color" => cap(v.safe_add(&amount), max),
+            "change_color" => cap(amount, max),
+            _ => {
+                // The amount is a fraction of the way to max or zero.
+                let f = amount.safe_div(max)?;
+                if f.is_negative() {
+                    v.safe_add(&v.safe_mul(&f))
+                } else {
+                    v.safe_add(&max.safe_sub(&v).safe_mul(&f))
+                }
+            }
+        })
+    };
+    c[i] = update(c[i], "lightness", &max)?;
+    let a = update(a, "alpha", &Rational::one())?;
+    Ok(Some(Value::Color4(space, c, a)))
+}
+
+/// Mix two colors, in the space of the first css color level 4 color.
+fn mix(s: &dyn Scope) -> Result<Option<Value>, Error> {
+    let (color1, color2) = (s.get("color1"), s.get("color2"));
+    let space = match (&color1, &color2) {
+        (&Value::Color4(space, ..), _) |
+        (_, &Value::Color4(space, ..)) => space,
+        _ => return Ok(None),
+    };
+    let (c1, a1) = match in_space(space, &color1) {
+        Some(c) => c,
+        None => return Err(Error::badarg("color", &color1)),
+    };
+    let (c2, a2) = match in_space(space, &color2) {
+        Some(c) => c,
+        None => return Err(Error::badarg("color", &color2)),
+    };
+    let w = match s.get("weight") {
+        Value::Numeric(ref w, ref u, ..) if u.is_percent() => {
+            big_to_f64(w) / 100.
+        }
+        Value::Numeric(ref w, ..) => big_to_f64(w),
+        w => return Err(Error::badarg("number", &w)),
+    };
+    let mut c = [0.; 3];
+    for i in 0..3 {
+        c[i] = c1[i] * w + c2[i] * (1. - w);
+    }
+    if space.is_polar() {
+        // Interpolate the hue along the shorter arc.
+        let d = c2[2] - c1[2];
+        let d = if d > 180. {
+            d - 360.
+        } else if d < -180. {
+            d + 360.
+        } else {
+            d
+        };
+        c[2] = (c1[2] + d * (1. - w)).rem_euclid(360.);
+    }
+    let a = to_f64(&a1) * w + to_f64(&a2) * (1. - w);
+    let a = from_f64(a).unwrap_or_else(Rational::one);
+    Ok(Some(Value::Color4(space, from_floats(c), a)))
+}
+
+/// Get the channels and alpha of any color in a given space.
+fn in_space(space: ColorSpace, color: &Value) -> Option<([f64; 3], Rational)> {
+    match *color {
+        Value::Color4(from, ref c, a) => {
+            Some((from.convert(space, to_floats(c)), a))
+        }
+        Value::Color(ref r, ref g, ref b, a, _) => {
+            let rgb = [to_f64(r) / 255., to_f64(g) / 255., to_f64(b) / 255.];
+            Some((space.convert_srgb(rgb), a))
+        }
+        _ => None,
+    }
+}
+
+/// Convert a css color level 4 color to a legacy rgb color.
+///
+/// Colors outside of the srgb gamut are mapped into it.
+pub fn to_rgb(space: ColorSpace, c: &[Rational; 3], a: Rational) -> Value {
+    let rgb = space.to_srgb(to_floats(c));
+    let byte = |v: f64| {
+        from_f64(v * 255.).unwrap_or_else(Rational::zero)
+    };
+    Value::rgba(byte(rgb[0]), byte(rgb[1]), byte(rgb[2]), a)
+}
+
+/// Convert a legacy rgb color to a css color level 4 color.
+pub fn from_rgb(space: ColorSpace,
+                r: &Rational,
+                g: &Rational,
+                b: &Rational,
+                a: Rational)
+                -> Value {
+    let rgb = [to_f64(r) / 255., to_f64(g) / 255., to_f64(b) / 255.];
+    Value::Color4(space, from_floats(space.convert_srgb(rgb)), a)
+}
+
+/// Create a color from the argument of a css color function, such as
+/// `50% 40 59.5 / 0.5`.
+///
+/// If the channels cannot be evaluated (e.g. when they contain a
+/// `var()` or a missing `none` channel), the function call is kept as
+/// it is.
+fn css4_color(space: ColorSpace,
+              name: &str,
+              args: Value)
+              -> Result<Value, Error> {
+    let keep = || Ok(css_call(name, vec![args.clone()]));
+    let (values, alpha) = match channels(&args) {
+        Some((ref c, ref a)) if c.len() == 3 => (c.clone(), a.clone()),
+        Some(_) => {
+            return Err(Error::BadArguments(format!("$channels: The {} \
+                                                    color space has \
+                                                    exactly 3 channels.",
+                                                   space)))
+        }
+        None => return keep(),
+    };
+    let names = match space {
+        ColorSpace::Lab | ColorSpace::Oklab => ["lightness", "a", "b"],
+        ColorSpace::Lch | ColorSpace::Oklch => ["lightness", "chroma", "hue"],
+        ColorSpace::DisplayP3 => ["red", "green", "blue"],
+    };
+    let refs = space.percent_ref();
+    let mut c = [Rational::zero(); 3];
+    for i in 0..3 {
+        let v = if space.is_polar() && i == 2 {
+            hue_value(&values[i], names[i])?
+        } else {
+            channel_value(&values[i], names[i], refs[i])?
+        };
+        c[i] = match v {
+            Some(v) => v,
+            None => return keep(),
+        };
+    }
+    if let Some((i, max)) = space.lightness() {
+        c[i] = cap(c[i], &from_f64(max).unwrap_or_else(Rational::one));
+    }
+    if space.is_polar() {
+        if c[1].is_negative() {
+            c[1] = Rational::zero();
+        }
+        let full = Rational::from_integer(360);
+        c[2] = c[2] - (c[2] / full).floor() * full;
+    }
+    let alpha = match alpha {
+        Some(alpha) => {
+            match alpha_value(&alpha)? {
+                Some(alpha) => alpha,
+                None => return keep(),
+            }
+        }
+        None => Rational::one(),
+    };
+    Ok(Value::Color4(space, c, alpha))
+}
+
+/// The arguments of a legacy color function, such as `rgb` or `hsl`.
+pub enum LegacyArgs {
+    /// Three channels and an alpha value.
+    Channels(Vec<Value>, Rational),
+    /// Some argument is not known at compile time, so the call is
+    /// kept as plain css.
+    Css(Value),
+}
+
+/// Get the arguments of a legacy color function.
+///
+/// The channels may be given as separate arguments, as in
+/// `rgba(0, 128, 255, 0.5)`, or as a space-separated list with an
+/// optional slash-separated alpha, as in `rgb(0 128 255 / 50%)`.
+/// The `names` are the names of the three channels and the alpha.
+pub fn legacy_args(name: &str,
+                   s: &dyn Scope,
+                   names: &[&str; 4])
+                   -> Result<LegacyArgs, Error> {
+    let first = s.get(names[0]);
+    let (c, alpha, single) = match channels(&first) {
+        Some((c, alpha)) if s.get(names[1]).is_null() &&
+                            s.get(names[2]).is_null() => {
+            (c, alpha.unwrap_or_else(|| s.get(names[3])), true)
+        }
+        _ => {
+            (vec![first.clone(), s.get(names[1]), s.get(names[2])],
+             s.get(names[3]),
+             false)
+        }
+    };
+    if c.iter().chain(Some(&alpha)).any(is_css_only) {
+        let args = if single {
+            vec![first]
+        } else {
+            c.into_iter()
+                .chain(Some(alpha))
+                .filter(|v| !v.is_null())
+                .collect()
+        };
+        return Ok(LegacyArgs::Css(css_call(name, args)));
+    }
+    if c.len() != 3 {
+        return Err(Error::BadArguments(format!("$channels: Expected 3 \
+                                                channels, got {}.",
+                                               c.len())));
+    }
+    let alpha = match alpha {
+        Value::Null => Rational::one(),
+        alpha => {
+            match alpha_value(&alpha)? {
+                Some(alpha) => alpha,
+                None => return Err(Error::badarg("number", &alpha)),
+            }
+        }
+    };
+    Ok(LegacyArgs::Channels(c, alpha))
+}
+
+/// True for a value that can only be evaluated by the browser, such
+/// as `var(--x)`, `env(x)`, a calculation or a missing (`none`)
+/// channel.
+pub fn is_css_only(v: &Value) -> bool {
+    match *v {
+        Value::Call(ref name, _) => name == "var" || name == "env",
+        Value::Literal(ref s, Quotes::None) => s == "none",
+        Value::Calc(..) => true,
+        Value::List(ref v, ..) => v.iter().any(is_css_only),
+        Value::Div(ref a, ref b) => is_css_only(a) || is_css_only(b),
+        _ => false,
+    }
+}
+
+/// A plain css function call, for arguments not known at compile time.
+///
+/// An alpha value after a slash is written with spaces around the
+/// slash, as in `rgb(0 0 0 / var(--a))`.
+pub fn css_call(name: &str, args: Vec<Value>) -> Value {
+    let arg = |v: Value| match channels(&v) {
+        Some((c, Some(alpha))) => {
+            let slash = Value::Literal("/".into(), Quotes::None);
+            (None,
+             Value::List(vec![Value::List(c, ListSeparator::Space, false),
+                              slash,
+                              alpha],
+                         ListSeparator::Space,
+                         false))
+        }
+        _ => (None, v),
+    };
+    Value::Call(name.into(), CallArgs::new(args.into_iter().map(arg).collect()))
+}
+
+/// Split the argument of a css color function into channels and an
+/// optional alpha value, as in `0 128 255 / 50%`.
+///
+/// Returns `None` if the argument is not a space-separated list.
+pub fn channels(args: &Value) -> Option<(Vec<Value>, Option<Value>)> {
+    match *args {
+        Value::List(ref v, ListSeparator::Slash, false) if v.len() == 2 => {
+            channels(&v[0]).map(|(c, _)| (c, Some(v[1].clone())))
+        }
+        Value::List(ref v, ListSeparator::Space, false) => {
+            let mut v = v.clone();
+            let alpha = match v.pop() {
+                Some(Value::List(ref last, ListSeparator::Slash, false)) if
+                    last.len() == 2 => {
+                    v.push(last[0].clone());
+                    Some(last[1].clone())
+                }
+                Some(Value::Div(ref c, ref a)) => {
+                    v.push((**c).clone());
+                    Some((**a).clone())
+                }
+                Some(last) => {
+                    v.push(last);
+                    None
+                }
+                None => None,
+            };
+            Some((v, alpha))
+        }
+        _ => None,
+    }
+}
+
+/// Get a channel value, where 100% is `full`.
+///
+/// Returns `None` for a value that is not known at compile time.
+pub fn channel_value(v: &Value,
+                     name: &str,
+                     full: f64)
+                     -> Result<Option<Rational>, Error> {
+    match *v {
+        Value::Numeric(ref v, ref u, ..) if u.is_percent() => {
+            let full = from_f64(full / 100.).unwrap_or_else(Rational::zero);
+            Ok(Some(from_big(v).safe_mul(&full)))
+        }
+        Value::Numeric(ref v, ref u, ..) if u.is_none() => {
+            Ok(Some(from_big(v)))
+        }
+        Value::Numeric(..) => {
+            Err(Error::BadArguments(format!("${}: Expected {} to have no \
+                                             units or \"%\".",
+                                            name,
+                                            v)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Get a hue value in degrees, from any angle unit.
+///
+/// Returns `None` for a value that is not known at compile time.
+pub fn hue_value(value: &Value,
+                 name: &str)
+                 -> Result<Option<Rational>, Error> {
+    match *value {
+        Value::Numeric(ref v, ref u, ..) => {
+            if u.is_none() {
+                Ok(Some(from_big(v)))
+            } else if let Some(scale) = u.scale_to(&Unit::Deg.into()) {
+                Ok(Some(from_big(&(v * scale))))
+            } else {
+                Err(Error::BadArguments(format!("${}: Expected {} to have \
+                                                 an angle unit \
+                                                 (deg, grad, rad, turn).",
+                                                name,
+                                                value)))
+            }
+        }
+        _ => channel_value(value, name, 0.),
+    }
+}
+
+/// Get an alpha value (0 .. 1), from a number or a percentage.
+///
+/// Returns `None` for a value that is not known at compile time.
+pub fn alpha_value(v: &Value) -> Result<Option<Rational>, Error> {
+    Ok(channel_value(v, "alpha", 1.)?.map(|a| cap(a, &Rational::one())))
+}
+
+/// Get the `$amount` argument of `fade-in` or `fade-out`.
+fn fade_amount(s: &dyn Scope) -> Result<Rational, Error> {
+    let amount = s.get("amount");
+    match channel_value(&amount, "amount", 1.)? {
+        Some(amount) => Ok(amount),
+        None => Err(Error::badarg("number", &amount)),
+    }
+}
+
+/// Limit `v` to `0 .. max`.
+fn cap(v: Rational, max: &Rational) -> Rational {
+    if v.is_negative() {
+        Rational::zero()
+    } else if v > *max {
+        *max
+    } else {
+        v
+    }
+}
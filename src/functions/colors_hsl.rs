@@ -1,109 +1,114 @@
 use super::{Error, SassFunction};
+use super::colors_css4::{LegacyArgs, hue_value, legacy_args};
+use formalargs::CallArgs;
 use num_rational::Rational;
 use num_traits::{One, Signed, Zero};
 use std::collections::BTreeMap;
 use unit::Unit;
-use value::Value;
+use value::{SafeArith, Value, big, from_big};
 use variablescope::Scope;
 
 pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
-    def!(f, hsl(hue, saturation, lightness), |s: &Scope| {
-        Ok(hsla_to_rgba(to_rational(s.get("hue"))? * Rational::new(1, 360),
-                        to_rational_percent(s.get("saturation"))?,
-                        to_rational_percent(s.get("lightness"))?,
-                        Rational::one()))
+    def!(f, hsl(hue, saturation, lightness, alpha), |s: &dyn Scope| {
+        hsla("hsl", s)
     });
-    def!(f, hsla(hue, saturation, lightness, alpha), |s: &Scope| {
-        Ok(hsla_to_rgba(to_rational(s.get("hue"))? * Rational::new(1, 360),
-                        to_rational_percent(s.get("saturation"))?,
-                        to_rational_percent(s.get("lightness"))?,
-                        to_rational(s.get("alpha"))?))
+    def!(f, hsla(hue, saturation, lightness, alpha), |s: &dyn Scope| {
+        hsla("hsla", s)
     });
-    def!(f, adjust_hue(color, degrees), |s: &Scope| {
+    def!(f, adjust_hue(color, degrees), |s: &dyn Scope| {
         fn a_comb(orig: Rational, x: Value) -> Result<Rational, Error> {
             match x {
                 Value::Null => Ok(orig),
-                x => Ok(orig + to_rational(x)?),
+                x => Ok(orig.safe_add(&to_degrees(x)?)),
             }
         }
         match &s.get("color") {
             &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
                 let h_adj = s.get("degrees");
-                let (h, s, l) = rgb_to_hsl(red, green, blue);
+                let (h, s, l) = rgb_to_hsl(red, green, blue)?;
                 let h = a_comb(h, h_adj)?;
-                Ok(hsla_to_rgba(h * Rational::new(1, 360), s, l, *alpha))
+                Ok(hsla_to_rgba(turns(h), s, l, *alpha))
             }
             v => Err(Error::badarg("color", v)),
         }
     });
-    def!(f, complement(color), |s: &Scope| match &s.get("color") {
+    def!(f, complement(color), |s: &dyn Scope| match &s.get("color") {
         &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
-            let (h, s, l) = rgb_to_hsl(red, green, blue);
-            let h = (h + Rational::from_integer(180)) * Rational::new(1, 360);
+            let (h, s, l) = rgb_to_hsl(red, green, blue)?;
+            let h = turns(h.safe_add(&Rational::from_integer(180)));
             Ok(hsla_to_rgba(h, s, l, *alpha))
         }
         v => Err(Error::badarg("color", v)),
     });
-    def!(f, saturate(color, amount), |args: &Scope| {
+    def!(f, saturate(color, amount), |args: &dyn Scope| {
         fn comb(orig: Rational, x: Value) -> Result<Rational, Error> {
             match x {
                 Value::Null => Ok(orig),
-                x => Ok(cap_percentage(orig + to_rational_percent(x)?)),
+                x => {
+                    let x = to_rational_percent(x)?;
+                    Ok(cap_percentage(orig.safe_add(&x)))
+                }
             }
         }
         match &args.get("color") {
             &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
-                let (h, s, l) = rgb_to_hsl(red, green, blue);
+                let (h, s, l) = rgb_to_hsl(red, green, blue)?;
                 let s = comb(s, args.get("amount"))?;
-                Ok(hsla_to_rgba(h * Rational::new(1, 360), s, l, *alpha))
+                Ok(hsla_to_rgba(turns(h), s, l, *alpha))
             }
             v => Err(Error::badarg("color", v)),
         }
     });
-    def!(f, lighten(color, amount), |args: &Scope| {
+    def!(f, lighten(color, amount), |args: &dyn Scope| {
         fn comb(orig: Rational, x: Value) -> Result<Rational, Error> {
             match x {
                 Value::Null => Ok(orig),
-                x => Ok(cap_percentage(orig + to_rational_percent(x)?)),
+                x => {
+                    let x = to_rational_percent(x)?;
+                    Ok(cap_percentage(orig.safe_add(&x)))
+                }
             }
         }
         match &args.get("color") {
             &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
-                let (h, s, l) = rgb_to_hsl(red, green, blue);
+                let (h, s, l) = rgb_to_hsl(red, green, blue)?;
                 let l = comb(l, args.get("amount"))?;
-                Ok(hsla_to_rgba(h * Rational::new(1, 360), s, l, *alpha))
+                Ok(hsla_to_rgba(turns(h), s, l, *alpha))
             }
             v => Err(Error::badarg("color", v)),
         }
     });
-    def!(f, darken(color, amount), |args: &Scope| {
+    def!(f, darken(color, amount), |args: &dyn Scope| {
         fn comb(orig: Rational, x: Value) -> Result<Rational, Error> {
             match x {
                 Value::Null => Ok(orig),
-                x => Ok(cap_percentage(orig - to_rational_percent(x)?)),
+                x => {
+                    let x = to_rational_percent(x)?;
+                    Ok(cap_percentage(orig.safe_sub(&x)))
+                }
             }
         }
         match &args.get("color") {
             &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
-                let (h, s, l) = rgb_to_hsl(red, green, blue);
+                let (h, s, l) = rgb_to_hsl(red, green, blue)?;
                 let l = comb(l, args.get("amount"))?;
-                Ok(hsla_to_rgba(h * Rational::new(1, 360), s, l, *alpha))
+                Ok(hsla_to_rgba(turns(h), s, l, *alpha))
             }
             v => Err(Error::badarg("color", v)),
         }
     });
-    def!(f, hue(color), |args: &Scope| match &args.get("color") {
+    def!(f, hue(color), |args: &dyn Scope| match &args.get("color") {
         &Value::Color(ref red, ref green, ref blue, ref _alpha, _) => {
-            let (h, _s, _l) = rgb_to_hsl(red, green, blue);
-            Ok(Value::Numeric(h, Unit::Deg, false, true))
+            let (h, _s, _l) = rgb_to_hsl(red, green, blue)?;
+            Ok(Value::Numeric(big(&h), Unit::Deg.into(), false, true))
         }
         v => Err(Error::badarg("color", v)),
     });
     def!(f, saturation(color), |args| match &args.get("color") {
         &Value::Color(ref red, ref green, ref blue, ref _alpha, _) => {
-            let (_h, s, _l) = rgb_to_hsl(red, green, blue);
-            Ok(Value::Numeric((s * Rational::from_integer(100)),
-                              Unit::Percent,
+            let (_h, s, _l) = rgb_to_hsl(red, green, blue)?;
+            Ok(Value::Numeric(big(&s.safe_mul(&Rational::from_integer(100))),
+                              Unit::Percent.into(),
                               false,
                               true))
         }
@@ -111,32 +116,61 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
     });
     def!(f, lightness(color), |args| match &args.get("color") {
         &Value::Color(ref red, ref green, ref blue, ref _alpha, _) => {
-            let (_h, _s, l) = rgb_to_hsl(red, green, blue);
-            Ok(Value::Numeric((l * Rational::from_integer(100)),
-                              Unit::Percent,
+            let (_h, _s, l) = rgb_to_hsl(red, green, blue)?;
+            Ok(Value::Numeric(big(&l.safe_mul(&Rational::from_integer(100))),
+                              Unit::Percent.into(),
                               false,
                               true))
         }
         v => Err(Error::badarg("color", v)),
     });
-    def!(f, desaturate(color, amount), |args: &Scope| {
+    def!(f, grayscale(color), |args: &dyn Scope| match args.get("color") {
+        Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
+            let (h, _s, l) = rgb_to_hsl(red, green, blue)?;
+            Ok(hsla_to_rgba(turns(h), Rational::zero(), l, *alpha))
+        }
+        // The css filter function.
+        v @ Value::Numeric(..) => {
+            Ok(Value::Call("grayscale".into(), CallArgs::from_value(v)))
+        }
+        v => Err(Error::badarg("color", &v)),
+    });
+    def!(f, desaturate(color, amount), |args: &dyn Scope| {
         fn comb(orig: Rational, x: Value) -> Result<Rational, Error> {
             match x {
                 Value::Null => Ok(orig),
-                x => Ok(cap_percentage(orig - to_rational_percent(x)?)),
+                x => {
+                    let x = to_rational_percent(x)?;
+                    Ok(cap_percentage(orig.safe_sub(&x)))
+                }
             }
         }
         match &args.get("color") {
             &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
-                let (h, s, l) = rgb_to_hsl(red, green, blue);
+                let (h, s, l) = rgb_to_hsl(red, green, blue)?;
                 let s = comb(s, args.get("amount"))?;
-                Ok(hsla_to_rgba(h * Rational::new(1, 360), s, l, *alpha))
+                Ok(hsla_to_rgba(turns(h), s, l, *alpha))
             }
             v => Err(Error::badarg("color", v)),
         }
     });
 }
 
+/// The `hsl` and `hsla` functions.
+fn hsla(name: &str, s: &dyn Scope) -> Result<Value, Error> {
+    match legacy_args(name,
+                      s,
+                      &["hue", "saturation", "lightness", "alpha"])? {
+        LegacyArgs::Channels(c, alpha) => {
+            Ok(hsla_to_rgba(turns(to_degrees(c[0].clone())?),
+                            to_rational_percent(c[1].clone())?,
+                            to_rational_percent(c[2].clone())?,
+                            alpha))
+        }
+        LegacyArgs::Css(call) => Ok(call),
+    }
+}
+
 pub fn hsla_to_rgba(hue: Rational,
                     sat: Rational,
                     lig: Rational,
@@ -155,28 +189,30 @@ fn hsl_to_rgb(hue: Rational,
         (l, l, l)
     } else {
         fn hue2rgb(p: Rational, q: Rational, t: Rational) -> Rational {
-            let t = t - t.floor();
+            let t = t.safe_sub(&t.floor());
+            let six = Rational::from_integer(6);
             if t < Rational::new(1, 6) {
-                p + (q - p) * Rational::from_integer(6) * t
+                p.safe_add(&q.safe_sub(&p).safe_mul(&six).safe_mul(&t))
             } else if t < Rational::new(1, 2) {
                 q
             } else if t < Rational::new(2, 3) {
-                p +
-                (q - p) * (Rational::new(2, 3) - t) * Rational::from_integer(6)
+                let t = Rational::new(2, 3).safe_sub(&t);
+                p.safe_add(&q.safe_sub(&p).safe_mul(&t).safe_mul(&six))
             } else {
                 p
             }
         }
         let q = if l < Rational::new(1, 2) {
-            l * (Rational::one() + sat)
+            l.safe_mul(&Rational::one().safe_add(&sat))
         } else {
-            l + sat - l * sat
+            l.safe_add(&sat).safe_sub(&l.safe_mul(&sat))
         };
-        let p = Rational::from_integer(2) * l - q;
+        let p = l.safe_add(&l).safe_sub(&q);
+        let third = Rational::new(1, 3);
 
-        (hue2rgb(p, q, hue + Rational::new(1, 3)),
+        (hue2rgb(p, q, hue.safe_add(&third)),
          hue2rgb(p, q, hue),
-         hue2rgb(p, q, hue - Rational::new(1, 3)))
+         hue2rgb(p, q, hue.safe_sub(&third)))
     }
 }
 
@@ -184,38 +220,79 @@ fn hsl_to_rgb(hue: Rational,
 pub fn rgb_to_hsl(red: &Rational,
                   green: &Rational,
                   blue: &Rational)
-                  -> (Rational, Rational, Rational) {
-    let ff = Rational::from_integer(255);
-    let (red, green, blue) = (red / ff, green / ff, blue / ff);
+                  -> Result<(Rational, Rational, Rational), Error> {
+    let ff = Rational::new(1, 255);
+    let (red, green, blue) =
+        (red.safe_mul(&ff), green.safe_mul(&ff), blue.safe_mul(&ff));
     let (max, min, largest) = max_min_largest(red, green, blue);
     let half = Rational::new(1, 2);
-    let mid = (max + min) * half;
+    let mid = max.safe_add(&min).safe_mul(&half);
 
     if max == min {
-        (Rational::zero(), Rational::zero(), mid)
+        Ok((Rational::zero(), Rational::zero(), mid))
     } else {
-        let d = max - min;
+        let d = max.safe_sub(&min);
         let s = if mid > half {
-            d / (Rational::from_integer(2) - max - min)
+            let two = Rational::from_integer(2);
+            d.safe_div(&two.safe_sub(&max).safe_sub(&min))?
         } else {
-            d / (max + min)
+            d.safe_div(&max.safe_add(&min))?
         };
         let h = match largest {
             0 => {
-                (green - blue) / d +
-                if green < blue {
+                green.safe_sub(&blue).safe_div(&d)?.safe_add(&if green < blue {
                     Rational::from_integer(6)
                 } else {
                     Rational::zero()
-                }
+                })
             }
-            1 => (blue - red) / d + Rational::from_integer(2),
-            _ => (red - green) / d + Rational::from_integer(4),
-        } * Rational::new(360, 6);
-        (h, s, mid)
+            1 => {
+                blue.safe_sub(&red)
+                    .safe_div(&d)?
+                    .safe_add(&Rational::from_integer(2))
+            }
+            _ => {
+                red.safe_sub(&green)
+                    .safe_div(&d)?
+                    .safe_add(&Rational::from_integer(4))
+            }
+        };
+        Ok((h.safe_mul(&Rational::from_integer(60)), s, mid))
     }
 }
 
+/// Convert hue (in turns) / whiteness (0 .. 1) / blackness (0 .. 1)
+/// and alpha to a color.
+pub fn hwba_to_rgba(hue: Rational,
+                    w: Rational,
+                    b: Rational,
+                    a: Rational)
+                    -> Result<Value, Error> {
+    let one = Rational::one();
+    let wb = w.safe_add(&b);
+    if wb >= one {
+        let gray = frac_to_int(w.safe_div(&wb)?);
+        Ok(Value::rgba(gray, gray, gray, a))
+    } else {
+        let (r, g, bl) = hsl_to_rgb(hue, one, Rational::new(1, 2));
+        let f = |c: Rational| frac_to_int(c.safe_mul(&one.safe_sub(&wb))
+                                               .safe_add(&w));
+        Ok(Value::rgba(f(r), f(g), f(bl), a))
+    }
+}
+
+/// Convert rgb (0 .. 255) to hue (degrees) / whiteness (0 .. 1) /
+/// blackness (0 .. 1)
+pub fn rgb_to_hwb(red: &Rational,
+                  green: &Rational,
+                  blue: &Rational)
+                  -> Result<(Rational, Rational, Rational), Error> {
+    let ff = Rational::new(1, 255);
+    let (h, _s, _l) = rgb_to_hsl(red, green, blue)?;
+    let (max, min, _) = max_min_largest(*red, *green, *blue);
+    Ok((h, min.safe_mul(&ff), Rational::one().safe_sub(&max.safe_mul(&ff))))
+}
+
 // Find which of three numbers are largest and smallest
 fn max_min_largest(a: Rational,
                    b: Rational,
@@ -227,9 +304,14 @@ fn max_min_largest(a: Rational,
     (max.0, min.0, max.1)
 }
 
+/// Convert an angle in degrees to turns.
+pub fn turns(deg: Rational) -> Rational {
+    deg.safe_mul(&Rational::new(1, 360))
+}
+
 /// Convert a value in the 0 .. 1 range to u8
 fn frac_to_int(v: Rational) -> Rational {
-    cap_u8(Rational::from_integer(255) * v)
+    cap_u8(Rational::from_integer(255).safe_mul(&v))
 }
 
 fn cap_u8(n: Rational) -> Rational {
@@ -243,23 +325,28 @@ fn cap_u8(n: Rational) -> Rational {
     }
 }
 
-fn to_rational(v: Value) -> Result<Rational, Error> {
-    match v {
-        Value::Numeric(v, ..) => Ok(v),
-        v => Err(Error::badarg("number", &v)),
+/// Get a hue in degrees, from a number with any angle unit.
+pub fn to_degrees(v: Value) -> Result<Rational, Error> {
+    match hue_value(&v, "hue")? {
+        Some(deg) => Ok(deg),
+        None => Err(Error::badarg("number", &v)),
     }
 }
 
 /// Gets a percentage as a fraction 0 .. 1.
 /// If v is not a percentage, keep it as it is.
 fn to_rational_percent(v: Value) -> Result<Rational, Error> {
+    let hundredth = Rational::new(1, 100);
     match v {
-        Value::Numeric(v, Unit::Percent, ..) => Ok(v * Rational::new(1, 100)),
-        Value::Numeric(v, ..) => {
+        Value::Numeric(ref v, ref u, ..) if u.is_percent() => {
+            Ok(from_big(v).safe_mul(&hundredth))
+        }
+        Value::Numeric(ref v, ..) => {
+            let v = from_big(v);
             if v <= Rational::new(1, 1) {
                 Ok(v)
             } else {
-                Ok(v * Rational::new(1, 100))
+                Ok(v.safe_mul(&hundredth))
             }
         }
         v => Err(Error::badarg("number", &v)),
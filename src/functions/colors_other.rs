@@ -1,10 +1,12 @@
 use super::{Error, SassFunction};
-use super::colors_hsl::{hsla_to_rgba, rgb_to_hsl};
-use num_rational::Rational;
+use super::colors_hsl::{hsla_to_rgba, hwba_to_rgba, rgb_to_hsl, rgb_to_hwb,
+                        to_degrees, turns};
+use formalargs::CallArgs;
+use num_rational::{BigRational, Rational};
 use num_traits::{One, Signed, Zero};
 use std::collections::BTreeMap;
-use unit::Unit;
-use value::{Quotes, Value};
+use unit::UnitSet;
+use value::{Quotes, SafeArith, Value, big, from_big};
 use variablescope::Scope;
 
 pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
@@ -16,74 +18,126 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
                       hue,
                       saturation,
                       lightness,
+                      whiteness,
+                      blackness,
                       alpha),
-         |s: &Scope| match &s.get("color") {
-             &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
-                 let h_adj = s.get("hue");
-                 let s_adj = s.get("saturation");
-                 let l_adj = s.get("lightness");
-                 let a_adj = s.get("alpha");
-                 if h_adj.is_null() && s_adj.is_null() && l_adj.is_null() {
-                     Ok(Value::rgba(c_add(*red, s.get("red"))?,
-                                    c_add(*green, s.get("green"))?,
-                                    c_add(*blue, s.get("blue"))?,
-                                    c_add(*alpha, a_adj)?))
-                 } else {
-                     let (h, s, l) = rgb_to_hsl(red, green, blue);
-                     Ok(hsla_to_rgba(c_add(h, h_adj)? * Rational::new(1, 360),
-                                     sl_add(s, s_adj)?,
-                                     sl_add(l, l_adj)?,
-                                     c_add(*alpha, a_adj)?))
-                 }
+         |s: &dyn Scope| match s.get("color") {
+             Value::Color(r, g, b, a, _) => {
+                 let add = |v: Rational, d: Option<Rational>| match d {
+                     Some(d) => v.safe_add(&d),
+                     None => v,
+                 };
+                 let a = add(a, num_arg(s, "alpha", -1, 1)?);
+                 Ok(match channel_space(s)? {
+                        Space::Rgb => {
+                            Value::rgba(add(r, num_arg(s, "red", -255, 255)?),
+                                        add(g, num_arg(s, "green", -255, 255)?),
+                                        add(b, num_arg(s, "blue", -255, 255)?),
+                                        a)
+                        }
+                        Space::Hsl => {
+                            let (h, sat, l) = rgb_to_hsl(&r, &g, &b)?;
+                            let sat = add(sat, pct_arg(s, "saturation", -100)?);
+                            let l = add(l, pct_arg(s, "lightness", -100)?);
+                            hsla_to_rgba(turns(add(h, hue_arg(s)?)),
+                                         cap_fraction(sat),
+                                         cap_fraction(l),
+                                         a)
+                        }
+                        Space::Hwb => {
+                            let (h, w, bl) = rgb_to_hwb(&r, &g, &b)?;
+                            let w = add(w, pct_arg(s, "whiteness", -100)?);
+                            let bl = add(bl, pct_arg(s, "blackness", -100)?);
+                            hwba_to_rgba(turns(add(h, hue_arg(s)?)),
+                                         cap_fraction(w),
+                                         cap_fraction(bl),
+                                         a)?
+                        }
+                        Space::None => Value::rgba(r, g, b, a),
+                    })
              }
-             v => Err(Error::badarg("color", v)),
+             v => Err(Error::badarg("color", &v)),
          });
     def!(f,
          scale_color(color,
                      red,
                      green,
                      blue,
-                     hue,
                      saturation,
                      lightness,
+                     whiteness,
+                     blackness,
                      alpha),
-         |s: &Scope| match &s.get("color") {
-             &Value::Color(ref red, ref green, ref blue, ref alpha, _) => {
-                 let h_adj = s.get("hue");
-                 let s_adj = s.get("saturation");
-                 let l_adj = s.get("lightness");
-                 let a_adj = s.get("alpha");
-                 if h_adj.is_null() && s_adj.is_null() && l_adj.is_null() {
-                     Ok(Value::rgba(c_comb(*red, s.get("red"))?,
-                                    c_comb(*green, s.get("green"))?,
-                                    c_comb(*blue, s.get("blue"))?,
-                                    comb(*alpha, a_adj)?))
-                 } else {
-                     let (h, s, l) = rgb_to_hsl(red, green, blue);
-                     Ok(hsla_to_rgba(comb(h, h_adj)? * Rational::new(1, 360),
-                                     comb(s, s_adj)?,
-                                     comb(l, l_adj)?,
-                                     comb(*alpha, a_adj)?))
-                 }
+         |s: &dyn Scope| match s.get("color") {
+             Value::Color(r, g, b, a, _) => {
+                 let ff = Rational::from_integer(255);
+                 let one = Rational::one();
+                 let a = scale(a, &one, scale_arg(s, "alpha")?);
+                 Ok(match channel_space(s)? {
+                        Space::Rgb => {
+                            Value::rgba(scale(r, &ff, scale_arg(s, "red")?),
+                                        scale(g, &ff, scale_arg(s, "green")?),
+                                        scale(b, &ff, scale_arg(s, "blue")?),
+                                        a)
+                        }
+                        Space::Hsl => {
+                            let (h, sat, l) = rgb_to_hsl(&r, &g, &b)?;
+                            hsla_to_rgba(turns(h),
+                                         scale(sat,
+                                               &one,
+                                               scale_arg(s, "saturation")?),
+                                         scale(l,
+                                               &one,
+                                               scale_arg(s, "lightness")?),
+                                         a)
+                        }
+                        Space::Hwb => {
+                            let (h, w, bl) = rgb_to_hwb(&r, &g, &b)?;
+                            hwba_to_rgba(turns(h),
+                                         scale(w,
+                                               &one,
+                                               scale_arg(s, "whiteness")?),
+                                         scale(bl,
+                                               &one,
+                                               scale_arg(s, "blackness")?),
+                                         a)?
+                        }
+                        Space::None => Value::rgba(r, g, b, a),
+                    })
              }
-             v => Err(Error::badarg("color", v)),
+             v => Err(Error::badarg("color", &v)),
          });
 
-    fn opacity(color: Value) -> Result<Value, Error> {
-        match color {
-            Value::Color(_r, _g, _b, a, _) => {
-                Ok(Value::Numeric(a, Unit::None, false, true))
+    def!(f, opacity(color), |s| match s.get("color") {
+        Value::Color(_r, _g, _b, a, _) => {
+            Ok(Value::Numeric(big(&a), UnitSet::scalar(), false, true))
+        }
+        // The css filter function.
+        v @ Value::Numeric(..) => {
+            Ok(Value::Call("opacity".into(), CallArgs::from_value(v)))
+        }
+        v => Err(Error::badarg("color", &v)),
+    });
+    def!(f, alpha(color), |s| match s.get("color") {
+        Value::Color(_r, _g, _b, a, _) => {
+            Ok(Value::Numeric(big(&a), UnitSet::scalar(), false, true))
+        }
+        // The old IE filter syntax, as in `alpha(opacity=50)`.
+        v @ Value::Literal(_, Quotes::None) => {
+            if v.to_string().contains('=') {
+                Ok(Value::Call("alpha".into(), CallArgs::from_value(v)))
+            } else {
+                Err(Error::badarg("color", &v))
             }
-            v => Err(Error::badarg("color", &v)),
         }
-    }
-    f.insert("opacity", func2!(opacity(color)));
-    f.insert("alpha", func2!(opacity(color)));
+        v => Err(Error::badarg("color", &v)),
+    });
 
     fn fade_in(color: Value, amount: Value) -> Result<Value, Error> {
         match color {
             Value::Color(red, green, blue, alpha, _) => {
-                Ok(Value::rgba(red, green, blue, alpha + to_rational(amount)?))
+                let alpha = alpha.safe_add(&to_rational(amount)?);
+                Ok(Value::rgba(red, green, blue, alpha))
             }
             v => Err(Error::badarg("color", &v)),
         }
@@ -94,7 +148,8 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
     fn fade_out(color: Value, amount: Value) -> Result<Value, Error> {
         match color {
             Value::Color(red, green, blue, alpha, _) => {
-                Ok(Value::rgba(red, green, blue, alpha - to_rational(amount)?))
+                let alpha = alpha.safe_sub(&to_rational(amount)?);
+                Ok(Value::rgba(red, green, blue, alpha))
             }
             v => Err(Error::badarg("color", &v)),
         }
@@ -110,25 +165,42 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
                       hue,
                       saturation,
                       lightness,
+                      whiteness,
+                      blackness,
                       alpha),
-         |s: &Scope| match s.get("color") {
-             Value::Color(red, green, blue, alpha, _) => {
-                 let h_adj = s.get("hue");
-                 let s_adj = s.get("saturation");
-                 let l_adj = s.get("lightness");
-                 let a_adj = s.get("alpha");
-                 if h_adj.is_null() && s_adj.is_null() && l_adj.is_null() {
-                     Ok(Value::rgba(c_or(red, s.get("red"))?,
-                                    c_or(green, s.get("green"))?,
-                                    c_or(blue, s.get("blue"))?,
-                                    a_or(alpha, s.get("alpha"))?))
-                 } else {
-                     let (h, s, l) = rgb_to_hsl(&red, &green, &blue);
-                     Ok(hsla_to_rgba(a_or(h, h_adj)? * Rational::new(1, 360),
-                                     sl_or(s, s_adj)?,
-                                     sl_or(l, l_adj)?,
-                                     a_or(alpha, a_adj)?))
-                 }
+         |s: &dyn Scope| match s.get("color") {
+             Value::Color(r, g, b, a, _) => {
+                 let a = num_arg(s, "alpha", 0, 1)?.unwrap_or(a);
+                 Ok(match channel_space(s)? {
+                        Space::Rgb => {
+                            Value::rgba(num_arg(s, "red", 0, 255)?
+                                            .unwrap_or(r),
+                                        num_arg(s, "green", 0, 255)?
+                                            .unwrap_or(g),
+                                        num_arg(s, "blue", 0, 255)?
+                                            .unwrap_or(b),
+                                        a)
+                        }
+                        Space::Hsl => {
+                            let (h, sat, l) = rgb_to_hsl(&r, &g, &b)?;
+                            hsla_to_rgba(turns(hue_arg(s)?.unwrap_or(h)),
+                                         pct_arg(s, "saturation", 0)?
+                                             .unwrap_or(sat),
+                                         pct_arg(s, "lightness", 0)?
+                                             .unwrap_or(l),
+                                         a)
+                        }
+                        Space::Hwb => {
+                            let (h, w, bl) = rgb_to_hwb(&r, &g, &b)?;
+                            hwba_to_rgba(turns(hue_arg(s)?.unwrap_or(h)),
+                                         pct_arg(s, "whiteness", 0)?
+                                             .unwrap_or(w),
+                                         pct_arg(s, "blackness", 0)?
+                                             .unwrap_or(bl),
+                                         a)?
+                        }
+                        Space::None => Value::rgba(r, g, b, a),
+                    })
              }
              v => Err(Error::badarg("color", &v)),
          });
@@ -137,8 +209,9 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
             fn to_byte(v: Rational) -> u8 {
                 v.round().to_integer() as u8
             }
+            let alpha = alpha.safe_mul(&Rational::new(255, 1));
             Ok(Value::Literal(format!("#{:02X}{:02X}{:02X}{:02X}",
-                                      to_byte(alpha * Rational::new(255, 1)),
+                                      to_byte(alpha),
                                       to_byte(r),
                                       to_byte(g),
                                       to_byte(b)),
@@ -148,80 +221,156 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
     });
 }
 
-fn c_add(orig: Rational, x: Value) -> Result<Rational, Error> {
-    match x {
-        Value::Null => Ok(orig),
-        x => Ok(orig + to_rational(x)?),
+/// The color space of the channels given to `adjust-color`,
+/// `scale-color` or `change-color`.
+enum Space {
+    Rgb,
+    Hsl,
+    Hwb,
+    /// Only alpha (or nothing) is given.
+    None,
+}
+
+/// Find the color space of the given channel arguments.
+///
+/// Channels from different spaces may not be combined.  A hue
+/// alone is a change in the hsl space.
+fn channel_space(s: &dyn Scope) -> Result<Space, Error> {
+    let given = |names: &[&str]| names.iter().any(|n| !s.get(n).is_null());
+    let rgb = given(&["red", "green", "blue"]);
+    let hsl = given(&["saturation", "lightness"]);
+    let hwb = given(&["whiteness", "blackness"]);
+    let hue = given(&["hue"]);
+    let mixed = |a: &str, b: &str| {
+        Err(Error::BadArguments(format!("{} parameters may not be passed \
+                                         along with {} parameters.",
+                                        a,
+                                        b)))
+    };
+    if rgb && (hsl || hue) {
+        mixed("RGB", "HSL")
+    } else if rgb && hwb {
+        mixed("RGB", "HWB")
+    } else if hsl && hwb {
+        mixed("HSL", "HWB")
+    } else if rgb {
+        Ok(Space::Rgb)
+    } else if hwb {
+        Ok(Space::Hwb)
+    } else if hsl || hue {
+        Ok(Space::Hsl)
+    } else {
+        Ok(Space::None)
     }
 }
-fn sl_add(orig: Rational, x: Value) -> Result<Rational, Error> {
-    match x {
-        Value::Null => Ok(orig),
-        x => Ok(orig + to_rational_percent(x)?),
+
+/// Get an optional number argument, that must be within `min .. max`.
+fn num_arg(s: &dyn Scope,
+           name: &str,
+           min: isize,
+           max: isize)
+           -> Result<Option<Rational>, Error> {
+    match s.get(name) {
+        Value::Null => Ok(None),
+        Value::Numeric(v, ..) => {
+            if v < BigRational::from_integer(min.into()) ||
+               v > BigRational::from_integer(max.into()) {
+                Err(out_of_range(name, &v, min, max, ""))
+            } else {
+                Ok(Some(from_big(&v)))
+            }
+        }
+        v => Err(Error::badarg("number", &v)),
     }
 }
 
-fn c_or(orig: Rational, x: Value) -> Result<Rational, Error> {
-    match x {
-        Value::Null => Ok(orig),
-        x => Ok(cap_u8(to_rational(x)?)),
+/// Get an optional percentage argument as a fraction.
+///
+/// The percentage must be within `min` and 100, and a unitless number
+/// is taken as a percentage.
+fn pct_arg(s: &dyn Scope,
+           name: &str,
+           min: isize)
+           -> Result<Option<Rational>, Error> {
+    match s.get(name) {
+        Value::Null => Ok(None),
+        Value::Numeric(v, ref u, ..) if u.is_none() || u.is_percent() => {
+            if v < BigRational::from_integer(min.into()) ||
+               v > BigRational::from_integer(100.into()) {
+                Err(out_of_range(name, &v, min, 100, "%"))
+            } else {
+                Ok(Some(from_big(&v).safe_mul(&Rational::new(1, 100))))
+            }
+        }
+        v => Err(Error::badarg("percentage", &v)),
     }
 }
-fn a_or(orig: Rational, x: Value) -> Result<Rational, Error> {
-    match x {
-        Value::Null => Ok(orig),
-        x => to_rational(x),
+
+/// Get an optional scale argument (-100% .. 100%) as a fraction.
+fn scale_arg(s: &dyn Scope, name: &str) -> Result<Option<Rational>, Error> {
+    match s.get(name) {
+        ref v @ Value::Numeric(_, ref u, ..) if !u.is_percent() => {
+            Err(Error::BadArguments(format!("${}: Expected {} to have unit \
+                                             \"%\".",
+                                            name,
+                                            v)))
+        }
+        _ => pct_arg(s, name, -100),
     }
 }
-fn sl_or(orig: Rational, x: Value) -> Result<Rational, Error> {
-    match x {
-        Value::Null => Ok(orig),
-        x => to_rational_percent(x),
+
+/// Get an optional hue argument, in degrees.
+fn hue_arg(s: &dyn Scope) -> Result<Option<Rational>, Error> {
+    match s.get("hue") {
+        Value::Null => Ok(None),
+        v => Ok(Some(to_degrees(v)?)),
     }
 }
 
-fn comb(orig: Rational, x: Value) -> Result<Rational, Error> {
-    match x {
-        Value::Null => Ok(orig),
-        x => {
-            let x = to_rational_percent(x)?;
-            Ok(if x.is_positive() {
-                   orig + (Rational::one() - orig) * x
-               } else {
-                   orig - orig * x.abs()
-               })
+fn out_of_range(name: &str,
+                v: &BigRational,
+                min: isize,
+                max: isize,
+                unit: &str)
+                -> Error {
+    Error::BadArguments(format!("${}: Expected {}{} to be within {}{} and \
+                                 {}{}.",
+                                name,
+                                Value::Numeric(v.clone(),
+                                               UnitSet::scalar(),
+                                               false,
+                                               true),
+                                unit,
+                                min,
+                                unit,
+                                max,
+                                unit))
+}
+
+/// Scale `v` towards zero or `max` by `by` (-1 .. 1).
+fn scale(v: Rational, max: &Rational, by: Option<Rational>) -> Rational {
+    match by {
+        Some(ref by) if by.is_positive() => {
+            v.safe_add(&max.safe_sub(&v).safe_mul(by))
         }
+        Some(ref by) => v.safe_add(&v.safe_mul(by)),
+        None => v,
     }
 }
-fn c_comb(orig: Rational, x: Value) -> Result<Rational, Error> {
-    let ff = Rational::new(255, 1);
-    Ok(ff * comb(orig / ff, x)?)
-}
 
-fn cap_u8(n: Rational) -> Rational {
-    let ff = Rational::new(255, 1);
-    if n > ff {
-        ff
-    } else if n.is_negative() {
+fn cap_fraction(r: Rational) -> Rational {
+    if r.is_negative() {
         Rational::zero()
+    } else if r > Rational::one() {
+        Rational::one()
     } else {
-        n
+        r
     }
 }
 
 fn to_rational(v: Value) -> Result<Rational, Error> {
     match v {
-        Value::Numeric(v, ..) => Ok(v),
-        v => Err(Error::badarg("number", &v)),
-    }
-}
-
-/// Gets a percentage as a fraction 0 .. 1.
-/// If v is not a percentage, keep it as it is.
-fn to_rational_percent(v: Value) -> Result<Rational, Error> {
-    match v {
-        Value::Numeric(v, Unit::Percent, ..) => Ok(v * Rational::new(1, 100)),
-        Value::Numeric(v, ..) => Ok(v),
+        Value::Numeric(ref v, ..) => Ok(from_big(v)),
         v => Err(Error::badarg("number", &v)),
     }
 }
@@ -1,33 +1,18 @@
 use super::{Error, SassFunction};
+use super::colors_css4::{LegacyArgs, alpha_value, css_call, is_css_only,
+                         legacy_args};
 use num_rational::Rational;
 use num_traits::One;
 use std::collections::BTreeMap;
-use unit::Unit;
-use value::Value;
+use unit::UnitSet;
+use value::{SafeArith, Value, big, from_big};
+use variablescope::Scope;
 
 pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
-    def!(f, rgb(red, green, blue), |s| {
-        Ok(Value::rgba(to_int(s.get("red"))?,
-                       to_int(s.get("green"))?,
-                       to_int(s.get("blue"))?,
-                       Rational::one()))
-    });
-    def!(f, rgba(red, green, blue, alpha, color), |s| {
-        let a = s.get("alpha");
-        let red = s.get("red");
-        let red = if red.is_null() { s.get("color") } else { red };
-        if let Value::Color(r, g, b, _, _) = red {
-            let a = if a.is_null() { s.get("green") } else { a };
-            Ok(Value::rgba(r, g, b, to_rational(a)?))
-        } else {
-            Ok(Value::rgba(to_int(red)?,
-                           to_int(s.get("green"))?,
-                           to_int(s.get("blue"))?,
-                           to_rational(a)?))
-        }
-    });
+    def!(f, rgb(red, green, blue, alpha, color), |s| rgba("rgb", s));
+    def!(f, rgba(red, green, blue, alpha, color), |s| rgba("rgba", s));
     fn num(v: &Rational) -> Result<Value, Error> {
-        Ok(Value::Numeric(*v, Unit::None, false, true))
+        Ok(Value::Numeric(big(v), UnitSet::scalar(), false, true))
     }
     def!(f, red(color), |s| match &s.get("color") {
         &Value::Color(ref red, _, _, _, _) => num(red),
@@ -49,15 +34,17 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
                 &Value::Color(ref r2, ref g2, ref b2, ref a2, _),
                 &Value::Numeric(ref w, ref wu, ..)) =
             (&color1, &color2, &weight) {
-            let w = if wu == &Unit::Percent {
-                w / Rational::from_integer(100)
+            let w = from_big(w);
+            let w = if wu.is_percent() {
+                w.safe_div(&Rational::from_integer(100))?
             } else {
-                *w
+                w
             };
             let one = Rational::one();
-            let w2 = one - (one - w * a1) * a2;
+            let w2 = one.safe_sub(&one.safe_sub(&w.safe_mul(a1)).safe_mul(a2));
             fn m(v1: &Rational, v2: &Rational, w: Rational) -> Rational {
-                *v1 * w + *v2 * (Rational::one() - w)
+                v1.safe_mul(&w)
+                    .safe_add(&v2.safe_mul(&Rational::one().safe_sub(&w)))
             }
             Ok(Value::rgba(m(r1, r2, w2),
                            m(g1, g2, w2),
@@ -77,17 +64,39 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
     });
 }
 
-fn to_int(v: Value) -> Result<Rational, Error> {
-    match v {
-        Value::Numeric(v, Unit::Percent, ..) => Ok(Rational::new(255, 100) * v),
-        Value::Numeric(v, ..) => Ok(v),
-        v => Err(Error::badarg("number", &v)),
+/// The `rgb` and `rgba` functions.
+///
+/// Either the channels are given, or a color and an alpha value.
+fn rgba(name: &str, s: &dyn Scope) -> Result<Value, Error> {
+    let red = s.get("red");
+    let red = if red.is_null() { s.get("color") } else { red };
+    if let Value::Color(r, g, b, _, _) = red.clone() {
+        let a = s.get("alpha");
+        let a = if a.is_null() { s.get("green") } else { a };
+        if is_css_only(&a) {
+            return Ok(css_call(name, vec![red, a]));
+        }
+        return match alpha_value(&a)? {
+                   Some(a) => Ok(Value::rgba(r, g, b, a)),
+                   None => Err(Error::badarg("number", &a)),
+               };
+    }
+    match legacy_args(name, s, &["red", "green", "blue", "alpha"])? {
+        LegacyArgs::Channels(c, alpha) => {
+            let mut c = c.into_iter();
+            let mut channel = || to_int(c.next().unwrap_or(Value::Null));
+            Ok(Value::rgba(channel()?, channel()?, channel()?, alpha))
+        }
+        LegacyArgs::Css(call) => Ok(call),
     }
 }
 
-fn to_rational(v: Value) -> Result<Rational, Error> {
+fn to_int(v: Value) -> Result<Rational, Error> {
     match v {
-        Value::Numeric(v, ..) => Ok(v),
+        Value::Numeric(ref v, ref u, ..) if u.is_percent() => {
+            Ok(Rational::new(255, 100).safe_mul(&from_big(v)))
+        }
+        Value::Numeric(ref v, ..) => Ok(from_big(v)),
         v => Err(Error::badarg("number", &v)),
     }
 }
@@ -41,33 +41,52 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
         match (&s.get("number1"), &s.get("number2")) {
             (&Value::Numeric(_, ref u1, ..),
              &Value::Numeric(_, ref u2, ..)) => {
-                // TODO e.g. cm and mm are comparable too!
-                // Simply comparig the dimension is not enough, though,
-                // as e.g em and vh is not comparable.
-                Ok(Value::bool(u1 == u2))
+                // The same rule as for addition and comparison.
+                Ok(Value::bool(u1.is_none() || u2.is_none() ||
+                               u1.scale_to(u2).is_some()))
             }
             (v1, v2) => Err(Error::badargs(&["number", "number"], &[v1, v2])),
         }
     });
-    def_va!(f, call(name, args), |s: &Scope| match &s.get("name") {
-        &Value::Literal(ref name, _) => {
-            // Ok, the spec says using a string for call is deprecated.
-            // Even though I have no other way implemented ...
-            dep_warn!("Passing a string to call() is deprecated and \
-                       will be illegal");
-            let args = CallArgs::from_value(s.get("args"));
-            match s.call_function(name, &args) {
-                Some(value) => Ok(value),
-                None => {
-                    if let Some(function) = get_builtin_function(name) {
-                        function.call(s, &args)
-                    } else {
-                        Ok(Value::Call(name.clone(), args.xyzzy(s)))
+    def!(f, get_function(name, css = b"false"), |s| match s.get("name") {
+        Value::Literal(name, _) => {
+            if s.get("css").is_true() {
+                Ok(Value::Function(name, None))
+            } else if let Some(function) = s.get_function(&name) {
+                Ok(Value::Function(name, Some(function.clone())))
+            } else {
+                Err(Error::BadArguments(format!("Function not found: {}",
+                                                name)))
+            }
+        }
+        v => Err(Error::badarg("string", &v)),
+    });
+    def_va!(f, call(function, args), |s: &dyn Scope| {
+        let args = CallArgs::from_value(s.get("args"));
+        match s.get("function") {
+            Value::Function(_, Some(function)) => function.call(s, &args),
+            Value::Function(name, None) => {
+                Ok(Value::Call(name, args.xyzzy(s)?))
+            }
+            Value::Literal(name, _) => {
+                dep_warn!(s,
+                          "Passing a string to call() is deprecated and \
+                           will be illegal in Dart Sass 2.0.0.\n\n\
+                           Recommendation: call(get-function({}))",
+                          Value::Literal(name.clone(), Quotes::Double));
+                match s.call_function(&name, &args)? {
+                    Some(value) => Ok(value),
+                    None => {
+                        if let Some(function) = get_builtin_function(&name) {
+                            function.call(s, &args)
+                        } else {
+                            Ok(Value::Call(name, args.xyzzy(s)?))
+                        }
                     }
                 }
             }
+            v => Err(Error::badarg("function", &v)),
         }
-        v => Err(Error::badarg("string", v)),
     });
 }
 
@@ -1,46 +1,75 @@
 use super::{Error, SassFunction};
 use std::collections::BTreeMap;
-use value::{ListSeparator, Value};
+use value::{ListSeparator, Quotes, Value};
 
 pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
     def!(f, length(list), |s| match s.get("list") {
-        Value::List(v, _) => Ok(Value::scalar(v.len() as isize)),
+        Value::List(v, ..) => Ok(Value::scalar(v.len() as isize)),
+        Value::ArgList(v, _) => Ok(Value::scalar(v.len() as isize)),
+        Value::Map(map) => Ok(Value::scalar(map.len() as isize)),
         v => Err(Error::badarg("list", &v)),
     });
     def!(f, nth(list, n), |s| {
-        let n = s.get("n").integer_value()?;
-        match s.get("list") {
-            Value::List(list, _) => Ok(list[n as usize - 1].clone()),
-            v => Err(Error::badarg("list", &v)),
-        }
+        let (list, ..) = get_list(s.get("list"));
+        let i = list_index(s.get("n"), list.len())?;
+        Ok(list[i].clone())
     });
-    def!(f, append(list, val, separator), |s| {
-        let (mut list, sep) = match s.get("list") {
-            Value::List(v, s) => (v, Some(s)),
-            v => (vec![v], None),
-        };
-        let sep = match (s.get("separator"), sep) {
-            (Value::Literal(ref s, _), _) if s == "comma" => {
-                ListSeparator::Comma
-            }
-            (Value::Literal(ref s, _), _) if s == "space" => {
-                ListSeparator::Space
-            }
-            (_, s) => s.unwrap_or(ListSeparator::Space),
-        };
+    def!(f, set_nth(list, n, value), |s| {
+        let (mut list, sep, bracketed) = get_list(s.get("list"));
+        let i = list_index(s.get("n"), list.len())?;
+        list[i] = s.get("value");
+        Ok(Value::List(list, sep.unwrap_or(ListSeparator::Space), bracketed))
+    });
+    def!(f, append(list, val, separator = b"auto"), |s| {
+        let (mut list, sep, bracketed) = get_list(s.get("list"));
+        let sep = get_separator(s.get("separator"))?
+            .or(sep)
+            .unwrap_or(ListSeparator::Space);
         list.push(match s.get("val") {
-                      Value::List(v, s) => {
-                          Value::Paren(Box::new(Value::List(v, s)))
+                      Value::List(v, s, false) => {
+                          Value::Paren(Box::new(Value::List(v, s, false)))
                       }
                       v => v,
                   });
-        Ok(Value::List(list, sep))
+        Ok(Value::List(list, sep, bracketed))
+    });
+    def!(f,
+         join(list1, list2, separator = b"auto", bracketed = b"auto"),
+         |s| {
+             let (mut list1, sep1, bracketed1) = get_list(s.get("list1"));
+             let (list2, sep2, _) = get_list(s.get("list2"));
+             let sep = get_separator(s.get("separator"))?
+                 .or(sep1)
+                 .or(sep2)
+                 .unwrap_or(ListSeparator::Space);
+             let bracketed = match s.get("bracketed") {
+                 Value::Literal(ref s, _) if s == "auto" => bracketed1,
+                 b => b.is_true(),
+             };
+             list1.extend(list2);
+             Ok(Value::List(list1, sep, bracketed))
+         });
+    def_va!(f, zip(lists), |s| {
+        let lists = s.get("lists")
+            .iter_items()
+            .into_iter()
+            .map(|v| get_list(v).0)
+            .collect::<Vec<_>>();
+        let len = lists.iter().map(|l| l.len()).min().unwrap_or(0);
+        Ok(Value::List((0..len)
+                           .map(|i| {
+                                    Value::List(lists.iter()
+                                                    .map(|l| l[i].clone())
+                                                    .collect(),
+                                                ListSeparator::Space,
+                                                false)
+                                })
+                           .collect(),
+                       ListSeparator::Comma,
+                       false))
     });
     def!(f, index(list, value), |s| {
-        let v = match s.get("list") {
-            Value::List(v, _) => v,
-            v => return Err(Error::badarg("list", &v)),
-        };
+        let (v, ..) = get_list(s.get("list"));
         let value = s.get("value");
         for (i, v) in v.iter().enumerate() {
             if v == &value {
@@ -49,6 +78,96 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
         }
         Ok(Value::Null)
     });
+    def!(f, is_bracketed(list), |s| match s.get("list") {
+        Value::List(_, _, bracketed) => Ok(Value::bool(bracketed)),
+        _ => Ok(Value::False),
+    });
+    def!(f, list_separator(list), |s| {
+        Ok(Value::Literal(match s.get("list") {
+                              Value::List(_, ListSeparator::Comma, _) |
+                              Value::ArgList(..) => "comma",
+                              Value::Map(ref map) if !map.is_empty() => {
+                                  "comma"
+                              }
+                              Value::List(_, ListSeparator::Slash, _) => {
+                                  "slash"
+                              }
+                              _ => "space",
+                          }
+                          .into(),
+                          Quotes::None))
+    });
+}
+
+/// Get the items, separator and brackets of a value as a list.
+///
+/// The separator is `None` when it is undecided, that is for a
+/// single value or a list of less than two items.
+/// A map is a comma-separated list of key-value pairs.
+fn get_list(v: Value) -> (Vec<Value>, Option<ListSeparator>, bool) {
+    match v {
+        Value::List(v, s, b) => {
+            let s = if v.len() > 1 || s == ListSeparator::Comma {
+                Some(s)
+            } else {
+                None
+            };
+            (v, s, b)
+        }
+        Value::ArgList(v, _) => (v, Some(ListSeparator::Comma), false),
+        Value::Map(map) => {
+            let sep = if map.is_empty() {
+                None
+            } else {
+                Some(ListSeparator::Comma)
+            };
+            (Value::Map(map).iter_items(), sep, false)
+        }
+        v => (vec![v], None, false),
+    }
+}
+
+/// Get a `$separator` argument, `None` meaning `auto`.
+fn get_separator(v: Value) -> Result<Option<ListSeparator>, Error> {
+    match v {
+        Value::Literal(ref s, _) if s == "auto" => Ok(None),
+        Value::Literal(ref s, _) if s == "comma" => {
+            Ok(Some(ListSeparator::Comma))
+        }
+        Value::Literal(ref s, _) if s == "space" => {
+            Ok(Some(ListSeparator::Space))
+        }
+        Value::Literal(ref s, _) if s == "slash" => {
+            Ok(Some(ListSeparator::Slash))
+        }
+        _ => {
+            Err(Error::BadArguments("$separator: Must be \"space\", \
+                                     \"comma\", \"slash\", or \"auto\"."
+                                            .into()))
+        }
+    }
+}
+
+/// Convert a sass list index to a rust index into a list of `len`
+/// items.
+///
+/// Sass indexes start at 1, and negative indexes count from the end
+/// of the list.
+fn list_index(n: Value, len: usize) -> Result<usize, Error> {
+    let i = n.integer_value()
+        .map_err(|_| Error::badarg("integer", &n))?;
+    if i == 0 {
+        Err(Error::BadArguments("$n: List index may not be 0.".into()))
+    } else if i.unsigned_abs() > len {
+        Err(Error::BadArguments(format!("$n: Invalid index {} for a list \
+                                         with {} elements.",
+                                        i,
+                                        len)))
+    } else if i > 0 {
+        Ok(i as usize - 1)
+    } else {
+        Ok(len - i.unsigned_abs())
+    }
 }
 
 #[cfg(test)]
@@ -82,4 +201,28 @@ mod test {
         assert_eq!(do_evaluate(&[], b"append((blue, red), green, space);"),
                    "blue red green")
     }
+    #[test]
+    fn is_bracketed() {
+        assert_eq!(do_evaluate(&[], b"is-bracketed([a b]);"), "true");
+        assert_eq!(do_evaluate(&[], b"is-bracketed(a b);"), "false");
+    }
+    #[test]
+    fn list_separator() {
+        assert_eq!(do_evaluate(&[], b"list-separator(1px 2px);"), "space");
+        assert_eq!(do_evaluate(&[], b"list-separator([a, b]);"), "comma");
+        assert_eq!(do_evaluate(&[], b"list-separator(a);"), "space");
+    }
+    #[test]
+    fn nth_negative() {
+        assert_eq!(do_evaluate(&[], b"nth(a b c, -1);"), "c")
+    }
+    #[test]
+    fn join_auto_separator() {
+        assert_eq!(do_evaluate(&[], b"join(a, (b, c));"), "a, b, c")
+    }
+    #[test]
+    fn zip_shortest() {
+        assert_eq!(do_evaluate(&[], b"zip(1px 2px 3px, solid dashed);"),
+                   "1px solid, 2px dashed")
+    }
 }
@@ -41,30 +41,15 @@ macro_rules! def_va {
 macro_rules! func2 {
     ($name:ident( $($arg:ident $( = $value:expr )* ),* )) => {
         func!(($($arg $( = $value )* ),*),
-              |s: &Scope| $name($(s.get(stringify!($arg))),*))
+              |s: &dyn Scope| $name($(s.get(stringify!($arg))),*))
     };
 }
 
 macro_rules! dep_warn {
-    ($first: expr, $($arg:expr),*) => {{
-        use std::io::{Write, stderr};
-        use std::sync::{ONCE_INIT, Once};
-        static WARN: Once = ONCE_INIT;
-        WARN.call_once(|| {
-            writeln!(&mut stderr(),
-                     concat!("DEPRECATION WARNING: ", $first),
-                     $($arg),*)
-                .unwrap();
-        });
+    ($scope: expr, $first: expr, $($arg:expr),*) => {{
+        $scope.get_log().deprecation(&format!($first, $($arg),*));
     }};
-    ($first: expr) => {{
-        use std::io::{Write, stderr};
-        use std::sync::{ONCE_INIT, Once};
-        static WARN: Once = ONCE_INIT;
-        WARN.call_once(|| {
-            writeln!(&mut stderr(),
-                     concat!("DEPRECATION WARNING: ", $first))
-                .unwrap();
-        });
+    ($scope: expr, $first: expr) => {{
+        $scope.get_log().deprecation($first);
     }}
 }
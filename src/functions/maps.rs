@@ -0,0 +1,134 @@
+use super::{Error, SassFunction};
+use ordermap::OrderMap;
+use std::collections::BTreeMap;
+use value::{ListSeparator, Value};
+
+pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
+    def!(f, map_get(map, key), |s| {
+        let map = get_map(s.get("map"))?;
+        Ok(find_key(&map, &s.get("key"))
+               .and_then(|k| map.get(&k).cloned())
+               .unwrap_or(Value::Null))
+    });
+    def!(f, map_merge(map1, map2), |s| {
+        let mut map1 = get_map(s.get("map1"))?;
+        for (key, value) in get_map(s.get("map2"))? {
+            map1.insert(find_key(&map1, &key).unwrap_or(key), value);
+        }
+        Ok(Value::Map(map1))
+    });
+    def_va!(f, map_remove(map, keys), |s| {
+        let mut map = get_map(s.get("map"))?;
+        for key in s.get("keys").iter_items() {
+            if let Some(key) = find_key(&map, &key) {
+                map.remove(&key);
+            }
+        }
+        Ok(Value::Map(map))
+    });
+    def!(f, map_keys(map), |s| {
+        let map = get_map(s.get("map"))?;
+        Ok(Value::List(map.keys().into_iter().cloned().collect(),
+                       ListSeparator::Comma,
+                       false))
+    });
+    def!(f, map_values(map), |s| {
+        let map = get_map(s.get("map"))?;
+        Ok(Value::List(map.values().into_iter().cloned().collect(),
+                       ListSeparator::Comma,
+                       false))
+    });
+    def!(f, map_has_key(map, key), |s| {
+        let map = get_map(s.get("map"))?;
+        Ok(Value::bool(find_key(&map, &s.get("key")).is_some()))
+    });
+    def!(f, keywords(args), |s| match s.get("args") {
+        Value::ArgList(_, keywords) => Ok(Value::Map(keywords)),
+        Value::List(..) => Ok(Value::Map(OrderMap::new())),
+        v => Err(Error::badarg("arglist", &v)),
+    });
+}
+
+/// Get a value as a map.
+///
+/// An empty list, as written `()`, is also an empty map.
+fn get_map(v: Value) -> Result<OrderMap<Value, Value>, Error> {
+    match v {
+        Value::Map(map) => Ok(map),
+        Value::List(ref l, ..) if l.is_empty() => Ok(OrderMap::new()),
+        v => Err(Error::badarg("map", &v)),
+    }
+}
+
+/// Find the key in `map` that is equal to `key`.
+///
+/// As for the sass `==` operator, strings are equal regardless of
+/// quoting.
+fn find_key(map: &OrderMap<Value, Value>, key: &Value) -> Option<Value> {
+    map.keys()
+        .into_iter()
+        .find(|k| match (*k, key) {
+                  (&Value::Literal(ref a, _), &Value::Literal(ref b, _)) => {
+                      a == b
+                  }
+                  (a, b) => a == b,
+              })
+        .cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use variablescope::test::do_evaluate;
+
+    #[test]
+    fn map_get() {
+        assert_eq!(do_evaluate(&[("m", "(foo: 1, bar: 2)")],
+                               b"map-get($m, bar);"),
+                   "2")
+    }
+    #[test]
+    fn map_get_quoted_key() {
+        assert_eq!(do_evaluate(&[("m", "(foo: 1, bar: 2)")],
+                               b"map-get($m, \"foo\");"),
+                   "1")
+    }
+    #[test]
+    fn map_get_missing() {
+        assert_eq!(do_evaluate(&[("m", "(foo: 1)")], b"map-get($m, baz);"),
+                   "")
+    }
+    #[test]
+    fn map_merge() {
+        assert_eq!(do_evaluate(&[("m", "(foo: 1, bar: 2)")],
+                               b"map-merge($m, (bar: 3, baz: 4));"),
+                   "(foo: 1, bar: 3, baz: 4)")
+    }
+    #[test]
+    fn map_remove() {
+        assert_eq!(do_evaluate(&[("m", "(a: 1, b: 2, c: 3)")],
+                               b"map-remove($m, a, c);"),
+                   "(b: 2)")
+    }
+    #[test]
+    fn map_keys() {
+        assert_eq!(do_evaluate(&[("m", "(foo: 1, bar: 2)")],
+                               b"map-keys($m);"),
+                   "foo, bar")
+    }
+    #[test]
+    fn map_values() {
+        assert_eq!(do_evaluate(&[("m", "(foo: 1, bar: 2)")],
+                               b"map-values($m);"),
+                   "1, 2")
+    }
+    #[test]
+    fn map_has_key() {
+        assert_eq!(do_evaluate(&[("m", "(foo: 1)")],
+                               b"map-has-key($m, foo) map-has-key($m, x);"),
+                   "true false")
+    }
+    #[test]
+    fn empty_list_is_map() {
+        assert_eq!(do_evaluate(&[], b"map-merge((), (a: b));"), "(a: b)")
+    }
+}
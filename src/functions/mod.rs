@@ -1,10 +1,12 @@
 use super::{Error, SassItem};
 use formalargs::{CallArgs, FormalArgs};
+use num_rational::BigRational;
 use std::{cmp, fmt};
 use std::collections::BTreeMap;
 use std::sync::Arc;
+use unit::UnitSet;
 use value::Value;
-use variablescope::Scope;
+use variablescope::{GlobalScope, Module, Scope};
 
 #[macro_use]
 mod macros;
@@ -12,10 +14,12 @@ mod macros;
 mod colors_rgb;
 mod colors_hsl;
 mod colors_other;
+mod colors_css4;
 mod introspection;
 mod numbers;
 mod strings;
 mod lists;
+mod maps;
 
 pub fn get_builtin_function(name: &str) -> Option<&'static SassFunction> {
     let name = name.replace("-", "_");
@@ -23,7 +27,121 @@ pub fn get_builtin_function(name: &str) -> Option<&'static SassFunction> {
     FUNCTIONS.get(name)
 }
 
-type BuiltinFn = Fn(&Scope) -> Result<Value, Error> + Send + Sync;
+/// Get a built-in module, as used by `@use "sass:name"`.
+///
+/// The members of the built-in modules are the global builtin
+/// functions, some of them by shorter names.
+pub fn get_builtin_module(name: &str) -> Option<Module> {
+    let members: &[(&str, &str)] = match name {
+        "math" => {
+            &[("abs", "abs"),
+              ("acos", "acos"),
+              ("asin", "asin"),
+              ("atan", "atan"),
+              ("atan2", "atan2"),
+              ("ceil", "ceil"),
+              ("clamp", "clamp"),
+              ("compatible", "comparable"),
+              ("cos", "cos"),
+              ("div", "div"),
+              ("floor", "floor"),
+              ("hypot", "hypot"),
+              ("is-unitless", "is-unitless"),
+              ("log", "log"),
+              ("max", "max"),
+              ("min", "min"),
+              ("percentage", "percentage"),
+              ("pow", "pow"),
+              ("random", "random"),
+              ("round", "round"),
+              ("sin", "sin"),
+              ("sqrt", "sqrt"),
+              ("tan", "tan"),
+              ("unit", "unit")]
+        }
+        "color" => {
+            &[("adjust", "adjust-color"),
+              ("alpha", "alpha"),
+              ("blue", "blue"),
+              ("change", "change-color"),
+              ("complement", "complement"),
+              ("grayscale", "grayscale"),
+              ("green", "green"),
+              ("hue", "hue"),
+              ("hwb", "hwb"),
+              ("ie-hex-str", "ie-hex-str"),
+              ("invert", "invert"),
+              ("lightness", "lightness"),
+              ("mix", "mix"),
+              ("opacity", "opacity"),
+              ("red", "red"),
+              ("saturation", "saturation"),
+              ("scale", "scale-color")]
+        }
+        "list" => {
+            &[("append", "append"),
+              ("index", "index"),
+              ("is-bracketed", "is-bracketed"),
+              ("join", "join"),
+              ("length", "length"),
+              ("nth", "nth"),
+              ("separator", "list-separator"),
+              ("set-nth", "set-nth"),
+              ("zip", "zip")]
+        }
+        "map" => {
+            &[("get", "map-get"),
+              ("has-key", "map-has-key"),
+              ("keys", "map-keys"),
+              ("merge", "map-merge"),
+              ("remove", "map-remove"),
+              ("values", "map-values")]
+        }
+        "string" => {
+            &[("index", "str-index"),
+              ("insert", "str-insert"),
+              ("length", "str-length"),
+              ("quote", "quote"),
+              ("slice", "str-slice"),
+              ("to-lower-case", "to-lower-case"),
+              ("to-upper-case", "to-upper-case"),
+              ("unique-id", "unique-id"),
+              ("unquote", "unquote")]
+        }
+        "meta" => {
+            &[("call", "call"),
+              ("function-exists", "function-exists"),
+              ("get-function", "get-function"),
+              ("global-variable-exists", "global-variable-exists"),
+              ("keywords", "keywords"),
+              ("mixin-exists", "mixin-exists"),
+              ("type-of", "type-of"),
+              ("variable-exists", "variable-exists")]
+        }
+        _ => return None,
+    };
+    let mut scope = GlobalScope::new();
+    for &(member, builtin) in members {
+        if let Some(f) = get_builtin_function(builtin) {
+            scope.define_function(member, f.clone());
+        }
+    }
+    if name == "math" {
+        use std::f64::consts::{E, PI};
+        for &(name, value) in &[("pi", PI), ("e", E)] {
+            let value = Value::Numeric(BigRational::from_float(value)
+                                           .unwrap(),
+                                       UnitSet::scalar(),
+                                       false,
+                                       false);
+            // Evaluating a plain number cannot fail.
+            scope.define(name, &value).unwrap();
+        }
+    }
+    Some(Module::new(scope))
+}
+
+type BuiltinFn = dyn Fn(&dyn Scope) -> Result<Value, Error> + Send + Sync;
 
 /// A function that can be called from a sass value.
 ///
@@ -47,7 +165,9 @@ impl cmp::PartialEq for FuncImpl {
             (&FuncImpl::UserDefined(ref a), &FuncImpl::UserDefined(ref b)) => {
                 a == b
             }
-            // Note: Maybe consider builtins equal if same Arc?
+            (&FuncImpl::Builtin(ref a), &FuncImpl::Builtin(ref b)) => {
+                Arc::ptr_eq(a, b)
+            }
             _ => false,
         }
     }
@@ -80,14 +200,25 @@ impl SassFunction {
         SassFunction { args: args, body: FuncImpl::UserDefined(body) }
     }
 
+    /// True if this function is implemented in rust.
+    pub fn is_builtin(&self) -> bool {
+        match self.body {
+            FuncImpl::Builtin(_) => true,
+            FuncImpl::UserDefined(_) => false,
+        }
+    }
+
     /// Call the function from a given scope and with a given set of
     /// arguments.
-    pub fn call(&self, scope: &Scope, args: &CallArgs) -> Result<Value, Error> {
-        let mut s = self.args.eval(scope, args);
+    pub fn call(&self,
+                scope: &dyn Scope,
+                args: &CallArgs)
+                -> Result<Value, Error> {
+        let mut s = self.args.eval(scope, args)?;
         match self.body {
             FuncImpl::Builtin(ref body) => body(&s),
             FuncImpl::UserDefined(ref body) => {
-                Ok(s.eval_body(body).unwrap_or(Value::Null))
+                Ok(s.eval_body(body)?.unwrap_or(Value::Null))
             }
         }
     }
@@ -110,6 +241,9 @@ lazy_static! {
         strings::register(&mut f);
         numbers::register(&mut f);
         lists::register(&mut f);
+        maps::register(&mut f);
+        colors_css4::register(&mut f);
+        colors_css4::wrap_legacy(&mut f);
         f
     };
 }
@@ -1,10 +1,11 @@
 use super::{Error, SassFunction};
-use num_rational::Rational;
-use num_traits::Signed;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero, pow};
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
-use unit::Unit;
-use value::Value;
+use unit::{Unit, UnitSet};
+use value::{Value, big_to_f64, limit};
+use variablescope::Scope;
 
 pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
     def!(f, abs(number), |s| match s.get("number") {
@@ -20,8 +21,9 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
         v => Err(Error::badarg("number", &v)),
     });
     def!(f, percentage(number), |s| match s.get("number") {
-        Value::Numeric(val, Unit::None, ..) => {
-            Ok(number(val * Rational::from_integer(100), Unit::Percent))
+        Value::Numeric(ref val, ref u, ..) if u.is_none() => {
+            Ok(number(val * BigRational::from_integer(100.into()),
+                      Unit::Percent.into()))
         }
         v => Err(Error::badarg("number", &v)),
     });
@@ -29,29 +31,230 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
         Value::Numeric(val, unit, ..) => Ok(number(val.round(), unit)),
         v => Err(Error::badarg("number", &v)),
     });
-    def_va!(f, max(numbers), |s| match s.get("numbers") {
-        Value::List(v, _) => Ok(find_extreme(&v, Ordering::Greater).clone()),
-        single_value => Ok(single_value),
+    def_va!(f, max(numbers), |s| {
+        let v = s.get("numbers").iter_items();
+        Ok(find_extreme(&v, Ordering::Greater).clone())
     });
-    def_va!(f, min(numbers), |s| match s.get("numbers") {
-        Value::List(v, _) => Ok(find_extreme(&v, Ordering::Less).clone()),
-        single_value => Ok(single_value),
+    def_va!(f, min(numbers), |s| {
+        let v = s.get("numbers").iter_items();
+        Ok(find_extreme(&v, Ordering::Less).clone())
+    });
+    fn is_unitless(number: Value) -> Result<Value, Error> {
+        match number {
+            Value::Numeric(_, ref u, ..) => Ok(Value::bool(u.is_none())),
+            v => Err(Error::badarg("number", &v)),
+        }
+    }
+    f.insert("unitless", func2!(is_unitless(number)));
+    f.insert("is_unitless", func2!(is_unitless(number)));
+    def!(f, div(number1, number2), |s| {
+        match (s.get("number1"), s.get("number2")) {
+            (Value::Numeric(ref a, ref au, ..),
+             Value::Numeric(ref b, ref bu, ..)) => {
+                if b.is_zero() {
+                    return Err(Error::BadArguments("$number2: Division by \
+                                                    zero is not supported."
+                                                           .into()));
+                }
+                let (unit, factor) = au.div(bu);
+                Ok(number(a / b * factor, unit))
+            }
+            (a, b) => Err(Error::badargs(&["number", "number"], &[&a, &b])),
+        }
+    });
+    def!(f, pow(base, exponent), |s| {
+        let base = unitless_number(s, "base")?;
+        let exponent = unitless_number(s, "exponent")?;
+        let negative = exponent.is_negative();
+        match exponent.to_integer().abs().to_usize() {
+            Some(e) if exponent.is_integer() && e <= MAX_EXACT_POW &&
+                       !(negative && base.is_zero()) => {
+                let result = pow(base, e);
+                Ok(number(if negative { result.recip() } else { result },
+                          UnitSet::scalar()))
+            }
+            _ => {
+                float(big_to_f64(&base).powf(big_to_f64(&exponent)),
+                      UnitSet::scalar())
+            }
+        }
+    });
+    def!(f, sqrt(number), |s| {
+        float(unitless(s, "number")?.sqrt(), UnitSet::scalar())
+    });
+    def!(f, log(number, base), |s| {
+        let number = unitless(s, "number")?;
+        let result = match s.get("base") {
+            Value::Null => number.ln(),
+            _ => number.log(unitless(s, "base")?),
+        };
+        float(result, UnitSet::scalar())
+    });
+    def!(f, sin(number), |s| {
+        float(radians(s, "number")?.sin(), UnitSet::scalar())
+    });
+    def!(f, cos(number), |s| {
+        float(radians(s, "number")?.cos(), UnitSet::scalar())
+    });
+    def!(f, tan(number), |s| {
+        float(radians(s, "number")?.tan(), UnitSet::scalar())
+    });
+    def!(f, asin(number), |s| degrees(unitless(s, "number")?.asin()));
+    def!(f, acos(number), |s| degrees(unitless(s, "number")?.acos()));
+    def!(f, atan(number), |s| degrees(unitless(s, "number")?.atan()));
+    def!(f, atan2(y, x), |s| match (s.get("y"), s.get("x")) {
+        (Value::Numeric(y, yu, ..), Value::Numeric(x, xu, ..)) => {
+            let x = convert(&x, &xu, &y, &yu)? * x;
+            degrees(big_to_f64(&y).atan2(big_to_f64(&x)))
+        }
+        (y, x) => Err(Error::badargs(&["number", "number"], &[&y, &x])),
+    });
+    def_va!(f, hypot(numbers), |s| {
+        let numbers = s.get("numbers").iter_items();
+        let (first, unit) = match numbers.first() {
+            Some(&Value::Numeric(ref v, ref u, ..)) => (v.clone(), u.clone()),
+            Some(v) => return Err(Error::badarg("number", v)),
+            None => {
+                return Err(Error::BadArguments("At least one argument \
+                                                must be passed."
+                                                       .into()))
+            }
+        };
+        let mut sum = 0.0;
+        for n in &numbers {
+            match *n {
+                Value::Numeric(ref v, ref u, ..) => {
+                    let v = big_to_f64(&(v * convert(v, u, &first, &unit)?));
+                    sum += v * v;
+                }
+                ref v => return Err(Error::badarg("number", v)),
+            }
+        }
+        float(sum.sqrt(), unit)
+    });
+    def!(f, clamp(min, number, max), |s| {
+        match (s.get("min"), s.get("number"), s.get("max")) {
+            (Value::Numeric(min, minu, ..),
+             Value::Numeric(n, nu, ..),
+             Value::Numeric(max, maxu, ..)) => {
+                if &min * convert(&min, &minu, &n, &nu)? >= n {
+                    Ok(number(min, minu))
+                } else if &max * convert(&max, &maxu, &n, &nu)? <= n {
+                    Ok(number(max, maxu))
+                } else {
+                    Ok(number(n, nu))
+                }
+            }
+            (min, n, max) => {
+                Err(Error::badargs(&["number", "number", "number"],
+                                   &[&min, &n, &max]))
+            }
+        }
     });
     def!(f, random(limit), |s| match s.get("limit") {
         Value::Null => {
             let rez = 1000000;
-            Ok(number(Rational::new(intrand(rez), rez), Unit::None))
+            Ok(number(BigRational::new(intrand(rez).into(), rez.into()),
+                      UnitSet::scalar()))
         }
         Value::Numeric(val, unit, ..) => {
-            let res = 1 + intrand(val.to_integer());
-            Ok(number(Rational::from_integer(res), unit))
+            let limit = val.to_integer().to_isize().unwrap_or(isize::MAX);
+            let res = 1 + intrand(limit);
+            Ok(number(BigRational::from_integer(res.into()), unit))
         }
         v => Err(Error::badarg("number or null", &v)),
     });
 }
 
-fn number(v: Rational, unit: Unit) -> Value {
-    Value::Numeric(v, unit, false, true)
+/// The largest integer exponent for which `pow` is calculated
+/// without floating point.
+///
+/// Larger exponents use floating point, to avoid huge numbers.
+const MAX_EXACT_POW: usize = 1000;
+
+fn number(v: BigRational, unit: UnitSet) -> Value {
+    Value::Numeric(limit(v), unit, false, true)
+}
+
+/// Get the argument `name` as a float, checking that it is unitless.
+fn unitless(s: &dyn Scope, name: &str) -> Result<f64, Error> {
+    unitless_number(s, name).map(|v| big_to_f64(&v))
+}
+
+/// Get the argument `name` as a number, checking that it is unitless.
+fn unitless_number(s: &dyn Scope, name: &str) -> Result<BigRational, Error> {
+    match s.get(name) {
+        Value::Numeric(v, ref u, ..) if u.is_none() => Ok(v),
+        ref v @ Value::Numeric(..) => {
+            Err(Error::BadArguments(format!("${}: Expected {} to have no \
+                                             units.",
+                                            name,
+                                            v)))
+        }
+        ref v => Err(Error::badarg("number", v)),
+    }
+}
+
+/// Get the argument `name` as an angle in radians.
+///
+/// Unitless numbers are taken to be radians already.
+fn radians(s: &dyn Scope, name: &str) -> Result<f64, Error> {
+    match s.get(name) {
+        Value::Numeric(ref v, ref u, ..) => {
+            if u.is_none() || u.single() == Some(&Unit::Rad) {
+                Ok(big_to_f64(v))
+            } else if let Some(f) = u.scale_to(&Unit::Deg.into()) {
+                Ok(big_to_f64(&(v * f)).to_radians())
+            } else {
+                Err(Error::BadArguments(format!("${}: Expected {} to have \
+                                                 an angle unit (deg, grad, \
+                                                 rad, turn).",
+                                                name,
+                                                number(v.clone(),
+                                                       u.clone()))))
+            }
+        }
+        ref v => Err(Error::badarg("number", v)),
+    }
+}
+
+/// Get the factor to convert `v` to the unit of `to`.
+///
+/// The units must be compatible, or both empty.
+fn convert(v: &BigRational,
+           vu: &UnitSet,
+           to: &BigRational,
+           tu: &UnitSet)
+           -> Result<BigRational, Error> {
+    vu.scale_to(tu).ok_or_else(|| {
+        Error::BadArguments(format!("{} and {} have incompatible units.",
+                                    number(to.clone(), tu.clone()),
+                                    number(v.clone(), vu.clone())))
+    })
+}
+
+/// Make a number from the result of a floating-point function.
+///
+/// Sass numbers are rationals, so NaN and infinite results,
+/// such as `sqrt(-1)` or `log(0)`, are errors.
+fn float(v: f64, unit: UnitSet) -> Result<Value, Error> {
+    match BigRational::from_float(v) {
+        Some(v) => Ok(number(v, unit)),
+        None => {
+            let v = if v.is_nan() {
+                "NaN"
+            } else if v > 0. {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            Err(Error::BadValue(format!("{} is not a supported number.", v)))
+        }
+    }
+}
+
+fn degrees(v: f64) -> Result<Value, Error> {
+    float(v.to_degrees(), Unit::Deg.into())
 }
 
 fn find_extreme(v: &[Value], pref: Ordering) -> &Value {
@@ -63,12 +266,14 @@ fn find_extreme(v: &[Value], pref: Ordering) -> &Value {
                 (a, &Value::Null) => a,
                 (&Value::Numeric(ref va, ref ua, ..),
                  &Value::Numeric(ref vb, ref ub, ..)) => {
-                    if ua == &Unit::None || ua == ub || ub == &Unit::None {
-                        if va.cmp(vb) == pref { first } else { second }
-                    } else if ua.dimension() == ub.dimension() {
-                        let sa = va * ua.scale_factor();
-                        let sb = vb * ub.scale_factor();
-                        if sa.cmp(&sb) == pref { first } else { second }
+                    let scale = if ua.is_none() || ub.is_none() {
+                        Some(BigRational::one())
+                    } else {
+                        ub.scale_to(ua)
+                    };
+                    if let Some(scale) = scale {
+                        let vb = vb * scale;
+                        if va.cmp(&vb) == pref { first } else { second }
                     } else {
                         &NULL_VALUE
                     }
@@ -1,79 +1,87 @@
 use super::{Error, SassFunction};
-use num_rational::Rational;
-use num_traits::Signed;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive};
+use output_style::Format;
+use rand::random;
+use std::cmp::min;
 use std::collections::BTreeMap;
-use unit::Unit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use unit::UnitSet;
 use value::{Quotes, Value};
+use variablescope::Scope;
 
 pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
     def!(f, quote(contents), |s| match s.get("contents") {
         Value::Literal(v, _) => Ok(Value::Literal(v, Quotes::Double)),
-        v => Ok(Value::Literal(format!("{}", v), Quotes::Double)),
+        v => {
+            let format = Format::with_precision(s.get_precision());
+            Ok(Value::Literal(v.format(format).to_string(), Quotes::Double))
+        }
     });
     def!(f, unquote(contents), |s| match s.get("contents") {
         Value::Literal(v, _) => Ok(Value::Literal(v, Quotes::None)),
         v => {
-            dep_warn!("Passing {}, a non-string value, to unquote()", v);
+            dep_warn!(s, "Passing {}, a non-string value, to unquote()", v);
             Ok(v)
         }
     });
-    def!(f,
-         str_insert(string, insert, index),
-         |s| match (s.get("string"), s.get("insert"), s.get("index")) {
-             (Value::Literal(s, q),
-              Value::Literal(insert, _),
-              Value::Numeric(index, Unit::None, ..)) => {
-                 let i = index_to_rust(index, &s);
-                 let mut s = s.chars();
-                 Ok(Value::Literal(format!("{}{}{}",
-                                           s.by_ref()
-                                               .take(i)
-                                               .collect::<String>(),
-                                           insert,
-                                           s.collect::<String>()),
-                                   q))
-             }
-             (s, i, v) => {
-                 Err(Error::badargs(&["string", "string", "number"],
-                                    &[&s, &i, &v]))
-             }
-         });
-    def!(f,
-         str_slice(string, start_at, end_at = b"-1;"),
-         |s| match (s.get("string"), s.get("start_at"), s.get("end_at")) {
-             (Value::Literal(s, q),
-              Value::Numeric(start_at, Unit::None, ..),
-              Value::Numeric(end_at, Unit::None, ..)) => {
-                 let start_at = index_to_rust(start_at, &s);
-                 let end_at = index_to_rust(end_at, &s);
-                 let c = s.chars();
-                 Ok(Value::Literal(c.skip(start_at)
-                                       .take(end_at + 1 - start_at)
-                                       .collect::<String>(),
-                                   q))
-             }
-             (v, s, e) => {
-                 Err(Error::badargs(&["string", "number", "number"],
-                                    &[&v, &s, &e]))
-             }
-         });
+    def!(f, str_insert(string, insert, index), |s| {
+        let (string, q) = string_arg(s, "string")?;
+        let (insert, _) = string_arg(s, "insert")?;
+        let index = integer_arg(s, "index")?;
+        let len = string.chars().count();
+        let i = clamp_index(if index > 0 {
+                                index - 1
+                            } else if index < 0 {
+                                len as isize + index + 1
+                            } else {
+                                0
+                            },
+                            len);
+        let mut chars = string.chars();
+        Ok(Value::Literal(format!("{}{}{}",
+                                  chars.by_ref().take(i).collect::<String>(),
+                                  insert,
+                                  chars.collect::<String>()),
+                          q))
+    });
+    def!(f, str_slice(string, start_at, end_at = b"-1;"), |s| {
+        let (string, q) = string_arg(s, "string")?;
+        let start_at = integer_arg(s, "start_at")?;
+        let end_at = integer_arg(s, "end_at")?;
+        let len = string.chars().count();
+        let start = clamp_index(if start_at > 0 {
+                                    start_at - 1
+                                } else if start_at < 0 {
+                                    len as isize + start_at
+                                } else {
+                                    0
+                                },
+                                len);
+        let end = clamp_index(if end_at < 0 {
+                                  len as isize + end_at + 1
+                              } else {
+                                  end_at
+                              },
+                              len);
+        Ok(Value::Literal(string.chars()
+                              .skip(start)
+                              .take(end.saturating_sub(start))
+                              .collect(),
+                          q))
+    });
     def!(f, str_length(string), |s| match &s.get("string") {
         &Value::Literal(ref v, _) => Ok(intvalue(v.chars().count())),
         v => Err(Error::badarg("string", v)),
     });
-    def!(f,
-         str_index(string, substring),
-         |s| match (s.get("string"), s.get("substring")) {
-             (Value::Literal(s, _), Value::Literal(sub, _)) => {
-                 Ok(match s.find(&sub) {
-                        Some(o) => intvalue(1 + s[0..o].chars().count()),
-                        None => Value::Null,
-                    })
-             }
-             (full, sub) => {
-                 Err(Error::badargs(&["string", "string"], &[&full, &sub]))
-             }
-         });
+    def!(f, str_index(string, substring), |s| {
+        let (string, _) = string_arg(s, "string")?;
+        let (sub, _) = string_arg(s, "substring")?;
+        Ok(match string.find(&sub) {
+               Some(o) => intvalue(1 + string[0..o].chars().count()),
+               None => Value::Null,
+           })
+    });
     def!(f, to_upper_case(string), |s| match s.get("string") {
         Value::Literal(v, q) => Ok(Value::Literal(v.to_uppercase(), q)),
         v => Ok(v),
@@ -82,23 +90,70 @@ pub fn register(f: &mut BTreeMap<&'static str, SassFunction>) {
         Value::Literal(v, q) => Ok(Value::Literal(v.to_lowercase(), q)),
         v => Ok(v),
     });
+    def!(f, unique_id(), |_| {
+        let id = UNIQUE_ID.fetch_add(1, Ordering::Relaxed);
+        let id = id.wrapping_add(*UNIQUE_BASE) & 0xffff_ffff;
+        Ok(Value::Literal(format!("u{:08x}", id), Quotes::None))
+    });
+}
+
+/// Counter for `unique-id()`, shared by all threads so ids are
+/// unique within the process.
+static UNIQUE_ID: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// Random start for `unique-id()`, so ids from separate
+    /// compilations are unlikely to collide.
+    static ref UNIQUE_BASE: usize = random::<u32>() as usize;
 }
 
 fn intvalue(n: usize) -> Value {
-    Value::Numeric(Rational::from_integer(n as isize), Unit::None, false, true)
+    Value::Numeric(BigRational::from_integer(n.into()),
+                   UnitSet::scalar(),
+                   false,
+                   true)
 }
 
-/// Convert index from sass (rational number, first is one) to rust
-/// (usize, first is zero).  Sass values might be negative, then -1 is
-/// the last char in the string.
-fn index_to_rust(index: Rational, s: &str) -> usize {
-    if index.is_negative() {
-        let l = s.chars().count();
-        let i = index.to_integer().abs() as usize;
-        if l > i { l - i + 1 } else { 0 }
-    } else if index.is_positive() {
-        index.to_integer() as usize - 1
-    } else {
+/// Get a string argument, and its quotes.
+fn string_arg(s: &dyn Scope, name: &str) -> Result<(String, Quotes), Error> {
+    match s.get(name) {
+        Value::Literal(v, q) => Ok((v, q)),
+        v => Err(Error::badarg("string", &v)),
+    }
+}
+
+/// Get an unitless integer argument.
+///
+/// Integers too large for an `isize` are saturated.
+fn integer_arg(s: &dyn Scope, name: &str) -> Result<isize, Error> {
+    match s.get(name) {
+        Value::Numeric(ref v, ref u, ..) if u.is_none() && v.is_integer() => {
+            Ok(v.to_integer().to_isize().unwrap_or(if v.is_negative() {
+                                                       isize::MIN
+                                                   } else {
+                                                       isize::MAX
+                                                   }))
+        }
+        ref v @ Value::Numeric(_, ref u, ..) if u.is_none() => {
+            Err(Error::BadArguments(format!("${}: {} is not an int.",
+                                            name.replace('_', "-"),
+                                            v)))
+        }
+        ref v @ Value::Numeric(..) => {
+            Err(Error::BadArguments(format!("${}: Expected {} to have no \
+                                             units.",
+                                            name.replace('_', "-"),
+                                            v)))
+        }
+        v => Err(Error::badarg("number", &v)),
+    }
+}
+
+/// Limit a code point offset to the length of a string.
+fn clamp_index(i: isize, len: usize) -> usize {
+    if i < 0 {
         0
+    } else {
+        min(i as usize, len)
     }
 }
@@ -0,0 +1,71 @@
+//! Finding and loading files for `@import`, `@use` and `@forward`.
+use error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// An importer finds and loads imported files.
+///
+/// The default importer, `FsImporter`, reads files from the file
+/// system.  A custom importer can be used to get sass sources from
+/// some other place, such as an embedded asset bundle.
+pub trait Importer: fmt::Debug + Send + Sync {
+    /// Find the file for `url`, as imported from a file in `base`.
+    ///
+    /// The `base` is the directory of the importing file (empty for
+    /// the current directory), or one of the load paths.
+    /// Returns the canonical path of the file, or `None` if there is
+    /// no such file.
+    fn resolve(&self, base: &Path, url: &str) -> Option<PathBuf>;
+
+    /// Load the file of a path returned by `resolve`.
+    ///
+    /// Returns the source text and the canonical path of the file.
+    /// The canonical path is used in error messages and source maps,
+    /// and to resolve imports from the loaded file.
+    /// If it ends with `.sass`, the source is parsed as indented
+    /// sass, otherwise as scss.
+    fn load(&self, path: &Path) -> Result<(Vec<u8>, PathBuf), Error>;
+}
+
+/// The default `Importer`, using the file system.
+///
+/// An `@import "foo"` in a file in `base` finds the first existing
+/// file of `base/foo`, `base/foo.scss`, `base/_foo.scss`,
+/// `base/foo.sass` and `base/_foo.sass`.
+#[derive(Clone, Debug, Default)]
+pub struct FsImporter;
+
+impl Importer for FsImporter {
+    fn resolve(&self, base: &Path, url: &str) -> Option<PathBuf> {
+        // TODO Check docs what expansions should be tried!
+        let url = Path::new(url);
+        let parent = url.parent().map(|p| base.join(p));
+        let parent = parent.as_deref().unwrap_or(base);
+        if let Some(name) = url.file_name().and_then(|n| n.to_str()) {
+            for name in &[name,
+                          &format!("{}.scss", name),
+                          &format!("_{}.scss", name),
+                          &format!("{}.sass", name),
+                          &format!("_{}.sass", name)] {
+                let full = parent.join(name);
+                if full.is_file() {
+                    return Some(full);
+                }
+            }
+        }
+        None
+    }
+
+    fn load(&self, path: &Path) -> Result<(Vec<u8>, PathBuf), Error> {
+        Ok((read_file(path)?, path.into()))
+    }
+}
+
+pub fn read_file(file: &Path) -> Result<Vec<u8>, Error> {
+    let mut f = File::open(file).map_err(|e| Error::Input(file.into(), e))?;
+    let mut data = vec![];
+    f.read_to_end(&mut data).map_err(|e| Error::Input(file.into(), e))?;
+    Ok(data)
+}
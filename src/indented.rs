@@ -0,0 +1,281 @@
+//! Support for the indented sass syntax.
+//!
+//! Indented sass is translated to scss, which is then parsed by the
+//! ordinary scss parser.  The translation keeps every line on the
+//! same line number, mostly only adding braces and semicolons at the
+//! end of lines, and records the origin of each line, so source
+//! positions refer to the indented sass the user wrote.
+use sourcepos::LineOrigin;
+
+/// A line of indented sass, as seen by the translator.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Blank,
+    Code,
+    Comment,
+}
+
+/// Translate indented sass to scss.
+///
+/// Returns the scss and the origin of each of its lines.
+pub fn translate(data: &str) -> (String, Vec<LineOrigin>) {
+    let lines = data.lines()
+        .map(|l| l.trim_end_matches('\r'))
+        .collect::<Vec<_>>();
+    let mut out = Output::default();
+    // The indentation of each currently open block's parent statement.
+    let mut open: Vec<usize> = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let text = line.trim_start();
+        if text.is_empty() {
+            out.push(Kind::Blank, String::new(), line, vec![]);
+            i += 1;
+            continue;
+        }
+        let indent = line.len() - text.len();
+        let mut closers = 0;
+        while open.last().map(|&o| o >= indent).unwrap_or(false) {
+            open.pop();
+            closers += 1;
+        }
+        let prefix = out.close_blocks(closers);
+
+        if text.starts_with("//") || text.starts_with("/*") {
+            let silent = text.starts_with("//");
+            out.push(Kind::Comment,
+                     format!("{}{}", prefix, line),
+                     line,
+                     vec![(prefix.len(), 0)]);
+            i += 1;
+            let mut closed = text.contains("*/");
+            while i < lines.len() && continues(lines[i], indent) {
+                let cont = lines[i];
+                let ctext = cont.trim_start();
+                if silent && !ctext.is_empty() {
+                    let cindent = cont.len() - ctext.len();
+                    out.push(Kind::Comment,
+                             format!("{}//{}", &cont[..cindent], ctext),
+                             cont,
+                             vec![(0, 0), (cindent + 2, cindent)]);
+                } else {
+                    out.push(Kind::Comment, cont.into(), cont, vec![(0, 0)]);
+                    closed = closed || cont.contains("*/");
+                }
+                i += 1;
+            }
+            if !silent && !closed {
+                let last = out.lines
+                    .iter()
+                    .rposition(|l| !l.trim().is_empty())
+                    .unwrap();
+                out.lines[last].push_str(" */");
+            }
+            continue;
+        }
+
+        // A statement may continue over several lines, when a line
+        // ends with a comma (as in a list of selectors).
+        let mut end = i + 1;
+        while strip_comment(lines[end - 1]).trim_end().ends_with(',') &&
+              end < lines.len() && !lines[end].trim().is_empty() {
+            end += 1;
+        }
+        let block = lines[end..]
+            .iter()
+            .find(|l| !l.trim().is_empty())
+            .map(|l| l.len() - l.trim_start().len() > indent)
+            .unwrap_or(false);
+
+        let (start, (t, o)) = translate_start(strip_comment(text), block);
+        out.push(Kind::Code,
+                 format!("{}{}{}", prefix, &line[..indent], start),
+                 line,
+                 vec![(0, 0), (prefix.len() + indent + t, indent + o)]);
+        for cont in &lines[i + 1..end] {
+            let text = strip_comment(cont).into();
+            out.push(Kind::Code, text, cont, vec![(0, 0)]);
+        }
+        i = end;
+        let last = out.lines.last_mut().unwrap();
+        if block {
+            last.push_str(" {");
+            open.push(indent);
+        } else {
+            last.push(';');
+        }
+    }
+    let closers = open.len();
+    let prefix = out.close_blocks(closers);
+    if !prefix.is_empty() {
+        out.lines.push(prefix.trim_end().into());
+    }
+    let mut result = out.lines.join("\n");
+    result.push('\n');
+    (result, out.origins)
+}
+
+/// The translated lines, with the kind and origin of each.
+#[derive(Default)]
+struct Output {
+    lines: Vec<String>,
+    kinds: Vec<Kind>,
+    origins: Vec<LineOrigin>,
+}
+
+impl Output {
+    /// Add `text`, translated from the original `line`.
+    ///
+    /// The `runs` are as in `LineOrigin`.
+    fn push(&mut self,
+            kind: Kind,
+            text: String,
+            line: &str,
+            runs: Vec<(usize, usize)>) {
+        self.lines.push(text);
+        self.kinds.push(kind);
+        self.origins.push(LineOrigin { text: line.into(), runs: runs });
+    }
+
+    /// Close `n` blocks.
+    ///
+    /// If the last non-blank line is code, the closing braces are
+    /// added to it, so the line numbers of following lines are kept.
+    /// Otherwise, the braces are returned, to be put before the next
+    /// line.
+    fn close_blocks(&mut self, n: usize) -> String {
+        if n == 0 {
+            return String::new();
+        }
+        let braces = vec!["}"; n].join(" ");
+        match self.kinds.iter().rposition(|k| *k != Kind::Blank) {
+            Some(last) if self.kinds[last] == Kind::Code => {
+                self.lines[last].push(' ');
+                self.lines[last].push_str(&braces);
+                String::new()
+            }
+            _ => format!("{} ", braces),
+        }
+    }
+}
+
+/// True if `line` is a continuation of a comment at `indent`.
+fn continues(line: &str, indent: usize) -> bool {
+    let text = line.trim_start();
+    text.is_empty() || line.len() - text.len() > indent
+}
+
+/// Remove a trailing silent comment from a line of code.
+///
+/// A `//` inside a string or an `url()` does not start a comment.
+fn strip_comment(text: &str) -> &str {
+    let bytes = text.as_bytes();
+    let mut quote = None;
+    let mut in_url = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if let Some(q) = quote {
+            if c == b'\\' {
+                i += 1;
+            } else if c == q {
+                quote = None;
+            }
+        } else if c == b'"' || c == b'\'' {
+            quote = Some(c);
+        } else if in_url {
+            in_url = c != b')';
+        } else if bytes[i..].starts_with(b"url(") {
+            in_url = true;
+            i += 3;
+        } else if bytes[i..].starts_with(b"//") {
+            return text[..i].trim_end();
+        }
+        i += 1;
+    }
+    text
+}
+
+/// Translate the indented-only shorthands at the start of a statement.
+///
+/// `block` is true if the statement has a nested block.  Returns the
+/// translated text, and a pair of an offset in the translated text
+/// and the offset in `text` that it corresponds to.
+fn translate_start(text: &str, block: bool) -> (String, (usize, usize)) {
+    if let Some(rest) = text.strip_prefix('=') {
+        let rest = rest.trim_start();
+        (format!("@mixin {}", rest), (7, text.len() - rest.len()))
+    } else if let Some(rest) = text.strip_prefix('+') {
+        let rest = rest.trim_start();
+        (format!("@include {}", rest), (9, text.len() - rest.len()))
+    } else if !block && text.starts_with(':') &&
+              text[1..].starts_with(|c: char| c.is_alphabetic()) &&
+              text.contains(' ') {
+        // Old-style property syntax, `:name value`.
+        let (name, value) = text[1..].split_at(text.find(' ').unwrap() - 1);
+        (format!("{}:{}", name, value), (0, 1))
+    } else {
+        (text.into(), (0, 0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::translate;
+
+    fn sass_to_scss(data: &str) -> String {
+        translate(data).0
+    }
+
+    #[test]
+    fn nested_rules() {
+        assert_eq!(sass_to_scss("a\n  b: c\n  d\n    e: f\ng\n  h: i\n"),
+                   "a {\n  b: c;\n  d {\n    e: f; } }\ng {\n  h: i; }\n")
+    }
+
+    #[test]
+    fn mixin_shorthands() {
+        assert_eq!(sass_to_scss("=m($x)\n  a: $x\np\n  +m(1)\n"),
+                   "@mixin m($x) {\n  a: $x; }\np {\n  @include m(1); }\n")
+    }
+
+    #[test]
+    fn multiline_selector() {
+        assert_eq!(sass_to_scss("a,\nb\n  c: d\n"), "a,\nb {\n  c: d; }\n")
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(sass_to_scss("/* loud\n   comment\na\n  // silent\n    \
+                                 comment\n  b: c\n"),
+                   "/* loud\n   comment */\na {\n  // silent\n    \
+                    //comment\n  b: c; }\n")
+    }
+
+    #[test]
+    fn trailing_comments() {
+        assert_eq!(sass_to_scss("a // x\n  b: c // note\n  \
+                                 d: url(http://e/f) // g\n  h: \"//i\"\n"),
+                   "a {\n  b: c;\n  d: url(http://e/f);\n  h: \"//i\"; }\n")
+    }
+
+    #[test]
+    fn old_property_syntax() {
+        assert_eq!(sass_to_scss("a\n  :b c\n"), "a {\n  b: c; }\n")
+    }
+
+    #[test]
+    fn nested_pseudo_selector() {
+        assert_eq!(sass_to_scss("a\n  :not(.b) .c\n    d: e\n"),
+                   "a {\n  :not(.b) .c {\n    d: e; } }\n")
+    }
+
+    #[test]
+    fn origins() {
+        let (_, origins) = translate("=m\n  a: b\np\n  +m\n");
+        assert_eq!(origins.iter().map(|o| &o.text[..]).collect::<Vec<_>>(),
+                   ["=m", "  a: b", "p", "  +m"]);
+        assert_eq!(origins[3].runs, [(0, 0), (11, 3)]);
+    }
+}
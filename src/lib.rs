@@ -36,21 +36,29 @@
 extern crate lazy_static;
 #[macro_use]
 extern crate nom;
+extern crate num_bigint;
 extern crate num_rational;
 extern crate num_traits;
 extern crate rand;
 
+use importer::read_file;
 use nom::IResult;
-use std::fs::File;
-use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::str::from_utf8;
+use std::sync::Arc;
 
 mod colors;
 mod error;
 mod formalargs;
 mod functions;
+mod importer;
+mod indented;
+mod logger;
+mod media;
+mod ordermap;
 mod selectors;
+mod sourcemap;
+mod sourcepos;
 mod parseutil;
 mod value;
 mod variablescope;
@@ -60,17 +68,28 @@ mod unit;
 pub use error::Error;
 use formalargs::{CallArgs, FormalArgs, call_args, formal_args};
 pub use functions::SassFunction;
-pub use num_rational::Rational;
-pub use output_style::OutputStyle;
+pub use importer::{FsImporter, Importer};
+pub use logger::{Logger, StderrLogger};
+pub use media::{MediaQueries, MediaQuery};
+use media::media_queries;
+pub use ordermap::OrderMap;
+pub use num_rational::{BigRational, Rational};
+pub use output_style::{Format, Formatted, OutputStyle};
 use parseutil::{comment, ignore_space, name, opt_spacelike, spacelike};
 use selectors::{Selectors, selectors};
-pub use unit::Unit;
-pub use value::{ListSeparator, Quotes, Value, function_call, interpolation,
-                quoted_string, singlequoted_string};
-use value::{single_value, value_expression};
-pub use variablescope::{GlobalScope, Scope};
+pub use sourcemap::SourceMap;
+pub use sourcepos::SourcePos;
+use sourcepos::{pos_of, position, with_source, with_translated_source};
+pub use unit::{Unit, UnitSet};
+pub use value::{ColorSpace, ListSeparator, Quotes, Value, function_call,
+                interpolation, quoted_string, singlequoted_string};
+use value::{single_value, space_list, value_expression};
+pub use variablescope::{ForwardFilter, GlobalScope, Module, Scope};
 
-/// Parse scss data from a buffer and write css in the given style.
+/// Parse scss data from a buffer and write css in the given format.
+///
+/// The format is a `Format`, or just an `OutputStyle` for the
+/// default options.
 ///
 /// # Example
 ///
@@ -84,18 +103,42 @@ pub use variablescope::{GlobalScope, Scope};
 ///                           }", OutputStyle::Compressed).unwrap(),
 ///            b"foo bar{baz:value}\n")
 /// ```
-pub fn compile_scss(input: &[u8],
-                    style: OutputStyle)
-                    -> Result<Vec<u8>, Error> {
+pub fn compile_scss<F>(input: &[u8], format: F) -> Result<Vec<u8>, Error>
+    where F: Into<Format>
+{
     let file_context = FileContext::new();
     let items = parse_scss_data(input)?;
-    style.write_root(&items, &mut GlobalScope::new(), file_context)
+    format.into().write_root(&items, &mut GlobalScope::new(), file_context)
+}
+
+/// Parse indented sass data from a buffer and write css in the
+/// given format.
+///
+/// # Example
+///
+/// ```
+/// use rsass::{OutputStyle, compile_sass};
+///
+/// assert_eq!(compile_sass(b"foo\n\
+///                            \x20 bar\n\
+///                            \x20   baz: value\n",
+///                         OutputStyle::Compressed).unwrap(),
+///            b"foo bar{baz:value}\n")
+/// ```
+pub fn compile_sass<F>(input: &[u8], format: F) -> Result<Vec<u8>, Error>
+    where F: Into<Format>
+{
+    let file_context = FileContext::new();
+    let items = parse_sass_data(input)?;
+    format.into().write_root(&items, &mut GlobalScope::new(), file_context)
 }
 
-/// Parse a file of scss data and write css in the given style.
+/// Parse a file of scss data and write css in the given format.
 ///
 /// Any `@import` directives will be handled relative to the directory
 /// part of `file`.
+/// If the name of `file` ends with `.sass`, it is parsed as indented
+/// sass.
 ///
 /// # Example
 ///
@@ -106,13 +149,56 @@ pub fn compile_scss(input: &[u8],
 ///                              OutputStyle::Compressed).unwrap(),
 ///            b"div span{moo:goo}\n")
 /// ```
-pub fn compile_scss_file(file: &Path,
-                         style: OutputStyle)
-                         -> Result<Vec<u8>, Error> {
+pub fn compile_scss_file<F>(file: &Path, format: F) -> Result<Vec<u8>, Error>
+    where F: Into<Format>
+{
+    let (sub_context, items) = FileContext::new().load(file)?;
+    format.into().write_root(&items, &mut GlobalScope::new(), sub_context)
+}
+
+/// Parse scss data from a buffer and write css in the given format,
+/// together with a source map for the css.
+///
+/// The source map refers to the data as `stdin`.
+pub fn compile_scss_with_sourcemap<F>(input: &[u8],
+                                      format: F)
+                                      -> Result<(Vec<u8>, SourceMap), Error>
+    where F: Into<Format>
+{
     let file_context = FileContext::new();
-    let (sub_context, file) = file_context.file(file);
-    let items = parse_scss_file(&file)?;
-    style.write_root(&items, &mut GlobalScope::new(), sub_context)
+    let items = parse_scss_data(input)?;
+    format.into().write_root_with_sourcemap(&items,
+                                            &mut GlobalScope::new(),
+                                            file_context)
+}
+
+/// Parse a file of scss data and write css in the given format,
+/// together with a source map for the css.
+///
+/// Every selector and declaration in the css is mapped to where it
+/// is defined, including in imported files and mixins.
+///
+/// # Example
+///
+/// ```
+/// use rsass::{OutputStyle, compile_scss_file_with_sourcemap};
+///
+/// let (css, map) = compile_scss_file_with_sourcemap(
+///     "tests/basic/14_imports/a.scss".as_ref(),
+///     OutputStyle::Normal).unwrap();
+/// assert_eq!(css, b"div span {\n  moo: goo;\n}\n");
+/// assert_eq!(map.sources(), &["tests/basic/14_imports/a.scss"]);
+/// ```
+pub fn compile_scss_file_with_sourcemap<F>
+    (file: &Path,
+     format: F)
+     -> Result<(Vec<u8>, SourceMap), Error>
+    where F: Into<Format>
+{
+    let (sub_context, items) = FileContext::new().load(file)?;
+    format.into().write_root_with_sourcemap(&items,
+                                            &mut GlobalScope::new(),
+                                            sub_context)
 }
 
 /// A file context specifies where to find files to load.
@@ -120,6 +206,10 @@ pub fn compile_scss_file(file: &Path,
 /// When opening an included file, an extended file context is
 /// created, to find further included files relative to the file they
 /// are inlcuded from.
+/// Files that are not found relative to the including file are
+/// searched for in the load paths, in order.
+/// The files are found and loaded by an `Importer`, by default the
+/// `FsImporter`.
 ///
 /// # Example
 /// ```
@@ -136,6 +226,8 @@ pub fn compile_scss_file(file: &Path,
 #[derive(Clone, Debug)]
 pub struct FileContext {
     path: PathBuf,
+    load_paths: Vec<PathBuf>,
+    importer: Arc<dyn Importer>,
 }
 
 impl FileContext {
@@ -143,35 +235,76 @@ impl FileContext {
     ///
     /// Files will be resolved from the current working directory.
     pub fn new() -> Self {
-        FileContext { path: PathBuf::new() }
+        FileContext::with_importer(Arc::new(FsImporter))
+    }
+    /// Create a new FileContext using a specific `Importer`.
+    pub fn with_importer(importer: Arc<dyn Importer>) -> Self {
+        FileContext {
+            path: PathBuf::new(),
+            load_paths: Vec::new(),
+            importer: importer,
+        }
+    }
+    /// Add a load path.
+    ///
+    /// Files that are not found relative to the file importing them
+    /// are searched for in the load paths, in the order they are
+    /// added.
+    ///
+    /// # Example
+    /// ```
+    /// use rsass::{FileContext, GlobalScope, OutputStyle};
+    ///
+    /// let mut context = FileContext::new();
+    /// context.push_path("tests/load_paths/vendor".as_ref());
+    /// let (context, items) = context.load("tests/load_paths/main.scss"
+    ///                                         .as_ref()).unwrap();
+    /// let css = OutputStyle::Compressed
+    ///     .write_root(&items, &mut GlobalScope::new(), context)
+    ///     .unwrap();
+    /// assert_eq!(css, b".main{color:red;border:1px solid}\n")
+    /// ```
+    pub fn push_path(&mut self, path: &Path) {
+        self.load_paths.push(path.into());
     }
     /// Get a file from this context.
     ///
     /// Get a path and a FileContext from this FileContext and a path.
     pub fn file(&self, file: &Path) -> (Self, PathBuf) {
         let t = self.path.join(file);
-        if let Some(dir) = t.parent() {
-            (FileContext { path: PathBuf::from(dir) }, t.clone())
+        (self.in_dir(&t), t)
+    }
+    /// Load and parse a file.
+    ///
+    /// The `file` is given to the `load` method of the importer as
+    /// is, so it should be a path that is already resolved.
+    /// Returns the items of the file, and a FileContext for
+    /// resolving imports from it.
+    pub fn load(&self, file: &Path) -> Result<(Self, Vec<SassItem>), Error> {
+        let (data, path) = self.importer.load(file)?;
+        let items = if path.extension().map(|e| e == "sass").unwrap_or(false) {
+            parse_indented(&path.to_string_lossy(), &data)?
         } else {
-            (FileContext::new(), t.clone())
-        }
+            with_source(&path.to_string_lossy(), &data, parse_items)?
+        };
+        Ok((self.in_dir(&path), items))
+    }
+    /// Find the file `name` imported from this context.
+    fn find_file(&self, name: &str) -> Option<PathBuf> {
+        self.importer.resolve(&self.path, name).or_else(|| {
+            self.load_paths
+                .iter()
+                .filter_map(|base| self.importer.resolve(base, name))
+                .next()
+        })
     }
-    fn find_file(&self, name: &Path) -> Option<(Self, PathBuf)> {
-        // TODO Check docs what expansions should be tried!
-        let parent = name.parent();
-        if let Some(name) = name.file_name().and_then(|n| n.to_str()) {
-            for name in &[name,
-                          &format!("{}.scss", name),
-                          &format!("_{}.scss", name)] {
-                let full =
-                    parent.map(|p| p.join(name)).unwrap_or_else(|| name.into());
-                let (c, p) = self.file(&full);
-                if p.is_file() {
-                    return Some((c, p));
-                }
-            }
+    /// A context for resolving imports from `file`.
+    fn in_dir(&self, file: &Path) -> Self {
+        FileContext {
+            path: file.parent().map(PathBuf::from).unwrap_or_default(),
+            load_paths: self.load_paths.clone(),
+            importer: self.importer.clone(),
         }
-        None
     }
 }
 
@@ -179,32 +312,88 @@ impl FileContext {
 ///
 /// Returns a vec of the top level items of the file (or an error message).
 pub fn parse_scss_file(file: &Path) -> Result<Vec<SassItem>, Error> {
-    let mut f = File::open(file).map_err(|e| Error::Input(file.into(), e))?;
-    let mut data = vec![];
-    f.read_to_end(&mut data).map_err(|e| Error::Input(file.into(), e))?;
-    parse_scss_data(&data)
+    let data = read_file(file)?;
+    with_source(&file.to_string_lossy(), &data, parse_items)
+}
+
+/// Parse a file of indented sass.
+///
+/// Returns a vec of the top level items of the file (or an error message).
+pub fn parse_sass_file(file: &Path) -> Result<Vec<SassItem>, Error> {
+    let data = read_file(file)?;
+    parse_indented(&file.to_string_lossy(), &data)
 }
 
 /// Parse scss data from a buffer.
 ///
 /// Returns a vec of the top level items of the file (or an error message).
 pub fn parse_scss_data(data: &[u8]) -> Result<Vec<SassItem>, Error> {
+    with_source("stdin", data, parse_items)
+}
+
+/// Parse indented sass data from a buffer.
+///
+/// Returns a vec of the top level items of the file (or an error
+/// message), the same as `parse_scss_data` does for the equivalent
+/// scss.
+pub fn parse_sass_data(data: &[u8]) -> Result<Vec<SassItem>, Error> {
+    parse_indented("stdin", data)
+}
+
+/// Parse indented sass `data` from the file `name`.
+///
+/// Positions in the result refer to `data`, not to the scss it is
+/// translated to.
+fn parse_indented(name: &str, data: &[u8]) -> Result<Vec<SassItem>, Error> {
+    let (data, origins) =
+        indented::translate(&String::from_utf8_lossy(data));
+    with_translated_source(name, data.as_bytes(), origins, parse_items)
+}
+
+fn parse_items(data: &[u8]) -> Result<Vec<SassItem>, Error> {
     match sassfile(data) {
-        IResult::Done(b"", items) => Ok(items),
+        IResult::Done(b"", items) => check_use_first(items),
         IResult::Done(rest, _styles) => {
-            let t = from_utf8(rest)
-                .map(|s| s.to_string())
-                .unwrap_or_else(|_| format!("{:?}", rest));
-            Err(Error::S(format!("Failed to parse entire input: `{}` remains.",
-                                 t)))
+            Err(Error::parse("Failed to parse entire input", pos_of(rest)))
         }
-        IResult::Incomplete(x) => Err(Error::S(format!("Incomplete: {:?}", x))),
-        IResult::Error(x) => Err(Error::S(format!("Error: {}", x))),
+        IResult::Incomplete(_) => {
+            let end = data.iter()
+                .rposition(|c| !b" \t\r\n".contains(c))
+                .map(|p| p + 1)
+                .unwrap_or(0);
+            Err(Error::parse("Unexpected end of input", pos_of(&data[end..])))
+        }
+        IResult::Error(x) => Err(Error::parse(&x.to_string(), pos_of(data))),
     }
 }
 
+/// Check that no `@use` comes after other rules than `@forward`,
+/// `@use` and variable declarations.
+fn check_use_first(items: Vec<SassItem>) -> Result<Vec<SassItem>, Error> {
+    let first_other = items.iter()
+        .position(|item| match *item {
+                      SassItem::None |
+                      SassItem::Comment(_) |
+                      SassItem::Use { .. } |
+                      SassItem::Forward { .. } |
+                      SassItem::VariableDeclaration { .. } => false,
+                      _ => true,
+                  })
+        .unwrap_or(items.len());
+    for item in &items[first_other..] {
+        if let SassItem::Use { ref pos, .. } = *item {
+            return Err(Error::parse("@use rules must be written before \
+                                     any other rules.",
+                                    pos.clone()));
+        }
+    }
+    Ok(items)
+}
+
 named!(sassfile<&[u8], Vec<SassItem> >,
        many0!(alt!(value!(SassItem::None, spacelike) |
+                   use_rule |
+                   forward_rule |
                    import |
                    variable_declaration |
                    mixin_declaration |
@@ -214,6 +403,9 @@ named!(sassfile<&[u8], Vec<SassItem> >,
                    function_declaration |
                    mixin_call |
                    if_statement |
+                   extend |
+                   diagnostic |
+                   media |
                    at_rule |
                    rule |
                    map!(comment,
@@ -224,33 +416,67 @@ named!(sassfile<&[u8], Vec<SassItem> >,
 /// Scoping items contains further sequences of items.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SassItem {
-    Import(Value),
+    /// An `@import`, with its position.
+    Import(Value, SourcePos),
+    /// A `@use` of a module, with the namespace to use it by (`*`
+    /// for no namespace) and configuration variables.
+    Use {
+        url: String,
+        namespace: String,
+        config: Vec<(String, Value)>,
+        pos: SourcePos,
+    },
+    /// A `@forward` of (some of) the members of a module.
+    Forward {
+        url: String,
+        filter: ForwardFilter,
+        pos: SourcePos,
+    },
+    /// A variable declaration, with the position of its value.
     VariableDeclaration {
         name: String,
         val: Value,
         default: bool,
         global: bool,
+        pos: SourcePos,
     },
     AtRule {
         name: String,
         args: Value,
         body: Option<Vec<SassItem>>,
     },
+    /// A `@media` rule, with the position of its queries.
+    Media(MediaQueries, Vec<SassItem>, SourcePos),
+    /// A `@debug` message, sent to the `Logger`.
+    Debug(Value, SourcePos),
+    /// A `@warn` message, sent to the `Logger`.
+    Warn(Value, SourcePos),
+    /// An `@error`, which aborts the compilation.
+    Error(Value, SourcePos),
 
     MixinDeclaration {
         name: String,
         args: FormalArgs,
         body: Vec<SassItem>,
     },
-    MixinCall { name: String, args: CallArgs, body: Vec<SassItem> },
+    /// An `@include` of a mixin, with the position of the `@include`.
+    MixinCall {
+        name: String,
+        args: CallArgs,
+        body: Vec<SassItem>,
+        pos: SourcePos,
+    },
     Content,
 
     FunctionDeclaration { name: String, func: SassFunction },
     Return(Value),
 
-    IfStatement(Value, Vec<SassItem>, Vec<SassItem>),
-    /// The value may be or evaluate to a list.
-    Each(String, Value, Vec<SassItem>),
+    /// An `@if`, with the position of its condition.
+    IfStatement(Value, Vec<SassItem>, Vec<SassItem>, SourcePos),
+    /// The value may be or evaluate to a list or a map.
+    /// With more than one name, each item is destructured.
+    /// The position is that of the value.
+    Each(Vec<String>, Value, Vec<SassItem>, SourcePos),
     For {
         name: String,
         from: Box<Value>,
@@ -260,19 +486,26 @@ pub enum SassItem {
     },
     While(Value, Vec<SassItem>),
 
-    Rule(Selectors, Vec<SassItem>),
+    /// A rule, with the position of its selectors.
+    Rule(Selectors, Vec<SassItem>, SourcePos),
+    /// An `@extend` of some selectors.
+    /// The flag is true for `!optional` extends.
+    Extend(Selectors, bool, SourcePos),
     NamespaceRule(String, Value, Vec<SassItem>),
-    Property(String, Value, bool),
+    /// A property declaration, with a flag for `!important` and the
+    /// position of the declaration.
+    Property(String, Value, bool, SourcePos),
     Comment(String),
     None,
 }
 
 named!(rule<SassItem>,
        do_parse!(opt_spacelike >>
+                 pos: position >>
                  selectors: selectors >>
                  opt!(is_a!(", \t\n")) >>
                  body: body_block >>
-                 (SassItem::Rule(selectors, body))));
+                 (SassItem::Rule(selectors, body, pos))));
 
 named!(body_item<SassItem>,
        alt_complete!(
@@ -287,10 +520,15 @@ named!(body_item<SassItem>,
            while_loop |
            function_declaration |
            mixin_call |
+           use_rule |
+           forward_rule |
            import |
            if_statement |
            return_stmt |
            content_stmt |
+           extend |
+           diagnostic |
+           media |
            at_rule |
            value!(SassItem::None,
                   delimited!(opt_spacelike, tag!(";"), opt_spacelike)) |
@@ -298,12 +536,97 @@ named!(body_item<SassItem>,
                ));
 
 named!(import<SassItem>,
-       map!(delimited!(tag!("@import "), value_expression, tag!(";")),
-            |v| SassItem::Import(v)));
+       do_parse!(pos: position >>
+                 tag!("@import ") >>
+                 value: value_expression >>
+                 tag!(";") >>
+                 (SassItem::Import(value, pos))));
+
+named!(use_rule<SassItem>,
+       do_parse!(pos: position >>
+                 tag!("@use") >> spacelike >>
+                 url: module_url >> opt_spacelike >>
+                 namespace: opt!(do_parse!(
+                     tag!("as") >> spacelike >>
+                     ns: alt!(value!("*".to_string(), tag!("*")) | name) >>
+                     opt_spacelike >>
+                     (ns))) >>
+                 config: opt!(module_config) >> opt_spacelike >>
+                 tag!(";") >>
+                 (SassItem::Use {
+                     namespace: namespace
+                         .unwrap_or_else(|| default_namespace(&url)),
+                     url: url,
+                     config: config.unwrap_or_default(),
+                     pos: pos,
+                 })));
+
+named!(forward_rule<SassItem>,
+       do_parse!(pos: position >>
+                 tag!("@forward") >> spacelike >>
+                 url: module_url >> opt_spacelike >>
+                 prefix: opt!(do_parse!(tag!("as") >> spacelike >>
+                                        prefix: name >> tag!("*") >>
+                                        opt_spacelike >>
+                                        (prefix.replace('-', "_")))) >>
+                 show: opt!(preceded!(terminated!(tag!("show"), spacelike),
+                                      member_list)) >>
+                 hide: opt!(preceded!(terminated!(tag!("hide"), spacelike),
+                                      member_list)) >>
+                 tag!(";") >>
+                 (SassItem::Forward {
+                     url: url,
+                     filter: ForwardFilter {
+                         prefix: prefix,
+                         show: show,
+                         hide: hide.unwrap_or_default(),
+                     },
+                     pos: pos,
+                 })));
+
+named!(module_url<String>,
+       map!(alt!(delimited!(tag!("\""), is_not!("\""), tag!("\"")) |
+                 delimited!(tag!("'"), is_not!("'"), tag!("'"))),
+            |u| from_utf8(u).unwrap().into()));
+
+named!(module_config<Vec<(String, Value)>>,
+       do_parse!(tag!("with") >> opt_spacelike >>
+                 tag!("(") >> opt_spacelike >>
+                 items: separated_nonempty_list!(
+                     delimited!(opt_spacelike, tag!(","), opt_spacelike),
+                     do_parse!(tag!("$") >> name: name >> opt_spacelike >>
+                               tag!(":") >> opt_spacelike >>
+                               val: space_list >>
+                               (name, val))) >>
+                 opt!(delimited!(opt_spacelike, tag!(","), opt_spacelike)) >>
+                 opt_spacelike >> tag!(")") >>
+                 (items)));
+
+// A list of module members, as in the `show` or `hide` of a
+// `@forward`.  Variables are listed with their `$`.
+named!(member_list<Vec<String>>,
+       terminated!(separated_nonempty_list!(
+           delimited!(opt_spacelike, tag!(","), opt_spacelike),
+           map!(recognize!(preceded!(opt!(tag!("$")), name)),
+                |n| from_utf8(n).unwrap().replace('-', "_"))),
+                   opt_spacelike));
+
+/// The namespace of a module used without `as`, the last part of
+/// its url without leading underscore or extension.
+fn default_namespace(url: &str) -> String {
+    let name = url.rsplit(&['/', ':'][..]).next().unwrap_or(url);
+    let name = name.trim_start_matches('_');
+    let name = match name.find('.') {
+        Some(dot) => &name[..dot],
+        None => name,
+    };
+    name.into()
+}
 
 named!(mixin_call<SassItem>,
-       do_parse!(tag!("@include") >> spacelike >>
-                 name: name >> opt_spacelike >>
+       do_parse!(pos: position >>
+                 tag!("@include") >> spacelike >>
+                 name: member_name >> opt_spacelike >>
                  args: opt!(call_args) >> opt_spacelike >>
                  body: opt!(body_block) >> opt_spacelike >>
                  opt!(tag!(";")) >>
@@ -311,8 +634,21 @@ named!(mixin_call<SassItem>,
                      name: name,
                      args: args.unwrap_or_default(),
                      body: body.unwrap_or_default(),
+                     pos: pos,
                  })));
 
+// A name, possibly in a namespace, as `ns.name`.
+named!(member_name<String>,
+       map!(recognize!(pair!(name, opt!(preceded!(tag!("."), name)))),
+            |n| from_utf8(n).unwrap().into()));
+
+named!(media<SassItem>,
+       do_parse!(tag!("@media") >> spacelike >>
+                 pos: position >>
+                 queries: media_queries >> opt_spacelike >>
+                 body: body_block >>
+                 (SassItem::Media(queries, body, pos))));
+
 named!(at_rule<SassItem>,
        do_parse!(tag!("@") >>
                  name: name >> ignore_space >>
@@ -328,6 +664,7 @@ named!(at_rule<SassItem>,
                                              .into(),
                                              Quotes::None))
                          ))) >>
+                 opt_spacelike >>
                  body: opt!(body_block) >>
                  opt!(tag!(";")) >>
                  (SassItem::AtRule {
@@ -335,7 +672,7 @@ named!(at_rule<SassItem>,
                      args: if args.len() == 1 {
                          args.into_iter().next().unwrap()
                      } else {
-                         Value::List(args, ListSeparator::Space)
+                         Value::List(args, ListSeparator::Space, false)
                      },
                      body: body,
                  })));
@@ -395,6 +732,7 @@ named!(if_statement<SassItem>,
 
 named!(if_statement_inner<SassItem>,
        do_parse!(tag!("if") >> spacelike >>
+                 pos: position >>
                  cond: value_expression >> opt_spacelike >>
                  body: body_block >>
                  else_body: opt!(complete!(preceded!(
@@ -404,14 +742,19 @@ named!(if_statement_inner<SassItem>,
                          map!(if_statement_inner, |s| vec![s]))))) >>
                  (SassItem::IfStatement(cond,
                                         body,
-                                        else_body.unwrap_or_default()))));
+                                        else_body.unwrap_or_default(),
+                                        pos))));
 
 named!(each_loop<SassItem>,
-       do_parse!(tag!("@each") >> spacelike >> tag!("$") >>
-                 name: name >> spacelike >> tag!("in") >> spacelike >>
+       do_parse!(tag!("@each") >> spacelike >>
+                 names: separated_nonempty_list!(
+                     delimited!(opt_spacelike, tag!(","), opt_spacelike),
+                     preceded!(tag!("$"), name)) >>
+                 spacelike >> tag!("in") >> spacelike >>
+                 pos: position >>
                  values: value_expression >> opt_spacelike >>
                  body: body_block >>
-                 (SassItem::Each(name, values, body))));
+                 (SassItem::Each(names, values, body, pos))));
 
 named!(for_loop<SassItem>,
        do_parse!(tag!("@for") >> spacelike >> tag!("$") >>
@@ -446,9 +789,12 @@ fn if_with_no_else() {
                             selectors(b"p").unwrap().1,
                             vec![SassItem::Property("color".into(),
                                                     Value::black(),
-                                                    false)]),
+                                                    false,
+                                                    SourcePos::unknown())],
+                            SourcePos::unknown()),
                              SassItem::None],
-                        vec![])))
+                        vec![],
+                        SourcePos::unknown())))
 }
 
 #[test]
@@ -459,6 +805,7 @@ fn test_mixin_call_noargs() {
                         name: "foo".to_string(),
                         args: CallArgs::new(vec![]),
                         body: vec![],
+                        pos: SourcePos::unknown(),
                     }))
 }
 
@@ -472,6 +819,7 @@ fn test_mixin_call_pos_args() {
                             vec![(None, string("bar")),
                                  (None, string("baz"))]),
                         body: vec![],
+                        pos: SourcePos::unknown(),
                     }))
 }
 
@@ -485,6 +833,7 @@ fn test_mixin_call_named_args() {
                             vec![(Some("x".into()), string("bar")),
                                  (Some("y".into()), string("baz"))]),
                         body: vec![],
+                        pos: SourcePos::unknown(),
                     }))
 }
 
@@ -523,8 +872,10 @@ fn test_mixin_declaration() {
                        Value::List(
                            vec![string("baz"),
                                 Value::Variable("x".into())],
-                           ListSeparator::Space),
-                       false)],
+                           ListSeparator::Space,
+                           false),
+                       false,
+                       SourcePos::unknown())],
                }))
 }
 
@@ -545,13 +896,16 @@ fn test_mixin_declaration_default_and_subrules() {
                    body: vec![
                        SassItem::Property("foo-bar".into(),
                                           string("baz"),
-                                          false),
+                                          false,
+                                          SourcePos::unknown()),
                        SassItem::Rule(
                            selectors(b"foo, bar").unwrap().1,
                            vec![SassItem::Property(
                                     "property".into(),
                                     Value::Variable("b".into()),
-                                    false)]),
+                                    false,
+                                    SourcePos::unknown())],
+                           SourcePos::unknown()),
                        SassItem::None,
                        ]}))
 }
@@ -572,19 +926,104 @@ named!(return_stmt<SassItem>,
                  opt!(tag!(";")) >>
                  (SassItem::Return(v))));
 
+named!(diagnostic<SassItem>,
+       do_parse!(pos: position >>
+                 tag!("@") >>
+                 kind: alt_complete!(tag!("debug") | tag!("warn") |
+                                     tag!("error")) >>
+                 spacelike >>
+                 value: value_expression >> opt_spacelike >>
+                 opt!(tag!(";")) >>
+                 (match kind {
+                     b"debug" => SassItem::Debug(value, pos),
+                     b"warn" => SassItem::Warn(value, pos),
+                     _ => SassItem::Error(value, pos),
+                 })));
+
 named!(content_stmt<SassItem>,
        do_parse!(tag!("@content") >> opt_spacelike >>
                  opt!(tag!(";")) >>
                  (SassItem::Content)));
 
+named!(extend<SassItem>,
+       do_parse!(pos: position >>
+                 tag!("@extend") >> spacelike >>
+                 selectors: selectors >> opt_spacelike >>
+                 optional: opt!(tag!("!optional")) >> opt_spacelike >>
+                 opt!(tag!(";")) >>
+                 (SassItem::Extend(selectors, optional.is_some(), pos))));
+
+#[test]
+fn test_use_with() {
+    assert_eq!(use_rule(b"@use \"lib/_colors.scss\" with ($a: 1 2);\n"),
+               IResult::Done(&b"\n"[..],
+                    SassItem::Use {
+                        url: "lib/_colors.scss".into(),
+                        namespace: "colors".into(),
+                        config: vec![("a".into(),
+                                      Value::List(vec![Value::scalar(1),
+                                                       Value::scalar(2)],
+                                                  ListSeparator::Space,
+                                                  false))],
+                        pos: SourcePos::unknown(),
+                    }))
+}
+
+#[test]
+fn test_use_builtin_as_star() {
+    assert_eq!(use_rule(b"@use 'sass:math' as *;\n"),
+               IResult::Done(&b"\n"[..],
+                    SassItem::Use {
+                        url: "sass:math".into(),
+                        namespace: "*".into(),
+                        config: vec![],
+                        pos: SourcePos::unknown(),
+                    }))
+}
+
+#[test]
+fn test_forward_prefix_show() {
+    assert_eq!(forward_rule(b"@forward \"src\" as my-* show a-b, $c;\n"),
+               IResult::Done(&b"\n"[..],
+                    SassItem::Forward {
+                        url: "src".into(),
+                        filter: ForwardFilter {
+                            prefix: Some("my_".into()),
+                            show: Some(vec!["a_b".into(), "$c".into()]),
+                            hide: vec![],
+                        },
+                        pos: SourcePos::unknown(),
+                    }))
+}
+
+#[test]
+fn test_diagnostic() {
+    assert_eq!(diagnostic(b"@warn \"a #{$b}\";\n"),
+               IResult::Done(&b"\n"[..],
+                             SassItem::Warn(value_expression(b"\"a #{$b}\";")
+                                                .unwrap()
+                                                .1,
+                                            SourcePos::unknown())))
+}
+
+#[test]
+fn test_extend_optional() {
+    assert_eq!(extend(b"@extend %foo !optional;\n"),
+               IResult::Done(&b"\n"[..],
+                    SassItem::Extend(selectors(b"%foo").unwrap().1,
+                                     true,
+                                     SourcePos::unknown())))
+}
+
 named!(property<&[u8], SassItem>,
        do_parse!(opt_spacelike >>
+                 pos: position >>
                  name: name >> opt_spacelike >>
                  tag!(":") >> opt_spacelike >>
                  val: value_expression >>
                  imp: opt_important >> opt_spacelike >>
                  opt!(tag!(";")) >> opt_spacelike >>
-                 (SassItem::Property(name, val, imp))));
+                 (SassItem::Property(name, val, imp, pos))));
 
 named!(namespace_rule<SassItem>,
        do_parse!(opt_spacelike >>
@@ -617,7 +1056,8 @@ fn test_simple_property() {
                IResult::Done(&b""[..], SassItem::Property(
                    "color".to_string(),
                    Value::Color(r(255), r(0), r(0), one, Some("red".into())),
-                   false)))
+                   false,
+                   SourcePos::unknown())))
 }
 #[test]
 fn test_property_2() {
@@ -625,28 +1065,46 @@ fn test_property_2() {
                IResult::Done(&b""[..], SassItem::Property(
                    "background-position".to_string(),
                    Value::List(vec![percentage(90), percentage(50)],
-                               ListSeparator::Space),
-                   false)))
+                               ListSeparator::Space,
+                               false),
+                   false,
+                   SourcePos::unknown())))
 }
 
 #[cfg(test)]
 fn percentage(v: isize) -> Value {
-    Value::Numeric(Rational::from_integer(v), Unit::Percent, false, false)
+    Value::Numeric(BigRational::from_integer(v.into()),
+                   Unit::Percent.into(),
+                   false,
+                   false)
 }
 
 named!(variable_declaration<SassItem>,
-       do_parse!(tag!("$") >>
+       do_parse!(namespace: opt!(terminated!(name, tag!("."))) >>
+                 tag!("$") >>
                  name: name >> opt_spacelike >>
                  tag!(":") >> opt_spacelike >>
+                 pos: position >>
                  val: value_expression >> opt_spacelike >>
                  default: opt!(tag!("!default")) >> opt_spacelike >>
                  global: opt!(tag!("!global")) >> opt_spacelike >>
                  tag!(";") >> opt_spacelike >>
-                 (SassItem::VariableDeclaration {
-                     name: name,
-                     val: val,
-                     default: default.is_some(),
-                     global: global.is_some(),
+                 (match namespace {
+                     // A module variable is always assigned globally.
+                     Some(ns) => SassItem::VariableDeclaration {
+                         name: format!("{}.{}", ns, name),
+                         val: val,
+                         default: default.is_some(),
+                         global: true,
+                         pos: pos,
+                     },
+                     None => SassItem::VariableDeclaration {
+                         name: name,
+                         val: val,
+                         default: default.is_some(),
+                         global: global.is_some(),
+                         pos: pos,
+                     },
                  })));
 
 #[test]
@@ -658,6 +1116,7 @@ fn test_variable_declaration_simple() {
                         val: string("bar"),
                         default: false,
                         global: false,
+                        pos: SourcePos::unknown(),
                     }))
 }
 
@@ -669,9 +1128,11 @@ fn test_variable_declaration_global() {
                         name: "y".into(),
                         val: Value::List(
                             vec![string("some"), string("value")],
-                            ListSeparator::Space),
+                            ListSeparator::Space,
+                            false),
                         default: false,
                         global: true,
+                        pos: SourcePos::unknown(),
                     }))
 }
 
@@ -683,9 +1144,24 @@ fn test_variable_declaration_default() {
                         name: "y".into(),
                         val: Value::List(
                             vec![string("some"), string("value")],
-                            ListSeparator::Space),
+                            ListSeparator::Space,
+                            false),
                         default: true,
                         global: false,
+                        pos: SourcePos::unknown(),
+                    }))
+}
+
+#[test]
+fn test_variable_declaration_namespaced() {
+    assert_eq!(variable_declaration(b"lib.$color: foo;\n"),
+               IResult::Done(&b""[..],
+                    SassItem::VariableDeclaration {
+                        name: "lib.color".into(),
+                        val: string("foo"),
+                        default: false,
+                        global: true,
+                        pos: SourcePos::unknown(),
                     }))
 }
 
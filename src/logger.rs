@@ -0,0 +1,96 @@
+//! Reporting of `@debug` and `@warn` messages and deprecation warnings.
+//!
+//! Messages are sent to the logger of the `GlobalScope` of the
+//! compilation, which is a `StderrLogger` unless another logger is
+//! set by `GlobalScope::set_logger`.
+//! Each deprecation warning is sent only once in a compilation.
+use sourcepos::SourcePos;
+use std::collections::HashSet;
+use std::io::{Write, stderr};
+use std::sync::{Arc, Mutex};
+use value::{Quotes, Value};
+
+/// A logger handles the diagnostic messages of a compilation.
+pub trait Logger: Send + Sync {
+    /// Handle the message of a `@debug` rule at `pos`.
+    fn debug(&self, msg: &str, pos: &SourcePos);
+    /// Handle the message of a `@warn` rule at `pos`.
+    fn warn(&self, msg: &str, pos: &SourcePos);
+    /// Handle a warning about deprecated usage.
+    fn deprecation(&self, msg: &str);
+}
+
+/// The default `Logger`, writing messages to stderr in the same
+/// format as dart-sass.
+#[derive(Clone, Debug, Default)]
+pub struct StderrLogger;
+
+impl Logger for StderrLogger {
+    fn debug(&self, msg: &str, pos: &SourcePos) {
+        let _ = writeln!(&mut stderr(),
+                         "{}:{} DEBUG: {}",
+                         pos.file,
+                         pos.line,
+                         msg);
+    }
+    fn warn(&self, msg: &str, pos: &SourcePos) {
+        let _ = writeln!(&mut stderr(),
+                         "WARNING: {}\n    {} {}:{}  root stylesheet",
+                         msg,
+                         pos.file,
+                         pos.line,
+                         pos.column);
+    }
+    fn deprecation(&self, msg: &str) {
+        let _ = writeln!(&mut stderr(), "DEPRECATION WARNING: {}", msg);
+    }
+}
+
+/// The logger of a compilation.
+///
+/// This is held by the `GlobalScope`.  Clones share the record of
+/// deprecation warnings already sent, so each deprecation warning is
+/// sent only once in a compilation, also from modules.
+#[derive(Clone)]
+pub struct Log {
+    logger: Arc<dyn Logger>,
+    deprecations: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Log {
+    pub fn new(logger: Arc<dyn Logger>) -> Self {
+        Log { logger: logger, deprecations: Arc::default() }
+    }
+
+    pub fn debug(&self, value: &Value, pos: &SourcePos) {
+        self.logger.debug(&message(value), pos)
+    }
+
+    pub fn warn(&self, value: &Value, pos: &SourcePos) {
+        self.logger.warn(&message(value), pos)
+    }
+
+    /// Send a deprecation warning, unless the same warning is
+    /// already sent in this compilation.
+    pub fn deprecation(&self, msg: &str) {
+        if self.deprecations.lock().unwrap().insert(msg.into()) {
+            self.logger.deprecation(msg)
+        }
+    }
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log::new(Arc::new(StderrLogger))
+    }
+}
+
+/// The text of a message value, unquoted if it is a string.
+pub fn message(value: &Value) -> String {
+    match *value {
+        Value::Literal(ref s, _) => {
+            Value::Literal(s.clone(), Quotes::None).to_string()
+        }
+        ref v => v.to_string(),
+    }
+}
@@ -2,8 +2,10 @@ extern crate clap;
 extern crate rsass;
 
 use clap::{App, Arg, ArgMatches};
-use rsass::{Error, OutputStyle, compile_scss_file};
+use rsass::{Error, FileContext, Format, GlobalScope, OutputStyle};
+use std::fs::File;
 use std::io::{Write, stderr, stdout};
+use std::path::Path;
 use std::process::exit;
 
 fn main() {
@@ -14,12 +16,35 @@ fn main() {
         .arg(Arg::with_name("PRECISION")
             .long("precision")
             .takes_value(true)
-            .help("Ignored"))
+            .value_name("DIGITS")
+            .help("Number of digits after the decimal point in numeric \
+                   output.  Default is 10."))
         .arg(Arg::with_name("STYLE")
             .short("t")
             .long("style")
             .takes_value(true)
-            .help("Output style. Can be compact (default) or compressed."))
+            .possible_values(&["expanded", "nested", "compact", "compressed"])
+            .help("Output style. Can be expanded (default), nested, \
+                   compact or compressed."))
+        .arg(Arg::with_name("SHORT_COLORS")
+            .long("short-colors")
+            .help("Write colors in their shortest form (e.g. #fff or red) \
+                   in compressed output."))
+        .arg(Arg::with_name("LOAD_PATH")
+            .short("I")
+            .long("load-path")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("PATH")
+            .help("Add a path to search for imported files. \
+                   May be given multiple times, paths are searched in order."))
+        .arg(Arg::with_name("SOURCEMAP")
+            .long("sourcemap")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Write a source map to FILE. \
+                   Only allowed with a single INPUT file."))
         .arg(Arg::with_name("INPUT")
             .required(true)
             .multiple(true)
@@ -27,24 +52,65 @@ fn main() {
         .after_help("At least one INPUT file is required.")
         .get_matches();
 
-    match run(&args) {
+    if args.is_present("SOURCEMAP") &&
+       args.values_of("INPUT").map(|i| i.count()) != Some(1) {
+        writeln!(&mut stderr(),
+                 "Error: --sourcemap requires exactly one input file!")
+            .unwrap();
+        exit(1);
+    }
+    let precision = match args.value_of("PRECISION").map(|p| p.parse()) {
+        Some(Ok(precision)) => precision,
+        Some(Err(_)) => {
+            writeln!(&mut stderr(),
+                     "Error: --precision must be a non-negative integer")
+                .unwrap();
+            exit(1);
+        }
+        None => 10,
+    };
+    match run(&args, precision) {
         Ok(()) => (),
         Err(err) => {
-            writeln!(&mut stderr(), "Error: {}!", err).unwrap();
+            writeln!(&mut stderr(), "Error: {}", err).unwrap();
             exit(1);
         }
     }
 }
 
-fn run(args: &ArgMatches) -> Result<(), Error> {
-    let style = if args.value_of("STYLE") == Some("compressed") {
-        OutputStyle::Compressed
-    } else {
-        OutputStyle::Normal
+fn run(args: &ArgMatches, precision: usize) -> Result<(), Error> {
+    let format = Format {
+        style: match args.value_of("STYLE") {
+            Some(style) => style.parse()?,
+            None => OutputStyle::Expanded,
+        },
+        precision: precision,
+        short_colors: args.is_present("SHORT_COLORS"),
     };
-    if let Some(inputs) = args.values_of("INPUT") {
+    let mut file_context = FileContext::new();
+    for path in args.values_of("LOAD_PATH").into_iter().flatten() {
+        file_context.push_path(path.as_ref());
+    }
+    if let Some(mapfile) = args.value_of("SOURCEMAP") {
+        let name = args.value_of("INPUT").unwrap();
+        let (context, items) = file_context.load(name.as_ref())?;
+        let (mut result, map) =
+            format.write_root_with_sourcemap(&items,
+                                             &mut GlobalScope::new(),
+                                             context)?;
+        let css_name = Path::new(name).with_extension("css");
+        File::create(mapfile)
+            .and_then(|mut f| {
+                          f.write_all(map.to_json(&css_name.to_string_lossy())
+                                          .as_bytes())
+                      })?;
+        writeln!(&mut result, "/*# sourceMappingURL={} */", mapfile)?;
+        stdout().lock().write_all(&result)?;
+    } else if let Some(inputs) = args.values_of("INPUT") {
         for name in inputs {
-            let result = compile_scss_file(name.as_ref(), style.clone())?;
+            let (context, items) = file_context.load(name.as_ref())?;
+            let result =
+                format.write_root(&items, &mut GlobalScope::new(), context)?;
             let out = stdout();
             out.lock().write_all(&result)?;
         }
@@ -0,0 +1,341 @@
+//! Media query lists, as in the prelude of `@media`.
+use error::Error;
+use parseutil::{name, opt_spacelike, spacelike};
+use std::fmt;
+use value::{Value, value_expression};
+use variablescope::Scope;
+
+/// A comma-separated list of media queries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaQueries(pub Vec<MediaQuery>);
+
+/// A single media query, such as `only screen and (min-width: 10em)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaQuery {
+    /// `not` or `only`, if any.
+    pub modifier: Option<String>,
+    /// The media type, such as `screen`, if any.
+    pub media_type: Option<String>,
+    /// The features the media must match, such as `(color)` or
+    /// `(min-width: 10em)`.
+    pub features: Vec<(String, Option<Value>)>,
+}
+
+impl MediaQueries {
+    /// Evaluate the values of all features.
+    pub fn evaluate(&self, scope: &dyn Scope) -> Result<Self, Error> {
+        Ok(MediaQueries(self.0
+                            .iter()
+                            .map(|q| q.evaluate(scope))
+                            .collect::<Result<_, _>>()?))
+    }
+
+    /// Merge these queries with queries of a `@media` nested in them.
+    ///
+    /// Each query is merged with each nested query.  Pairs that can
+    /// never both match, as `screen` with `print`, are dropped.
+    /// Returns `None` if any pair cannot be written as a single
+    /// query, then the nested `@media` must be kept as it is.
+    pub fn merge(&self, inner: &MediaQueries) -> Option<MediaQueries> {
+        let mut merged = vec![];
+        for o in &self.0 {
+            for i in &inner.0 {
+                match o.merge(i) {
+                    Merged::Empty => (),
+                    Merged::Unrepresentable => return None,
+                    Merged::Query(q) => merged.push(q),
+                }
+            }
+        }
+        Some(MediaQueries(merged))
+    }
+}
+
+/// The result of merging two media queries.
+enum Merged {
+    /// The queries can never both match.
+    Empty,
+    /// The queries can both match, but that cannot be written as a
+    /// single query.
+    Unrepresentable,
+    Query(MediaQuery),
+}
+
+impl MediaQuery {
+    fn evaluate(&self, scope: &dyn Scope) -> Result<Self, Error> {
+        let mut features = Vec::with_capacity(self.features.len());
+        for &(ref name, ref value) in &self.features {
+            let value = match *value {
+                Some(ref v) => Some(v.evaluate(scope)?),
+                None => None,
+            };
+            features.push((name.clone(), value));
+        }
+        Ok(MediaQuery {
+               modifier: self.modifier.clone(),
+               media_type: self.media_type.clone(),
+               features: features,
+           })
+    }
+
+    /// Merge this query with a nested query, following the rules of
+    /// the reference implementation.
+    fn merge(&self, inner: &MediaQuery) -> Merged {
+        let outer_not = is_not(&self.modifier);
+        let inner_not = is_not(&inner.modifier);
+        let same_type = match (&self.media_type, &inner.media_type) {
+            (&Some(ref t1), &Some(ref t2)) => t1.eq_ignore_ascii_case(t2),
+            (&None, &None) => true,
+            _ => false,
+        };
+        let both = || {
+            self.features.iter().chain(inner.features.iter()).cloned().collect()
+        };
+        let none = None;
+        let (modifier, media_type, features) = if outer_not != inner_not {
+            if same_type {
+                let (neg, pos) = if outer_not {
+                    (self, inner)
+                } else {
+                    (inner, self)
+                };
+                return if neg.has_features_of(pos) {
+                           Merged::Empty
+                       } else {
+                           Merged::Unrepresentable
+                       };
+            } else if self.matches_all_types() || inner.matches_all_types() {
+                return Merged::Unrepresentable;
+            }
+            // Only the query that is not negated matters.
+            if outer_not {
+                (&inner.modifier, &inner.media_type, inner.features.clone())
+            } else {
+                (&self.modifier, &self.media_type, self.features.clone())
+            }
+        } else if outer_not {
+            // There is no way to write "neither screen nor print".
+            if !same_type {
+                return Merged::Unrepresentable;
+            }
+            // The query with more features is the narrower one, if
+            // it has all features of the other.
+            let (more, fewer) = if self.features.len() > inner.features.len() {
+                (self, inner)
+            } else {
+                (inner, self)
+            };
+            if !fewer.has_features_of(more) {
+                return Merged::Unrepresentable;
+            }
+            (&self.modifier, &self.media_type, more.features.clone())
+        } else if self.matches_all_types() {
+            // Omit the type if neither query wrote it.
+            let media_type = if self.media_type.is_none() &&
+                                inner.matches_all_types() {
+                &none
+            } else {
+                &inner.media_type
+            };
+            (&inner.modifier, media_type, both())
+        } else if inner.matches_all_types() {
+            (&self.modifier, &self.media_type, both())
+        } else if !same_type {
+            return Merged::Empty;
+        } else if self.modifier.is_some() {
+            (&self.modifier, &self.media_type, both())
+        } else {
+            (&inner.modifier, &self.media_type, both())
+        };
+        Merged::Query(MediaQuery {
+                          modifier: modifier.clone(),
+                          media_type: media_type.clone(),
+                          features: features,
+                      })
+    }
+
+    /// True if all features of this query are features of `other`.
+    fn has_features_of(&self, other: &MediaQuery) -> bool {
+        self.features.iter().all(|f| other.features.contains(f))
+    }
+
+    /// True if this query has no media type, or the type `all`.
+    fn matches_all_types(&self) -> bool {
+        self.media_type
+            .as_ref()
+            .map(|t| t.eq_ignore_ascii_case("all"))
+            .unwrap_or(true)
+    }
+}
+
+fn is_not(modifier: &Option<String>) -> bool {
+    modifier.as_ref().map(|m| m.eq_ignore_ascii_case("not")).unwrap_or(false)
+}
+
+named!(pub media_queries<MediaQueries>,
+       map!(separated_nonempty_list!(
+           complete!(delimited!(opt_spacelike, tag!(","), opt_spacelike)),
+           media_query),
+            MediaQueries));
+
+named!(media_query<MediaQuery>,
+       alt_complete!(
+           do_parse!(modifier: opt!(complete!(terminated!(
+               alt_complete!(tag_no_case!("not") | tag_no_case!("only")),
+               spacelike))) >>
+                     media_type: name >>
+                     features: many0!(preceded!(and, feature)) >>
+                     (MediaQuery {
+                         modifier: modifier.map(|m| {
+                             String::from_utf8_lossy(m).into_owned()
+                         }),
+                         media_type: Some(media_type),
+                         features: features,
+                     })) |
+           do_parse!(first: feature >>
+                     rest: many0!(preceded!(and, feature)) >>
+                     (MediaQuery {
+                         modifier: None,
+                         media_type: None,
+                         features: Some(first)
+                             .into_iter()
+                             .chain(rest)
+                             .collect(),
+                     }))));
+
+named!(and<()>,
+       complete!(do_parse!(spacelike >> tag_no_case!("and") >> spacelike >>
+                           ())));
+
+named!(feature<(String, Option<Value>)>,
+       do_parse!(tag!("(") >> opt_spacelike >>
+                 name: name >> opt_spacelike >>
+                 value: opt!(complete!(do_parse!(tag!(":") >>
+                                                 opt_spacelike >>
+                                                 value: value_expression >>
+                                                 opt_spacelike >>
+                                                 (value)))) >>
+                 tag!(")") >>
+                 (name, value)));
+
+impl fmt::Display for MediaQueries {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((first, rest)) = self.0.split_first() {
+            first.fmt(out)?;
+            for item in rest {
+                write!(out, ", {}", item)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MediaQuery {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(ref media_type) = self.media_type {
+            parts.push(match self.modifier {
+                           Some(ref modifier) => {
+                               format!("{} {}", modifier, media_type)
+                           }
+                           None => media_type.clone(),
+                       });
+        }
+        for &(ref name, ref value) in &self.features {
+            parts.push(match *value {
+                           Some(ref v) => format!("({}: {})", name, v),
+                           None => format!("({})", name),
+                       });
+        }
+        out.write_str(&parts.join(" and "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nom::IResult::Done;
+
+    #[test]
+    fn parse_type_and_features() {
+        let (rest, q) =
+            media_queries(b"only screen and (min-width: 10em) and (color) {")
+                .unwrap();
+        assert_eq!(rest, b" {");
+        assert_eq!(q.0.len(), 1);
+        assert_eq!(q.0[0].modifier, Some("only".into()));
+        assert_eq!(q.0[0].media_type, Some("screen".into()));
+        assert_eq!(q.0[0].features.len(), 2);
+        assert_eq!(q.to_string(),
+                   "only screen and (min-width: 10em) and (color)");
+    }
+
+    #[test]
+    fn parse_list() {
+        assert_eq!(media_queries(b"print, (max-width: 5px){")
+                       .map(|q| q.to_string()),
+                   Done(&b"{"[..], "print, (max-width: 5px)".to_string()))
+    }
+
+    #[test]
+    fn merge_features() {
+        assert_eq!(merge("screen", "(min-width: 10em)"),
+                   "screen and (min-width: 10em)")
+    }
+
+    #[test]
+    fn merge_lists() {
+        assert_eq!(merge("screen, print", "(color), (monochrome)"),
+                   "screen and (color), screen and (monochrome), \
+                    print and (color), print and (monochrome)")
+    }
+
+    #[test]
+    fn merge_not() {
+        assert_eq!(merge("not print", "screen and (color)"),
+                   "screen and (color)")
+    }
+
+    #[test]
+    fn merge_inner_not() {
+        assert_eq!(merge("print and (x)", "not screen and (color)"),
+                   "print and (x)")
+    }
+
+    #[test]
+    fn merge_different_types() {
+        assert_eq!(merge("screen", "print"), "(empty)")
+    }
+
+    #[test]
+    fn merge_not_type_with_features() {
+        assert_eq!(merge("not print", "(color)"), "(nested)")
+    }
+
+    #[test]
+    fn merge_not_with_different_features() {
+        assert_eq!(merge("not screen and (a)", "not screen and (b)"),
+                   "(nested)")
+    }
+
+    #[test]
+    fn merge_not_with_more_features() {
+        assert_eq!(merge("not screen and (a)", "not screen and (a) and (b)"),
+                   "not screen and (a) and (b)")
+    }
+
+    #[test]
+    fn merge_not_same_type() {
+        assert_eq!(merge("not screen", "screen and (color)"), "(empty)")
+    }
+
+    fn merge(outer: &str, inner: &str) -> String {
+        let outer = media_queries(outer.as_bytes()).unwrap().1;
+        let inner = media_queries(inner.as_bytes()).unwrap().1;
+        match outer.merge(&inner) {
+            Some(ref q) if q.0.is_empty() => "(empty)".into(),
+            Some(q) => q.to_string(),
+            None => "(nested)".into(),
+        }
+    }
+}
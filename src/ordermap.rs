@@ -0,0 +1,101 @@
+//! An ordered map, as used for sass maps.
+
+use std::iter::FromIterator;
+use std::slice;
+use std::vec;
+
+/// A map that keeps its entries in insertion order.
+///
+/// Sass maps are typically small, so this is simply a vector of
+/// key-value pairs with linear lookup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderMap<K, V>(Vec<(K, V)>);
+
+impl<K: PartialEq, V> OrderMap<K, V> {
+    /// Create a new empty map.
+    pub fn new() -> Self {
+        OrderMap(Vec::new())
+    }
+    /// Insert a value for a key.
+    ///
+    /// If the key was already present, the value is replaced but
+    /// the key keeps its position.
+    pub fn insert(&mut self, key: K, val: V) {
+        if let Some(pos) = self.position(&key) {
+            self.0[pos].1 = val;
+        } else {
+            self.0.push((key, val));
+        }
+    }
+    /// Get the value for a key, if present.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.position(key).map(|pos| &self.0[pos].1)
+    }
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.position(key).is_some()
+    }
+    /// Remove a key from the map, returning its value if it was present.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.position(key).map(|pos| self.0.remove(pos).1)
+    }
+    pub fn keys(&self) -> Vec<&K> {
+        self.0.iter().map(|&(ref k, _)| k).collect()
+    }
+    pub fn values(&self) -> Vec<&V> {
+        self.0.iter().map(|&(_, ref v)| v).collect()
+    }
+    pub fn iter<'a>(&'a self) -> slice::Iter<'a, (K, V)> {
+        self.0.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    fn position(&self, key: &K) -> Option<usize> {
+        self.0.iter().position(|&(ref k, _)| k == key)
+    }
+}
+
+/// Create a map from pairs without merging duplicate keys.
+///
+/// This is used for parsed map literals, where keys are not known
+/// until the map is evaluated, and duplicates are reported then.
+pub fn with_duplicates<K, V>(items: Vec<(K, V)>) -> OrderMap<K, V> {
+    OrderMap(items)
+}
+
+impl<K: PartialEq, V> Default for OrderMap<K, V> {
+    fn default() -> Self {
+        OrderMap::new()
+    }
+}
+
+impl<K: PartialEq, V> FromIterator<(K, V)> for OrderMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut result = OrderMap::new();
+        for (k, v) in iter {
+            result.insert(k, v);
+        }
+        result
+    }
+}
+
+impl<K, V> IntoIterator for OrderMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = vec::IntoIter<(K, V)>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[test]
+fn insert_keeps_order() {
+    let mut map = OrderMap::new();
+    map.insert("b", 1);
+    map.insert("a", 2);
+    map.insert("b", 3);
+    assert_eq!(map.into_iter().collect::<Vec<_>>(),
+               vec![("b", 3), ("a", 2)]);
+}
@@ -1,31 +1,172 @@
-use super::{FileContext, SassItem, parse_scss_file};
+use super::{FileContext, SassItem};
 use error::Error;
 use formalargs::FormalArgs;
-use selectors::Selectors;
-use std::ascii::AsciiExt;
-use std::fmt;
+use functions::get_builtin_module;
+use logger::{self, Log};
+use media::MediaQueries;
+use selectors::{Selector, Selectors};
+use sourcemap::SourceMap;
+use sourcepos::SourcePos;
+use std::{cmp, fmt, io, mem};
+use std::collections::BTreeMap;
 use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
 use value::Value;
-use variablescope::{Scope, ScopeImpl};
+use variablescope::{GlobalScope, Member, Module, Scope, ScopeImpl,
+                    check_member};
 
 /// Selected target format.
 /// Only formats that are variants of this type are supported by rsass.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OutputStyle {
-    Normal, // TODO What should be the name of this format?
+    /// The same as `Expanded`.
+    Normal,
+    /// Each property on a line of its own, and the closing brace of
+    /// each rule on a line of its own.
+    Expanded,
+    /// Like `Expanded`, but nested rules are indented as in the
+    /// source, and the closing brace is at the end of the last line
+    /// of each rule.
+    Nested,
+    /// Each rule on a single line.
+    Compact,
+    /// As little whitespace as possible.
     Compressed,
 }
 
+impl FromStr for OutputStyle {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "expanded" => Ok(OutputStyle::Expanded),
+            "nested" => Ok(OutputStyle::Nested),
+            "compact" => Ok(OutputStyle::Compact),
+            "compressed" => Ok(OutputStyle::Compressed),
+            s => Err(Error::BadValue(format!("Unknown output style {:?}", s))),
+        }
+    }
+}
+
 impl OutputStyle {
     /// Write a slice of sass items in this format.
     /// The `file_context` is needed if there are `@import` statements
     /// in the sass file.
     pub fn write_root(&self,
                       items: &[SassItem],
-                      globals: &mut Scope,
+                      globals: &mut dyn Scope,
+                      file_context: FileContext)
+                      -> Result<Vec<u8>, Error> {
+        Format::from(*self).write_root(items, globals, file_context)
+    }
+    /// Write a slice of sass items in this format, and get a source
+    /// map for the result.
+    pub fn write_root_with_sourcemap(&self,
+                                     items: &[SassItem],
+                                     globals: &mut dyn Scope,
+                                     file_context: FileContext)
+                                     -> Result<(Vec<u8>, SourceMap), Error> {
+        Format::from(*self)
+            .write_root_with_sourcemap(items, globals, file_context)
+    }
+}
+
+/// How css is written: the output style and the options for writing
+/// numbers and colors.
+///
+/// A `Format` from an `OutputStyle` has the default precision and
+/// writes colors as they are given.
+///
+/// # Example
+/// ```
+/// use rsass::{FileContext, Format, GlobalScope, OutputStyle,
+///             parse_scss_data};
+///
+/// let format = Format {
+///     style: OutputStyle::Compressed,
+///     precision: 3,
+///     short_colors: true,
+/// };
+/// let items = parse_scss_data(b"a { b: (1/3); c: #FF0000; }").unwrap();
+/// let css = format
+///     .write_root(&items, &mut GlobalScope::new(), FileContext::new())
+///     .unwrap();
+/// assert_eq!(css, b"a{b:.333;c:red}\n")
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Format {
+    /// The output style.
+    pub style: OutputStyle,
+    /// The number of decimals written for numbers that are not
+    /// integers.
+    ///
+    /// Numbers are also compared at this precision: two numbers are
+    /// equal if they differ by less than one in the decimal after
+    /// the last written one.  So numbers that are written the same
+    /// are not always equal; at precision 3, `1/3` is written as
+    /// `0.333`, but `(1/3) == 0.333` is false.
+    /// The default precision is 10, as in dart-sass.
+    pub precision: usize,
+    /// Write colors in compressed output in their shortest form,
+    /// such as `red` or `#0000`, rather than as the author spelled
+    /// them.
+    pub short_colors: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            style: OutputStyle::Normal,
+            precision: 10,
+            short_colors: false,
+        }
+    }
+}
+
+impl From<OutputStyle> for Format {
+    fn from(style: OutputStyle) -> Self {
+        Format { style: style, ..Format::default() }
+    }
+}
+
+/// A value together with the `Format` to write it in.
+///
+/// The `Display` implementation of a `Formatted` value writes it as
+/// css in that format.
+pub struct Formatted<'a, T: 'a> {
+    pub value: &'a T,
+    pub format: Format,
+}
+
+impl Format {
+    /// The default format, but with a specific `precision`.
+    ///
+    /// This is used when values are made into strings during
+    /// evaluation, as for interpolation.
+    pub fn with_precision(precision: usize) -> Self {
+        Format { precision: precision, ..Format::default() }
+    }
+    /// Write a slice of sass items in this format.
+    /// The `file_context` is needed if there are `@import` statements
+    /// in the sass file.
+    pub fn write_root(&self,
+                      items: &[SassItem],
+                      globals: &mut dyn Scope,
                       file_context: FileContext)
                       -> Result<Vec<u8>, Error> {
-        let mut result = CssWriter::new(self.clone());
+        self.write_root_with_sourcemap(items, globals, file_context)
+            .map(|(css, _map)| css)
+    }
+    /// Write a slice of sass items in this format, and get a source
+    /// map for the result.
+    pub fn write_root_with_sourcemap(&self,
+                                     items: &[SassItem],
+                                     globals: &mut dyn Scope,
+                                     file_context: FileContext)
+                                     -> Result<(Vec<u8>, SourceMap), Error> {
+        globals.set_precision(self.precision);
+        let mut result = CssWriter::new(*self);
         for item in items {
             self.handle_root_item(item, globals, &file_context, &mut result)?;
         }
@@ -33,21 +174,23 @@ impl OutputStyle {
     }
     fn handle_root_item(&self,
                         item: &SassItem,
-                        scope: &mut Scope,
+                        scope: &mut dyn Scope,
                         file_context: &FileContext,
                         result: &mut CssWriter)
                         -> Result<(), Error> {
         match *item {
-            SassItem::Import(ref name) => {
-                let name = name.evaluate(scope);
+            SassItem::Import(ref name, ref pos) => {
+                let name = name.evaluate(scope)?;
                 if let Value::Literal(ref x, _) = name {
-                    if let Some((sub_context, file)) =
-                        file_context.find_file(x.as_ref()) {
-                        for item in parse_scss_file(&file)? {
+                    if let Some(file) = file_context.find_file(x) {
+                        let (sub_context, items) = file_context.load(&file)
+                            .map_err(|e| e.imported_from(pos))?;
+                        for item in items {
                             self.handle_root_item(&item,
                                                   scope,
                                                   &sub_context,
-                                                  result)?;
+                                                  result)
+                                .map_err(|e| e.imported_from(pos))?;
                         }
                     } else {
                         write!(result.to_imports(),
@@ -62,56 +205,115 @@ impl OutputStyle {
                            if self.is_compressed() { "" } else { "\n" })?;
                 }
             }
+            SassItem::Use { ref url, ref namespace, ref config, ref pos } => {
+                if namespace != "*" && scope.get_module(namespace).is_some() {
+                    return Err(Error::eval(&format!("There's already a \
+                                                     module with namespace \
+                                                     \"{}\".",
+                                                    namespace),
+                                           pos));
+                }
+                let config = config.iter()
+                    .map(|&(ref name, ref val)| {
+                             Ok((name.clone(), val.evaluate(scope)?))
+                         })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let module = self.load_module(url,
+                                              &config,
+                                              pos,
+                                              scope.get_log(),
+                                              file_context,
+                                              result)?;
+                scope.define_module(namespace, module);
+            }
+            SassItem::Forward { ref url, ref filter, ref pos } => {
+                let module = self.load_module(url,
+                                              &[],
+                                              pos,
+                                              scope.get_log(),
+                                              file_context,
+                                              result)?;
+                scope.forward_module(module, filter.clone());
+            }
             SassItem::VariableDeclaration {
                 ref name,
                 ref val,
                 ref default,
                 ref global,
+                ref pos,
             } => {
-                if *default {
-                    scope.define_default(name, val, *global);
+                let defined = if *default {
+                    scope.define_default(name, val, *global)
                 } else if *global {
-                    scope.define_global(name, val);
+                    scope.define_global(name, val)
                 } else {
-                    scope.define(name, val);
-                }
+                    scope.define(name, val)
+                };
+                defined.map_err(|e| e.at(pos))?;
+            }
+            SassItem::Debug(ref value, ref pos) => {
+                scope.get_log().debug(&value.evaluate(scope)?, pos);
+            }
+            SassItem::Warn(ref value, ref pos) => {
+                scope.get_log().warn(&value.evaluate(scope)?, pos);
+            }
+            SassItem::Error(ref value, ref pos) => {
+                let msg = logger::message(&value.evaluate(scope)?);
+                return Err(Error::eval(&msg, pos));
             }
             SassItem::AtRule { ref name, ref args, ref body } => {
-                result.do_separate()?;
-                let args = args.evaluate(scope);
-                write!(result.to_content(), "@{} {}", name, args)?;
-                if let &Some(ref body) = body {
-                    if self.is_compressed() || args.is_null() {
-                        write!(result.to_content(), "{{")?;
-                    } else {
-                        write!(result.to_content(), " {{")?;
-                    }
-                    let mut direct = vec![];
-                    let mut sub = vec![];
-                    self.handle_body(&mut direct,
-                                     &mut sub,
+                let args = args.evaluate(scope)?;
+                let body = if let Some(ref body) = *body {
+                    let mut block = CssBlock::default();
+                    self.handle_body(&mut block,
+                                     &mut result.extends,
                                      &mut ScopeImpl::sub(scope),
                                      &Selectors::root(),
                                      body,
-                                     file_context,
-                                     2)?;
-                    if !sub.is_empty() {
-                        result.do_indent(0)?;
-                        result.to_content().write_all(&sub)?;
-                    }
-                    self.write_items(result.to_content(), &direct, 2)?;
-                    write!(result.to_content(), "}}")?;
+                                     file_context)?;
+                    Some(block)
                 } else {
-                    write!(result.to_content(), ";")?;
-                }
+                    None
+                };
+                result.items.push(CssItem::AtRule(name.clone(), args, body));
+            }
+            SassItem::Media(ref queries, ref body, ref pos) => {
+                let queries = queries.evaluate(scope).map_err(|e| e.at(pos))?;
+                let mut block = CssBlock::default();
+                let first_extend = result.extends.len();
+                self.handle_body(&mut block,
+                                 &mut result.extends,
+                                 &mut ScopeImpl::sub(scope),
+                                 &Selectors::root(),
+                                 body,
+                                 file_context)?;
+                in_media(&mut result.extends[first_extend..], &queries);
+                result.items.extend(media_items(queries, block));
             }
 
             SassItem::MixinDeclaration { ref name, ref args, ref body } => {
                 scope.define_mixin(name, args, body)
             }
-            SassItem::MixinCall { ref name, ref args, ref body } => {
+            SassItem::MixinCall { ref name, ref args, ref body, ref pos } => {
                 if let Some((m_args, m_body)) = scope.get_mixin(name) {
-                    let mut scope = m_args.eval(scope, args);
+                    let mut scope =
+                        m_args.eval(scope, args).map_err(|e| e.at(pos))?;
+                    scope.define_mixin("%%BODY%%",
+                                       &FormalArgs::default(),
+                                       body);
+                    for item in m_body {
+                        self.handle_root_item(&item,
+                                              &mut scope,
+                                              file_context,
+                                              result)?;
+                    }
+                } else if let Some((module, name)) =
+                    scope.get_mixin_module(name) {
+                    let (m_args, m_body, m_scope) =
+                        module.get_mixin(&name).unwrap();
+                    let mut scope = args.xyzzy(scope)
+                        .and_then(|args| m_args.eval(m_scope, &args))
+                        .map_err(|e| e.at(pos))?;
                     scope.define_mixin("%%BODY%%",
                                        &FormalArgs::default(),
                                        body);
@@ -122,7 +324,9 @@ impl OutputStyle {
                                               result)?;
                     }
                 } else {
-                    panic!(format!("Unknown mixin {}({:?})", name, args))
+                    check_member(scope, name, Member::Mixin)
+                        .map_err(|e| e.at(pos))?;
+                    return Err(Error::eval("Undefined mixin.", pos));
                 }
             }
             SassItem::Content => {
@@ -136,20 +340,21 @@ impl OutputStyle {
                 panic!("Return not allowed in global context");
             }
 
-            SassItem::IfStatement(ref cond, ref do_if, ref do_else) => {
-                let cond = cond.evaluate(scope).is_true();
+            SassItem::IfStatement(ref cond,
+                                  ref do_if,
+                                  ref do_else,
+                                  ref pos) => {
+                let cond = cond.evaluate(scope).map_err(|e| e.at(pos))?;
+                let cond = cond.is_true();
                 let items = if cond { do_if } else { do_else };
                 for item in items {
                     self.handle_root_item(item, scope, file_context, result)?;
                 }
             }
-            SassItem::Each(ref name, ref values, ref body) => {
-                let values = match values.evaluate(scope) {
-                    Value::List(v, _) => v,
-                    v => vec![v],
-                };
-                for value in values {
-                    scope.define(name, &value);
+            SassItem::Each(ref names, ref values, ref body, ref pos) => {
+                let values = values.evaluate(scope).map_err(|e| e.at(pos))?;
+                for value in values.iter_items() {
+                    scope.define_multi(names, &value)?;
                     for item in body {
                         self.handle_root_item(item,
                                               scope,
@@ -165,12 +370,12 @@ impl OutputStyle {
                 inclusive,
                 ref body,
             } => {
-                let from = from.evaluate(scope).integer_value()?;
-                let to = to.evaluate(scope).integer_value()?;
+                let from = from.evaluate(scope)?.integer_value()?;
+                let to = to.evaluate(scope)?.integer_value()?;
                 let to = if inclusive { to + 1 } else { to };
                 for value in from..to {
                     let mut scope = ScopeImpl::sub(scope);
-                    scope.define(name, &Value::scalar(value));
+                    scope.define(name, &Value::scalar(value))?;
                     for item in body {
                         self.handle_root_item(item,
                                               &mut scope,
@@ -181,7 +386,7 @@ impl OutputStyle {
             }
             SassItem::While(ref cond, ref body) => {
                 let mut scope = ScopeImpl::sub(scope);
-                while cond.evaluate(&scope).is_true() {
+                while cond.evaluate(&scope)?.is_true() {
                     for item in body {
                         self.handle_root_item(item,
                                               &mut scope,
@@ -191,15 +396,20 @@ impl OutputStyle {
                 }
             }
 
-            SassItem::Rule(ref s, ref b) => {
-                result.do_separate()?;
-                self.write_rule(s,
-                                b,
-                                result.to_content(),
-                                scope,
-                                None,
-                                file_context,
-                                0)?;
+            SassItem::Rule(ref s, ref b, ref pos) => {
+                let rule = self.handle_rule(s,
+                                            b,
+                                            pos,
+                                            scope,
+                                            None,
+                                            file_context,
+                                            &mut result.extends)?;
+                result.items.push(rule);
+            }
+            SassItem::Extend(_, _, ref pos) => {
+                return Err(Error::eval("@extend may only be used within \
+                                        style rules.",
+                                       pos));
             }
             SassItem::NamespaceRule(..) => {
                 panic!("Global namespaced property not allowed");
@@ -209,160 +419,229 @@ impl OutputStyle {
             }
             SassItem::Comment(ref c) => {
                 if !self.is_compressed() {
-                    result.do_separate()?;
-                    write!(result.to_content(), "/*{}*/", c)?;
+                    result.items.push(CssItem::Comment(c.clone()));
                 }
             }
             SassItem::None => (),
         }
         Ok(())
     }
-    fn write_rule(&self,
-                  selectors: &Selectors,
-                  body: &[SassItem],
-                  out: &mut Write,
-                  scope: &mut Scope,
-                  parent: Option<&Selectors>,
-                  file_context: &FileContext,
-                  indent: usize)
-                  -> Result<(), Error> {
+    /// Load a module for `@use` or `@forward`.
+    ///
+    /// A module file is only evaluated, and its css emitted, the
+    /// first time it is loaded.
+    /// The `config` variables are defined in the module before it
+    /// is evaluated, to override its `!default` variables.
+    /// Messages from the module are sent to `log`.
+    fn load_module(&self,
+                   url: &str,
+                   config: &[(String, Value)],
+                   pos: &SourcePos,
+                   log: &Log,
+                   file_context: &FileContext,
+                   result: &mut CssWriter)
+                   -> Result<Arc<Module>, Error> {
+        if let Some(name) = url.strip_prefix("sass:") {
+            return get_builtin_module(name)
+                .map(Arc::new)
+                .ok_or_else(|| {
+                                Error::eval(&format!("Unknown built-in \
+                                                      module {}.",
+                                                     url),
+                                            pos)
+                            });
+        }
+        let file = file_context
+            .find_file(url)
+            .ok_or_else(|| {
+                            Error::eval("Can't find stylesheet to import.",
+                                        pos)
+                        })?;
+        if let Some(module) = result.modules.get(&file) {
+            if !config.is_empty() {
+                return Err(Error::eval("This module was already loaded, \
+                                        so it can't be configured using \
+                                        \"with\".",
+                                       pos));
+            }
+            return Ok(module.clone());
+        }
+        let (sub_context, items) = file_context.load(&file)
+            .map_err(|e| e.imported_from(pos))?;
+        let mut scope = GlobalScope::new();
+        scope.set_precision(self.precision);
+        scope.set_log(log.clone());
+        for &(ref name, ref value) in config {
+            scope.define(name, value)?;
+        }
+        for item in items {
+            self.handle_root_item(&item, &mut scope, &sub_context, result)
+                .map_err(|e| e.imported_from(pos))?;
+        }
+        let module = Arc::new(Module::new(scope));
+        result.modules.insert(file, module.clone());
+        Ok(module)
+    }
+    fn handle_rule(&self,
+                   selectors: &Selectors,
+                   body: &[SassItem],
+                   pos: &SourcePos,
+                   scope: &mut dyn Scope,
+                   parent: Option<&Selectors>,
+                   file_context: &FileContext,
+                   extends: &mut Vec<Extend>)
+                   -> Result<CssItem, Error> {
         let selectors = selectors.inside(parent);
-        let mut direct = Vec::new();
-        let mut sub = Vec::new();
-        self.handle_body(&mut direct,
-                         &mut sub,
+        let mut block = CssBlock::default();
+        self.handle_body(&mut block,
+                         extends,
                          &mut ScopeImpl::sub(scope),
                          &selectors,
                          body,
-                         file_context,
-                         indent)?;
-        if !direct.is_empty() {
-            self.do_indent_no_lf(out, indent)?;
-            if self.is_compressed() {
-                write!(out, "{:#}{{", selectors)?;
-            } else {
-                write!(out, "{} {{", selectors)?;
-            }
-            self.write_items(out, &direct, indent + 2)?;
-            write!(out, "}}")?;
-            self.do_indent(out, 0)?;
-        }
-        out.write_all(&sub)?;
-        Ok(())
+                         file_context)?;
+        Ok(CssItem::Rule(selectors, block, pos.clone()))
     }
 
     fn handle_body(&self,
-                   direct: &mut Vec<CssBodyItem>,
-                   sub: &mut Write,
-                   scope: &mut Scope,
+                   block: &mut CssBlock,
+                   extends: &mut Vec<Extend>,
+                   scope: &mut dyn Scope,
                    selectors: &Selectors,
                    body: &[SassItem],
-                   file_context: &FileContext,
-                   indent: usize)
+                   file_context: &FileContext)
                    -> Result<(), Error> {
         for b in body {
             match *b {
-                SassItem::Import(ref name) => {
-                    let name = name.evaluate(scope);
+                SassItem::Import(ref name, ref pos) => {
+                    let name = name.evaluate(scope)?;
                     if let Value::Literal(ref x, _) = name {
-                        let (sub_context, file) = file_context.file(x.as_ref());
-                        let items = parse_scss_file(&file)?;
-                        self.handle_body(direct,
-                                         sub,
+                        let file = file_context
+                            .find_file(x)
+                            .unwrap_or_else(|| file_context.file(x.as_ref()).1);
+                        let (sub_context, items) = file_context.load(&file)
+                            .map_err(|e| e.imported_from(pos))?;
+                        self.handle_body(block,
+                                         extends,
                                          scope,
                                          selectors,
                                          &items,
-                                         &sub_context,
-                                         0)?;
+                                         &sub_context)
+                            .map_err(|e| e.imported_from(pos))?;
                     } else {
                         // TODO writeln!(direct, "@import {};", name)?;
                     }
                 }
+                SassItem::Use { ref pos, .. } |
+                SassItem::Forward { ref pos, .. } => {
+                    return Err(Error::eval("This at-rule is not allowed here.",
+                                           pos));
+                }
                 SassItem::VariableDeclaration {
                     ref name,
                     ref val,
                     default,
                     global,
+                    ref pos,
                 } => {
-                    if default {
-                        scope.define_default(name, val, global);
+                    let defined = if default {
+                        scope.define_default(name, val, global)
                     } else if global {
-                        scope.define_global(name, val);
+                        scope.define_global(name, val)
                     } else {
-                        scope.define(name, val);
-                    }
+                        scope.define(name, val)
+                    };
+                    defined.map_err(|e| e.at(pos))?;
+                }
+                SassItem::Debug(ref value, ref pos) => {
+                    scope.get_log().debug(&value.evaluate(scope)?, pos);
+                }
+                SassItem::Warn(ref value, ref pos) => {
+                    scope.get_log().warn(&value.evaluate(scope)?, pos);
+                }
+                SassItem::Error(ref value, ref pos) => {
+                    let msg = logger::message(&value.evaluate(scope)?);
+                    return Err(Error::eval(&msg, pos));
                 }
                 SassItem::AtRule { ref name, ref args, ref body } => {
-                    write!(sub, "@{} {}", name, args)?;
-                    if let &Some(ref body) = body {
-                        if self.is_compressed() {
-                            write!(sub, "{{")?;
-                        } else {
-                            write!(sub, " {{")?;
-                        }
-
-                        let mut s1 = vec![];
-                        let mut s2 = vec![];
-                        self.handle_body(&mut s1,
-                                         &mut s2,
-                                         &mut ScopeImpl::sub(scope),
-                                         selectors,
-                                         body,
-                                         file_context,
-                                         2)?;
-
-                        if !s1.is_empty() {
-                            self.do_indent(sub, 2)?;
-                            if self.is_compressed() {
-                                write!(sub, "{:#}{{", selectors)?;
-                            } else {
-                                write!(sub, "{} {{", selectors)?;
-                            }
-                            self.write_items(sub, &s1, 4)?;
-                            write!(sub, "}}")?;
-                        }
-                        self.do_indent(sub, 0)?;
-                        if !s2.is_empty() {
-                            sub.write_all(&s2)?;
-                        }
-                        write!(sub, "}}")?;
+                    let args = args.evaluate(scope)?;
+                    let body = if let Some(ref body) = *body {
+                        Some(self.handle_at_rule_body(extends,
+                                                      scope,
+                                                      selectors,
+                                                      body,
+                                                      file_context)?)
                     } else {
-                        write!(sub, ";")?;
-                    }
+                        None
+                    };
+                    block.sub.push(CssItem::AtRule(name.clone(), args, body));
+                }
+                SassItem::Media(ref queries, ref body, ref pos) => {
+                    let queries =
+                        queries.evaluate(scope).map_err(|e| e.at(pos))?;
+                    let first_extend = extends.len();
+                    let body = self.handle_at_rule_body(extends,
+                                                        scope,
+                                                        selectors,
+                                                        body,
+                                                        file_context)?;
+                    in_media(&mut extends[first_extend..], &queries);
+                    block.sub.extend(media_items(queries, body));
                 }
 
                 SassItem::MixinDeclaration { ref name, ref args, ref body } => {
                     scope.define_mixin(name, args, body);
                 }
-                SassItem::MixinCall { ref name, ref args, ref body } => {
+                SassItem::MixinCall {
+                    ref name,
+                    ref args,
+                    ref body,
+                    ref pos,
+                } => {
                     if let Some((m_args, m_body)) = scope.get_mixin(name) {
-                        let mut argscope = m_args.eval(scope, args);
+                        let mut argscope =
+                            m_args.eval(scope, args).map_err(|e| e.at(pos))?;
                         argscope.define_mixin("%%BODY%%",
                                               &FormalArgs::default(),
                                               body);
-                        self.handle_body(direct,
-                                         sub,
+                        self.handle_body(block,
+                                         extends,
                                          &mut argscope,
                                          selectors,
                                          &m_body,
-                                         file_context,
-                                         indent)?;
+                                         file_context)?;
+                    } else if let Some((module, name)) =
+                        scope.get_mixin_module(name) {
+                        let (m_args, m_body, m_scope) =
+                            module.get_mixin(&name).unwrap();
+                        let mut argscope = args.xyzzy(scope)
+                            .and_then(|args| m_args.eval(m_scope, &args))
+                            .map_err(|e| e.at(pos))?;
+                        argscope.define_mixin("%%BODY%%",
+                                              &FormalArgs::default(),
+                                              body);
+                        self.handle_body(block,
+                                         extends,
+                                         &mut argscope,
+                                         selectors,
+                                         &m_body,
+                                         file_context)?;
                     } else {
-                        direct.push(CssBodyItem::Comment(
+                        check_member(scope, name, Member::Mixin)
+                            .map_err(|e| e.at(pos))?;
+                        block.direct.push(CssBodyItem::Comment(
                             format!("Unknown mixin {}({:?})", name, args)));
                     }
                 }
                 SassItem::Content => {
                     if let Some((_args, m_body)) = scope.get_mixin("%%BODY%%") {
-                        self.handle_body(direct,
-                                         sub,
+                        self.handle_body(block,
+                                         extends,
                                          scope,
                                          selectors,
                                          &m_body,
-                                         file_context,
-                                         indent)?;
+                                         file_context)?;
                     } else {
-                        direct.push(CssBodyItem::Comment(
+                        block.direct.push(CssBodyItem::Comment(
                             "Mixin @content not found.".to_string()));
                     }
                 }
@@ -374,32 +653,32 @@ impl OutputStyle {
                     panic!("Return not allowed in plain context");
                 }
 
-                SassItem::IfStatement(ref cond, ref do_if, ref do_else) => {
-                    let cond = cond.evaluate(scope).is_true();
+                SassItem::IfStatement(ref cond,
+                                      ref do_if,
+                                      ref do_else,
+                                      ref pos) => {
+                    let cond = cond.evaluate(scope).map_err(|e| e.at(pos))?;
+                    let cond = cond.is_true();
                     let items = if cond { do_if } else { do_else };
-                    self.handle_body(direct,
-                                     sub,
+                    self.handle_body(block,
+                                     extends,
                                      &mut ScopeImpl::sub(scope),
                                      selectors,
                                      items,
-                                     file_context,
-                                     0)?;
+                                     file_context)?;
                 }
-                SassItem::Each(ref name, ref values, ref body) => {
-                    let values = match values.evaluate(scope) {
-                        Value::List(v, _) => v,
-                        v => vec![v],
-                    };
-                    for value in values {
+                SassItem::Each(ref names, ref values, ref body, ref pos) => {
+                    let values =
+                        values.evaluate(scope).map_err(|e| e.at(pos))?;
+                    for value in values.iter_items() {
                         let mut scope = ScopeImpl::sub(scope);
-                        scope.define(name, &value);
-                        self.handle_body(direct,
-                                         sub,
+                        scope.define_multi(names, &value)?;
+                        self.handle_body(block,
+                                         extends,
                                          &mut scope,
                                          selectors,
                                          body,
-                                         file_context,
-                                         0)?;
+                                         file_context)?;
                     }
                 }
                 SassItem::For {
@@ -409,81 +688,102 @@ impl OutputStyle {
                     inclusive,
                     ref body,
                 } => {
-                    let from = from.evaluate(scope).integer_value()?;
-                    let to = to.evaluate(scope).integer_value()?;
+                    let from = from.evaluate(scope)?.integer_value()?;
+                    let to = to.evaluate(scope)?.integer_value()?;
                     let to = if inclusive { to + 1 } else { to };
                     for value in from..to {
                         let mut scope = ScopeImpl::sub(scope);
-                        scope.define(name, &Value::scalar(value));
-                        self.handle_body(direct,
-                                         sub,
+                        scope.define(name, &Value::scalar(value))?;
+                        self.handle_body(block,
+                                         extends,
                                          &mut scope,
                                          selectors,
                                          body,
-                                         file_context,
-                                         0)?;
+                                         file_context)?;
                     }
                 }
                 SassItem::While(ref cond, ref body) => {
                     let mut scope = ScopeImpl::sub(scope);
-                    while cond.evaluate(&scope).is_true() {
-                        self.handle_body(direct,
-                                         sub,
+                    while cond.evaluate(&scope)?.is_true() {
+                        self.handle_body(block,
+                                         extends,
                                          &mut scope,
                                          selectors,
                                          body,
-                                         file_context,
-                                         0)?;
+                                         file_context)?;
                     }
                 }
 
-                SassItem::Rule(ref s, ref b) => {
-                    self.write_rule(s,
-                                    b,
-                                    sub,
-                                    scope,
-                                    Some(selectors),
-                                    file_context,
-                                    indent)?;
+                SassItem::Rule(ref s, ref b, ref pos) => {
+                    let rule = self.handle_rule(s,
+                                                b,
+                                                pos,
+                                                scope,
+                                                Some(selectors),
+                                                file_context,
+                                                extends)?;
+                    block.sub.push(rule);
+                }
+                SassItem::Extend(ref target, optional, ref pos) => {
+                    extends.push(Extend {
+                                     extender: selectors.clone(),
+                                     target: target.clone(),
+                                     optional: optional,
+                                     media: None,
+                                     pos: pos.clone(),
+                                 });
                 }
                 SassItem::NamespaceRule(ref name, ref value, ref body) => {
-                    let value = value.evaluate(scope);
+                    let value = value.evaluate(scope)?;
                     if !value.is_null() {
-                        direct.push(CssBodyItem::Property(name.clone(),
-                                                          value,
-                                                          false));
+                        block.direct.push(CssBodyItem::Property(name.clone(),
+                                                                value,
+                                                                false,
+                                                                SourcePos::unknown()));
                     }
-                    let mut t = Vec::new();
+                    let mut t = CssBlock::default();
                     self.handle_body(&mut t,
-                                     sub,
+                                     extends,
                                      scope,
                                      selectors,
                                      body,
-                                     file_context,
-                                     indent)?;
-                    for item in t {
-                        direct.push(match item {
-                                        CssBodyItem::Property(n, v, i) => {
-                                            CssBodyItem::Property(
-                                                format!("{}-{}", name, n),
-                                                v,
-                                                i)
-                                        }
-                                        c => c,
-                                    })
+                                     file_context)?;
+                    for item in t.direct {
+                        block.direct.push(match item {
+                            CssBodyItem::Property(n, v, i, pos) => {
+                                CssBodyItem::Property(format!("{}-{}",
+                                                              name,
+                                                              n),
+                                                      v,
+                                                      i,
+                                                      pos)
+                            }
+                            c => c,
+                        })
                     }
+                    block.sub.extend(t.sub);
                 }
-                SassItem::Property(ref name, ref value, ref important) => {
-                    let v = value.evaluate(scope);
+                SassItem::Property(ref name,
+                                   ref value,
+                                   ref important,
+                                   ref pos) => {
+                    let v = value.evaluate(scope).map_err(|e| e.at(pos))?;
+                    if let Some(bad) = v.invalid_css() {
+                        return Err(Error::eval(&format!("{} isn't a valid \
+                                                         CSS value.",
+                                                        bad),
+                                               pos));
+                    }
                     if !v.is_null() {
-                        direct.push(CssBodyItem::Property(name.clone(),
-                                                          v,
-                                                          *important));
+                        block.direct.push(CssBodyItem::Property(name.clone(),
+                                                                v,
+                                                                *important,
+                                                                pos.clone()));
                     }
                 }
                 SassItem::Comment(ref c) => {
                     if !self.is_compressed() {
-                        direct.push(CssBodyItem::Comment(c.clone()));
+                        block.direct.push(CssBodyItem::Comment(c.clone()));
                     }
                 }
                 SassItem::None => (),
@@ -492,31 +792,190 @@ impl OutputStyle {
         Ok(())
     }
 
+    /// Handle the body of an at-rule inside a rule.
+    ///
+    /// The at-rule bubbles out of the rule, so the properties
+    /// directly in the at-rule are put in a rule with the enclosing
+    /// selectors.
+    fn handle_at_rule_body(&self,
+                           extends: &mut Vec<Extend>,
+                           scope: &mut dyn Scope,
+                           selectors: &Selectors,
+                           body: &[SassItem],
+                           file_context: &FileContext)
+                           -> Result<CssBlock, Error> {
+        let mut inner = CssBlock::default();
+        self.handle_body(&mut inner,
+                         extends,
+                         &mut ScopeImpl::sub(scope),
+                         selectors,
+                         body,
+                         file_context)?;
+        if *selectors == Selectors::root() {
+            Ok(inner)
+        } else {
+            Ok(CssBlock {
+                   direct: vec![],
+                   sub: vec![CssItem::Rule(selectors.clone(),
+                                           inner,
+                                           SourcePos::unknown())],
+               })
+        }
+    }
+
+    fn write_item(&self,
+                  out: &mut CssBuf,
+                  item: &CssItem,
+                  depth: usize)
+                  -> Result<(), Error> {
+        match *item {
+            CssItem::Rule(ref selectors, ref block, ref pos) => {
+                let sub_depth = if !block.direct.is_empty() {
+                    self.do_indent_no_lf(out, 2 * depth)?;
+                    out.mark(pos);
+                    if self.is_compressed() {
+                        write!(out, "{:#}{{", selectors)?;
+                    } else {
+                        write!(out, "{} {{", selectors)?;
+                    }
+                    self.write_items(out, &block.direct, 2 * depth + 2)?;
+                    write!(out, "}}")?;
+                    self.do_indent(out, 0)?;
+                    // The nested style indents rules as in the source.
+                    if self.style == OutputStyle::Nested {
+                        depth + 1
+                    } else {
+                        depth
+                    }
+                } else {
+                    depth
+                };
+                self.write_sub_items(out, &block.sub, sub_depth)?;
+            }
+            CssItem::AtRule(ref name, ref args, ref body) => {
+                self.do_indent_no_lf(out, 2 * depth)?;
+                write!(out,
+                       "@{} {}",
+                       name,
+                       args.format(Format::with_precision(self.precision)))?;
+                if let Some(ref body) = *body {
+                    if self.is_compressed() || args.is_null() {
+                        write!(out, "{{")?;
+                    } else {
+                        write!(out, " {{")?;
+                    }
+                    let mut sub = CssBuf::new();
+                    self.write_sub_items(&mut sub, &body.sub, depth + 1)?;
+                    self.write_at_rule_body(out, sub, &body.direct, depth)?;
+                } else {
+                    write!(out, ";")?;
+                }
+            }
+            CssItem::Media(ref queries, ref body) => {
+                // A media rule that ends up empty is not written.
+                let mut sub = CssBuf::new();
+                self.write_sub_items(&mut sub, &body.sub, depth + 1)?;
+                if !(sub.is_empty() && body.direct.is_empty()) {
+                    self.do_indent_no_lf(out, 2 * depth)?;
+                    if self.is_compressed() {
+                        write!(out, "@media {}{{", queries)?;
+                    } else {
+                        write!(out, "@media {} {{", queries)?;
+                    }
+                    self.write_at_rule_body(out, sub, &body.direct, depth)?;
+                }
+            }
+            CssItem::Comment(ref c) => {
+                self.do_indent_no_lf(out, 2 * depth)?;
+                write!(out, "/*{}*/", c)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the body of an at-rule, after the opening brace.
+    ///
+    /// The `sub` items are already written, as they decide if the
+    /// at-rule is written at all.
+    fn write_at_rule_body(&self,
+                          out: &mut CssBuf,
+                          mut sub: CssBuf,
+                          direct: &[CssBodyItem],
+                          depth: usize)
+                          -> Result<(), Error> {
+        if !sub.is_empty() {
+            if self.style == OutputStyle::Compact {
+                sub.trim_start();
+                write!(out, " ")?;
+            } else {
+                self.do_indent(out, 0)?;
+            }
+            out.append(sub);
+        }
+        self.write_items(out, direct, 2 * depth + 2)?;
+        if self.closes_on_last_line() {
+            out.trim_end();
+            write!(out, " ")?;
+        } else if direct.is_empty() {
+            self.do_indent_no_lf(out, 2 * depth)?;
+        }
+        write!(out, "}}")?;
+        Ok(())
+    }
+
+    /// Write items nested in a rule or at-rule.
+    ///
+    /// Each item ends up on a line of its own.
+    fn write_sub_items(&self,
+                       out: &mut CssBuf,
+                       items: &[CssItem],
+                       depth: usize)
+                       -> Result<(), Error> {
+        for item in items {
+            self.write_item(out, item, depth)?;
+            if let CssItem::Rule(..) = *item {
+            } else {
+                self.do_indent(out, 0)?;
+            }
+        }
+        Ok(())
+    }
+
     fn write_items(&self,
-                   out: &mut Write,
+                   out: &mut CssBuf,
                    items: &[CssBodyItem],
                    indent: usize)
                    -> Result<(), Error> {
         if !items.is_empty() {
-            let mut buf = Vec::new();
+            let mut buf = CssBuf::new();
             for item in items {
-                self.do_indent(&mut buf, indent)?;
-                if self.is_compressed() {
-                    write!(buf, "{:#}", item)?;
+                if self.style == OutputStyle::Compact {
+                    write!(buf, " ")?;
                 } else {
-                    write!(buf, "{}", item)?;
+                    self.do_indent(&mut buf, indent)?;
+                }
+                if let CssBodyItem::Property(_, _, _, ref pos) = *item {
+                    buf.mark(pos);
                 }
+                write!(buf, "{}", item.format(*self))?;
             }
-            if self.is_compressed() && buf.last() == Some(&b';') {
-                buf.pop();
+            if self.is_compressed() && buf.data.last() == Some(&b';') {
+                buf.data.pop();
+            }
+            out.append(buf);
+            if self.closes_on_last_line() {
+                write!(out, " ")?;
+            } else {
+                self.do_indent(out, indent - 2)?;
             }
-            out.write_all(&buf)?;
-            self.do_indent(out, indent - 2)?;
         }
         Ok(())
     }
 
-    fn do_indent(&self, out: &mut Write, steps: usize) -> Result<(), Error> {
+    fn do_indent(&self,
+                 out: &mut dyn Write,
+                 steps: usize)
+                 -> Result<(), Error> {
         if !self.is_compressed() {
             write!(out, "\n")?;
             for _i in 0..steps {
@@ -526,7 +985,7 @@ impl OutputStyle {
         Ok(())
     }
     fn do_indent_no_lf(&self,
-                       out: &mut Write,
+                       out: &mut dyn Write,
                        steps: usize)
                        -> Result<(), Error> {
         if !self.is_compressed() {
@@ -537,91 +996,418 @@ impl OutputStyle {
         Ok(())
     }
 
-    fn is_compressed(&self) -> bool {
-        self == &OutputStyle::Compressed
+    /// True if this format is the compressed style.
+    pub fn is_compressed(&self) -> bool {
+        self.style == OutputStyle::Compressed
+    }
+    /// True for the styles that puts the closing brace of a block at
+    /// the end of its last line.
+    fn closes_on_last_line(&self) -> bool {
+        self.style == OutputStyle::Nested ||
+        self.style == OutputStyle::Compact
     }
 }
 
 struct CssWriter {
     imports: Vec<u8>,
-    contents: Vec<u8>,
-    style: OutputStyle,
-    separate: bool,
+    items: Vec<CssItem>,
+    extends: Vec<Extend>,
+    modules: BTreeMap<PathBuf, Arc<Module>>,
+    format: Format,
 }
 
 impl CssWriter {
-    fn new(style: OutputStyle) -> Self {
+    fn new(format: Format) -> Self {
         CssWriter {
             imports: Vec::new(),
-            contents: Vec::new(),
-            style: style,
-            separate: false,
+            items: Vec::new(),
+            extends: Vec::new(),
+            modules: BTreeMap::new(),
+            format: format,
         }
     }
-    fn get_result(self) -> Result<Vec<u8>, Error> {
-        let mut result = vec![];
+    fn get_result(self) -> Result<(Vec<u8>, SourceMap), Error> {
         let compressed = self.is_compressed();
-        if !self.imports.is_ascii() || !self.contents.is_ascii() {
+        let mut used = vec![false; self.extends.len()];
+        let mut contents = CssBuf::new();
+        for item in self.items {
+            let item = item.extended(&self.extends, None, &mut used)?;
+            let mut buf = CssBuf::new();
+            self.format.write_item(&mut buf, &item, 0)?;
+            if !buf.is_empty() {
+                if !contents.is_empty() {
+                    self.format.do_indent(&mut contents, 0)?;
+                }
+                contents.append(buf);
+            }
+        }
+        for (extend, used) in self.extends.iter().zip(used) {
+            if !(used || extend.optional) {
+                return Err(Error::eval(&format!("The target selector was \
+                                                 not found.\nUse \"@extend \
+                                                 {} !optional\" to avoid \
+                                                 this error.",
+                                                extend.target),
+                                       &extend.pos));
+            }
+        }
+
+        let mut result = CssBuf::new();
+        if !self.imports.is_ascii() || !contents.data.is_ascii() {
             if compressed {
                 // U+FEFF is byte order mark, used to show encoding.
-                result.extend_from_slice("\u{feff}".as_bytes());
+                result.write_all("\u{feff}".as_bytes())?;
             } else {
-                result.extend_from_slice(b"@charset \"UTF-8\";\n");
+                result.write_all(b"@charset \"UTF-8\";\n")?;
             }
         }
-        result.extend(self.imports);
-        result.extend(self.contents);
-        if compressed && result.last() == Some(&b';') {
-            result.pop();
+        result.write_all(&self.imports)?;
+        result.append(contents);
+        if compressed && result.data.last() == Some(&b';') {
+            result.data.pop();
         }
-        if result.last().unwrap_or(&b'\n') != &b'\n' {
+        if result.data.last().unwrap_or(&b'\n') != &b'\n' {
             write!(&mut result, "\n")?;
         }
-        Ok(result)
+        let map = result.sourcemap();
+        Ok((result.data, map))
     }
 
-    fn to_imports(&mut self) -> &mut Write {
+    fn to_imports(&mut self) -> &mut dyn Write {
         &mut self.imports
     }
-    fn to_content(&mut self) -> &mut Write {
-        &mut self.contents
+    fn is_compressed(&self) -> bool {
+        self.format.is_compressed()
+    }
+}
+
+/// An `@extend` directive, found in the body of a rule.
+///
+/// An extend inside a `@media` rule only applies to rules in the
+/// same media `queries`.
+struct Extend {
+    extender: Selectors,
+    target: Selectors,
+    optional: bool,
+    media: Option<MediaQueries>,
+    pos: SourcePos,
+}
+
+/// Mark `extends` found in the body of a `@media` rule as limited to
+/// `queries`, merged with any queries of nested `@media` rules.
+fn in_media(extends: &mut [Extend], queries: &MediaQueries) {
+    for extend in extends {
+        extend.media = match extend.media.take() {
+            Some(inner) => Some(queries.merge(&inner).unwrap_or(inner)),
+            None => Some(queries.clone()),
+        };
     }
-    fn do_separate(&mut self) -> Result<(), Error> {
-        if self.separate {
-            self.do_indent(0)?;
-        } else {
-            self.separate = true;
+}
+
+/// Css output, with the source positions of rules and properties.
+struct CssBuf {
+    data: Vec<u8>,
+    /// Byte offsets in `data` and the source positions written there.
+    marks: Vec<(usize, SourcePos)>,
+}
+
+impl CssBuf {
+    fn new() -> Self {
+        CssBuf { data: Vec::new(), marks: Vec::new() }
+    }
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    /// Mark that output written next comes from `pos`.
+    fn mark(&mut self, pos: &SourcePos) {
+        if pos.is_known() {
+            self.marks.push((self.data.len(), pos.clone()));
         }
-        Ok(())
     }
-    fn do_indent(&mut self, steps: usize) -> Result<(), Error> {
-        if !self.is_compressed() {
-            if !self.contents.is_empty() {
-                write!(self.contents, "\n")?;
+    /// Remove whitespace from the start of this buffer.
+    fn trim_start(&mut self) {
+        let n = self.data
+            .iter()
+            .position(|c| !b" \n".contains(c))
+            .unwrap_or(self.data.len());
+        self.data.drain(..n);
+        for mark in &mut self.marks {
+            mark.0 = mark.0.saturating_sub(n);
+        }
+    }
+    /// Remove whitespace from the end of this buffer.
+    fn trim_end(&mut self) {
+        while self.data.last().map(|c| b" \n".contains(c)).unwrap_or(false) {
+            self.data.pop();
+        }
+        let len = self.data.len();
+        for mark in &mut self.marks {
+            mark.0 = cmp::min(mark.0, len);
+        }
+    }
+    /// Add the content of `other` at the end of this buffer.
+    fn append(&mut self, other: CssBuf) {
+        let offset = self.data.len();
+        self.data.extend(other.data);
+        self.marks
+            .extend(other.marks.into_iter().map(|(o, pos)| (o + offset, pos)));
+    }
+    fn sourcemap(&self) -> SourceMap {
+        let mut map = SourceMap::new();
+        let mut line = 0;
+        let mut line_start = 0;
+        let mut marks = self.marks.iter().peekable();
+        for (i, c) in self.data.iter().enumerate() {
+            while let Some(&&(offset, ref pos)) = marks.peek() {
+                if offset > i {
+                    break;
+                }
+                let column = String::from_utf8_lossy(&self.data[line_start..
+                                                                  offset])
+                    .chars()
+                    .count();
+                map.add(line, column, pos);
+                marks.next();
             }
-            for _i in 0..steps {
-                write!(self.contents, " ")?;
+            if *c == b'\n' {
+                line += 1;
+                line_start = i + 1;
             }
         }
+        map
+    }
+}
+
+impl io::Write for CssBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
         Ok(())
     }
-    fn is_compressed(&self) -> bool {
-        self.style == OutputStyle::Compressed
+}
+
+/// Evaluated css, to be written when all `@extend`s are known.
+enum CssItem {
+    /// A rule, with the source position of its selectors.
+    Rule(Selectors, CssBlock, SourcePos),
+    AtRule(String, Value, Option<CssBlock>),
+    Media(MediaQueries, CssBlock),
+    Comment(String),
+}
+
+/// The body of a rule or at-rule.
+///
+/// The `direct` items are the properties of the rule itself, `sub`
+/// are the rules and at-rules nested in it.
+#[derive(Default)]
+struct CssBlock {
+    direct: Vec<CssBodyItem>,
+    sub: Vec<CssItem>,
+}
+
+impl CssItem {
+    /// Apply `extends` to the selectors of this item, and remove any
+    /// selectors that are only placeholders.
+    ///
+    /// `media` is the queries of the `@media` rule this item is in,
+    /// if any.
+    /// `used` keeps track of which of the extends has matched any
+    /// selector.
+    fn extended(self,
+                extends: &[Extend],
+                media: Option<&MediaQueries>,
+                used: &mut [bool])
+                -> Result<CssItem, Error> {
+        match self {
+            CssItem::Rule(selectors, block, pos) => {
+                let selectors =
+                    extend_selectors(&selectors, extends, media, used)?
+                        .without_placeholders();
+                let direct = if selectors.0.is_empty() {
+                    vec![]
+                } else {
+                    block.direct
+                };
+                Ok(CssItem::Rule(selectors,
+                                 CssBlock {
+                                     direct: direct,
+                                     sub: extended_items(block.sub,
+                                                         extends,
+                                                         media,
+                                                         used)?,
+                                 },
+                                 pos))
+            }
+            CssItem::AtRule(name, args, Some(block)) => {
+                Ok(CssItem::AtRule(name,
+                                   args,
+                                   Some(CssBlock {
+                                            direct: block.direct,
+                                            sub: extended_items(block.sub,
+                                                                extends,
+                                                                media,
+                                                                used)?,
+                                        })))
+            }
+            CssItem::Media(queries, block) => {
+                let sub = extended_items(block.sub,
+                                         extends,
+                                         Some(&queries),
+                                         used)?;
+                Ok(CssItem::Media(queries,
+                                  CssBlock {
+                                      direct: block.direct,
+                                      sub: sub,
+                                  }))
+            }
+            item => Ok(item),
+        }
     }
 }
 
+impl CssBlock {
+    /// Remove all `@media` rules from this block, including those
+    /// nested in rules, and add them to `media`.
+    fn take_media(&mut self, media: &mut Vec<(MediaQueries, CssBlock)>) {
+        for item in mem::take(&mut self.sub) {
+            match item {
+                CssItem::Media(queries, block) => media.push((queries, block)),
+                CssItem::Rule(selectors, mut block, pos) => {
+                    block.take_media(media);
+                    self.sub.push(CssItem::Rule(selectors, block, pos));
+                }
+                item => self.sub.push(item),
+            }
+        }
+    }
+}
+
+/// Create the css items for a `@media` rule.
+///
+/// Any `@media` nested in the `block` bubbles out, to follow this
+/// rule, with its queries merged with `queries`.
+/// Nested rules with queries that can never match together with
+/// `queries` are dropped.  A nested `@media` with queries that cannot
+/// be merged into a single query is kept nested in this rule.
+fn media_items(queries: MediaQueries, mut block: CssBlock) -> Vec<CssItem> {
+    let mut nested = vec![];
+    block.take_media(&mut nested);
+    let mut bubbled = vec![];
+    for (inner, inner_block) in nested {
+        match queries.merge(&inner) {
+            Some(merged) => {
+                if !merged.0.is_empty() {
+                    bubbled.extend(media_items(merged, inner_block));
+                }
+            }
+            None => block.sub.push(CssItem::Media(inner, inner_block)),
+        }
+    }
+    let mut result = vec![CssItem::Media(queries, block)];
+    result.extend(bubbled);
+    result
+}
+
+fn extended_items(items: Vec<CssItem>,
+                  extends: &[Extend],
+                  media: Option<&MediaQueries>,
+                  used: &mut [bool])
+                  -> Result<Vec<CssItem>, Error> {
+    items.into_iter().map(|i| i.extended(extends, media, used)).collect()
+}
+
+/// Get `selectors` with all matching `extends` applied.
+///
+/// Each extended selector is placed directly after the selector it
+/// was extended from.
+/// Selectors created by an extend may be extended further, but never
+/// by the same extend again.
+/// It is an error for an extend inside a `@media` rule to match a
+/// selector outside of it.
+fn extend_selectors(selectors: &Selectors,
+                    extends: &[Extend],
+                    media: Option<&MediaQueries>,
+                    used: &mut [bool])
+                    -> Result<Selectors, Error> {
+    if extends.is_empty() {
+        return Ok(selectors.clone());
+    }
+    let mut result: Vec<Selector> = vec![];
+    for selector in &selectors.0 {
+        let mut found: Vec<(Selector, Vec<usize>)> =
+            vec![(selector.clone(), vec![])];
+        let mut i = 0;
+        while i < found.len() {
+            let (selector, history) = found[i].clone();
+            for (n, extend) in extends.iter().enumerate() {
+                if history.contains(&n) {
+                    continue;
+                }
+                for target in &extend.target.0 {
+                    let target = target.compound()
+                        .ok_or_else(|| {
+                            Error::eval(&format!("Can't extend {}: can't \
+                                                  extend nested selectors",
+                                                 target),
+                                        &extend.pos)
+                        })?;
+                    if !selector.contains(&target) {
+                        continue;
+                    }
+                    if extend.media.is_some() &&
+                       extend.media.as_ref() != media {
+                        return Err(Error::eval("You may not @extend \
+                                                selectors across media \
+                                                queries.",
+                                               &extend.pos));
+                    }
+                    used[n] = true;
+                    for extender in &extend.extender.0 {
+                        for s in selector.extend(&target, extender) {
+                            if !found.iter().any(|&(ref f, _)| f == &s) {
+                                let mut history = history.clone();
+                                history.push(n);
+                                found.push((s, history));
+                            }
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+        for (s, _) in found {
+            if !result.contains(&s) {
+                result.push(s);
+            }
+        }
+    }
+    Ok(Selectors(result))
+}
+
 enum CssBodyItem {
-    Property(String, Value, bool),
+    /// A property, with flag for `!important` and its source position.
+    Property(String, Value, bool, SourcePos),
     Comment(String),
 }
 
-impl fmt::Display for CssBodyItem {
+impl CssBodyItem {
+    fn format<'a>(&'a self, format: Format) -> Formatted<'a, CssBodyItem> {
+        Formatted {
+            value: self,
+            format: format,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Formatted<'a, CssBodyItem> {
     fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            CssBodyItem::Property(ref name, ref val, ref imp) => {
-                if out.alternate() {
+        match *self.value {
+            CssBodyItem::Property(ref name, ref val, ref imp, _) => {
+                let val = val.format(self.format);
+                if self.format.is_compressed() {
                     let important = if *imp { "!important" } else { "" };
-                    write!(out, "{}:{:#}{};", name, val, important)
+                    write!(out, "{}:{}{};", name, val, important)
                 } else {
                     let important = if *imp { " !important" } else { "" };
                     write!(out, "{}: {}{};", name, val, important)
@@ -24,6 +24,18 @@ impl Selectors {
             self.clone()
         }
     }
+
+    /// Remove any selector containing a placeholder.
+    ///
+    /// Placeholder selectors are only useful as targets for `@extend`
+    /// and should never be written to css.
+    pub fn without_placeholders(&self) -> Self {
+        Selectors(self.0
+                      .iter()
+                      .filter(|s| !s.has_placeholder())
+                      .cloned()
+                      .collect())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -52,6 +64,232 @@ impl Selector {
             Selector(result)
         }
     }
+
+    pub fn has_placeholder(&self) -> bool {
+        self.0.iter().any(|p| match *p {
+            SelectorPart::Placeholder(_) => true,
+            _ => false,
+        })
+    }
+
+    /// Get the simple selectors of this selector, if it is a single
+    /// compound selector (i.e. contains no combinators).
+    ///
+    /// Only a compound selector can be the target of an `@extend`.
+    pub fn compound(&self) -> Option<Vec<SelectorPart>> {
+        match self.compounds().as_slice() {
+            [(None, ref parts)] => Some(parts.clone()),
+            _ => None,
+        }
+    }
+
+    /// True if any compound selector in this selector contains all
+    /// the parts of `target`.
+    pub fn contains(&self, target: &[SelectorPart]) -> bool {
+        self.compounds()
+            .iter()
+            .any(|&(_, ref parts)| target.iter().all(|t| parts.contains(t)))
+    }
+
+    /// Get the selectors resulting from replacing the `target`
+    /// compound selector by `extender` in this selector.
+    ///
+    /// The result is empty if the target is not part of this selector
+    /// or if the extender cannot be unified with this selector.
+    /// When both this selector and the extender has parent selectors,
+    /// they are woven together in the ways that can match the same
+    /// element.
+    pub fn extend(&self,
+                  target: &[SelectorPart],
+                  extender: &Selector)
+                  -> Vec<Selector> {
+        let host = self.compounds();
+        let ext = extender.compounds();
+        let (e_pre, &(ref e_comb, ref e_last)) = match ext.split_last() {
+            Some((last, pre)) => (pre, last),
+            None => return vec![],
+        };
+        let mut result: Vec<Selector> = vec![];
+        for (i, &(ref h_comb, ref parts)) in host.iter().enumerate() {
+            if !target.iter().all(|t| parts.contains(t)) {
+                continue;
+            }
+            let unified = match unify_compound(parts, target, e_last) {
+                Some(unified) => unified,
+                None => continue,
+            };
+            for (mut prefix, comb) in
+                weave(&host[..i], h_comb, e_pre, e_comb) {
+                prefix.push((comb, unified.clone()));
+                prefix.extend(host[i + 1..].iter().cloned());
+                let selector = Selector::from_compounds(&prefix);
+                if !result.contains(&selector) {
+                    result.push(selector);
+                }
+            }
+        }
+        result
+    }
+
+    /// Split this selector into compound selectors, each with the
+    /// combinator that precedes it (if any).
+    ///
+    /// Simple parts like `a.foo#bar` are split into the separate
+    /// simple selectors `a`, `.foo` and `#bar`.
+    fn compounds(&self) -> Vec<Compound> {
+        let mut result = vec![];
+        let mut comb = None;
+        let mut current = vec![];
+        for part in &self.0 {
+            match *part {
+                SelectorPart::Descendant => {
+                    if !current.is_empty() {
+                        result.push((comb, current));
+                        current = vec![];
+                        comb = Some(SelectorPart::Descendant);
+                    }
+                }
+                SelectorPart::RelOp(_) => {
+                    if !current.is_empty() {
+                        result.push((comb, current));
+                        current = vec![];
+                    }
+                    comb = Some(part.clone());
+                }
+                SelectorPart::Simple(ref s) => {
+                    current.extend(split_simple(s));
+                }
+                ref part => current.push(part.clone()),
+            }
+        }
+        if !current.is_empty() {
+            result.push((comb, current));
+        }
+        result
+    }
+
+    fn from_compounds(compounds: &[Compound]) -> Self {
+        let mut result = vec![];
+        for &(ref comb, ref parts) in compounds {
+            match *comb {
+                Some(ref comb) => result.push(comb.clone()),
+                None if !result.is_empty() => {
+                    result.push(SelectorPart::Descendant)
+                }
+                None => (),
+            }
+            result.extend(parts.iter().cloned());
+        }
+        Selector(result)
+    }
+}
+
+/// A compound selector, with the combinator that precedes it (if any).
+type Compound = (Option<SelectorPart>, Vec<SelectorPart>);
+
+/// Split a simple selector string like `a.foo#bar` into its parts.
+fn split_simple(s: &str) -> Vec<SelectorPart> {
+    let mut result = vec![];
+    let mut current = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if (c == '.' || c == '#') && !current.is_empty() {
+            result.push(SelectorPart::Simple(current));
+            current = String::new();
+        }
+        current.push(c);
+        if c == '\\' {
+            if let Some(c) = chars.next() {
+                current.push(c);
+            }
+        }
+    }
+    if !current.is_empty() {
+        result.push(SelectorPart::Simple(current));
+    }
+    result
+}
+
+/// Replace the `target` parts of `compound` with the parts of
+/// `extender`.
+///
+/// Returns None if the result cannot match any element, e.g. if it
+/// would require two different element names or ids.
+fn unify_compound(compound: &[SelectorPart],
+                  target: &[SelectorPart],
+                  extender: &[SelectorPart])
+                  -> Option<Vec<SelectorPart>> {
+    let mut result: Vec<SelectorPart> = vec![];
+    let mut inserted = false;
+    for part in compound {
+        if target.contains(part) {
+            if !inserted {
+                for e in extender {
+                    if !result.contains(e) {
+                        result.push(e.clone());
+                    }
+                }
+                inserted = true;
+            }
+        } else if !result.contains(part) {
+            result.push(part.clone());
+        }
+    }
+    let mut types = result.iter().filter(|p| p.is_type()).cloned();
+    if let Some(first) = types.next() {
+        let others = types.filter(|p| !p.is_universal()).collect::<Vec<_>>();
+        let element = if first.is_universal() && !others.is_empty() {
+            others[0].clone()
+        } else {
+            first
+        };
+        if others.iter().any(|p| p != &element) {
+            return None;
+        }
+        result.retain(|p| !p.is_type());
+        result.insert(0, element);
+    }
+    let ids = result.iter().filter(|p| p.is_id()).collect::<Vec<_>>();
+    if ids.iter().any(|id| id != &ids[0]) {
+        return None;
+    }
+    Some(result)
+}
+
+/// Combine the parent parts of a selector being extended and of the
+/// extender, returning each possible prefix with the combinator that
+/// should precede the unified compound selector.
+fn weave(host: &[Compound],
+         h_comb: &Option<SelectorPart>,
+         ext: &[Compound],
+         e_comb: &Option<SelectorPart>)
+         -> Vec<(Vec<Compound>, Option<SelectorPart>)> {
+    fn is_descendant(comb: &Option<SelectorPart>) -> bool {
+        match *comb {
+            None | Some(SelectorPart::Descendant) => true,
+            _ => false,
+        }
+    }
+    fn concat(a: &[Compound], b: &[Compound]) -> Vec<Compound> {
+        let mut result = a.to_vec();
+        result.extend(b.iter().cloned());
+        result
+    }
+    if ext.is_empty() {
+        vec![(host.to_vec(), h_comb.clone().or_else(|| e_comb.clone()))]
+    } else if host.is_empty() {
+        vec![(ext.to_vec(), e_comb.clone())]
+    } else {
+        match (is_descendant(h_comb), is_descendant(e_comb)) {
+            (true, true) => {
+                vec![(concat(host, ext), h_comb.clone()),
+                     (concat(ext, host), h_comb.clone())]
+            }
+            (true, false) => vec![(concat(host, ext), e_comb.clone())],
+            (false, true) => vec![(concat(ext, host), h_comb.clone())],
+            (false, false) => vec![],
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -61,6 +299,8 @@ pub enum SelectorPart {
     RelOp(u8), // >, +, ~
     Attribute { name: String, op: String, val: String },
     Pseudo { name: String, arg: Option<Selectors> },
+    /// A `%placeholder` selector, only usable with `@extend`.
+    Placeholder(String),
     BackRef,
 }
 
@@ -122,6 +362,8 @@ named!(selector_part<&[u8], SelectorPart>,
                          op: "".to_string(),
                          val: "".to_string(),
                      })) |
+           map!(preceded!(tag!("%"), selector_string),
+                |s| SelectorPart::Placeholder(s)) |
            value!(SelectorPart::BackRef, tag!("&")) |
            delimited!(opt_spacelike,
                       alt!(value!(SelectorPart::RelOp(b'>'), tag!(">")) |
@@ -161,9 +403,28 @@ impl SelectorPart {
             SelectorPart::Simple(_) |
             SelectorPart::Attribute { .. } |
             SelectorPart::Pseudo { .. } |
+            SelectorPart::Placeholder(_) |
             SelectorPart::BackRef => false,
         }
     }
+    /// True for an element name (or `*`).
+    fn is_type(&self) -> bool {
+        match *self {
+            SelectorPart::Simple(ref s) => {
+                !(s.starts_with('.') || s.starts_with('#'))
+            }
+            _ => false,
+        }
+    }
+    fn is_universal(&self) -> bool {
+        *self == SelectorPart::Simple("*".into())
+    }
+    fn is_id(&self) -> bool {
+        match *self {
+            SelectorPart::Simple(ref s) => s.starts_with('#'),
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for Selectors {
@@ -229,6 +490,7 @@ impl fmt::Display for SelectorPart {
                     write!(out, ":{}", name)
                 }
             }
+            SelectorPart::Placeholder(ref s) => write!(out, "%{}", s),
             SelectorPart::BackRef => write!(out, "&"),
         }
     }
@@ -287,6 +549,14 @@ mod test {
                         }])))
     }
 
+    #[test]
+    fn placeholder_selector() {
+        assert_eq!(selector(b"%foo-bar "),
+                   Done(&b""[..],
+                        Selector(vec![SelectorPart::Placeholder(
+                            "foo-bar".into())])))
+    }
+
     #[test]
     fn pseudo_selector() {
         assert_eq!(selector(b":before "),
@@ -0,0 +1,213 @@
+//! Source maps, version 3.
+//!
+//! See https://sourcemaps.info/spec.html for the format.
+use sourcepos::SourcePos;
+use std::fmt::Write;
+
+/// A source map, mapping positions in generated css back to the sass
+/// source files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    sources: Vec<String>,
+    mappings: Vec<Mapping>,
+}
+
+/// A single mapping.  All lines and columns are counted from zero.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Mapping {
+    line: usize,
+    column: usize,
+    source: usize,
+    source_line: usize,
+    source_column: usize,
+}
+
+impl SourceMap {
+    /// Create a new empty source map.
+    pub fn new() -> Self {
+        SourceMap::default()
+    }
+
+    /// Add a mapping from `line` and `column` (counted from zero) in
+    /// the generated css to `pos`.
+    ///
+    /// Unknown positions are ignored.
+    pub fn add(&mut self, line: usize, column: usize, pos: &SourcePos) {
+        if !pos.is_known() {
+            return;
+        }
+        let source = match self.sources.iter().position(|s| s == &pos.file) {
+            Some(source) => source,
+            None => {
+                self.sources.push(pos.file.clone());
+                self.sources.len() - 1
+            }
+        };
+        self.mappings.push(Mapping {
+                               line: line,
+                               column: column,
+                               source: source,
+                               source_line: pos.line - 1,
+                               source_column: pos.column - 1,
+                           });
+    }
+
+    /// The source files referenced by this map.
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Find the source file, line and column for a line and column
+    /// in the generated css, if there is a mapping for exactly that
+    /// position.
+    ///
+    /// The css position is counted from zero, the source position
+    /// from one, as in `SourcePos`.
+    pub fn lookup(&self,
+                  line: usize,
+                  column: usize)
+                  -> Option<(&str, usize, usize)> {
+        self.mappings
+            .iter()
+            .find(|m| m.line == line && m.column == column)
+            .map(|m| {
+                     (self.sources[m.source].as_ref(),
+                      m.source_line + 1,
+                      m.source_column + 1)
+                 })
+    }
+
+    /// Get the mappings in the base64 vlq encoded form used in json.
+    pub fn encoded_mappings(&self) -> String {
+        let mut mappings = self.mappings.clone();
+        mappings.sort_by_key(|m| (m.line, m.column));
+        let mut result = String::new();
+        let mut line = 0;
+        let mut column = 0;
+        let mut source = 0;
+        let mut source_line = 0;
+        let mut source_column = 0;
+        for m in mappings {
+            if m.line > line {
+                for _ in line..m.line {
+                    result.push(';');
+                }
+                line = m.line;
+                column = 0;
+            } else if !result.is_empty() && !result.ends_with(';') {
+                result.push(',');
+            }
+            vlq(&mut result, m.column as isize - column as isize);
+            vlq(&mut result, m.source as isize - source as isize);
+            vlq(&mut result, m.source_line as isize - source_line as isize);
+            vlq(&mut result,
+                m.source_column as isize - source_column as isize);
+            column = m.column;
+            source = m.source;
+            source_line = m.source_line;
+            source_column = m.source_column;
+        }
+        result
+    }
+
+    /// Get this map as json, for the generated css `file`.
+    pub fn to_json(&self, file: &str) -> String {
+        let mut out = String::new();
+        write!(out, "{{\n  \"version\": 3,\n  \"file\": ").unwrap();
+        json_string(&mut out, file);
+        write!(out, ",\n  \"sources\": [").unwrap();
+        for (i, source) in self.sources.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            json_string(&mut out, source);
+        }
+        write!(out,
+               "],\n  \"names\": [],\n  \"mappings\": \"{}\"\n}}\n",
+               self.encoded_mappings())
+            .unwrap();
+        out
+    }
+}
+
+/// Write `value` in base64 vlq encoding.
+fn vlq(out: &mut String, value: isize) {
+    static BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                                     abcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut v = if value < 0 {
+        ((-value as usize) << 1) | 1
+    } else {
+        (value as usize) << 1
+    };
+    loop {
+        let mut digit = v & 0x1f;
+        v >>= 5;
+        if v > 0 {
+            digit |= 0x20;
+        }
+        out.push(BASE64[digit] as char);
+        if v == 0 {
+            break;
+        }
+    }
+}
+
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).unwrap()
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pos(line: usize, column: usize) -> SourcePos {
+        SourcePos {
+            file: "a.scss".into(),
+            line: line,
+            column: column,
+            line_text: String::new(),
+        }
+    }
+
+    #[test]
+    fn vlq_values() {
+        let mut s = String::new();
+        for v in &[0, 1, -1, 15, 16, -17, 1000] {
+            vlq(&mut s, *v);
+            s.push(' ');
+        }
+        assert_eq!(s, "A C D e gB jB w+B ")
+    }
+
+    #[test]
+    fn mappings() {
+        let mut map = SourceMap::new();
+        map.add(0, 0, &pos(1, 1));
+        map.add(1, 2, &pos(2, 3));
+        map.add(3, 2, &pos(5, 3));
+        map.add(3, 9, &SourcePos::unknown());
+        assert_eq!(map.encoded_mappings(), "AAAA;EACE;;EAGA")
+    }
+
+    #[test]
+    fn json() {
+        let mut map = SourceMap::new();
+        map.add(0, 0, &pos(1, 1));
+        assert_eq!(map.to_json("a.css"),
+                   "{\n  \"version\": 3,\n  \"file\": \"a.css\",\n  \
+                    \"sources\": [\"a.scss\"],\n  \"names\": [],\n  \
+                    \"mappings\": \"AAAA\"\n}\n")
+    }
+}
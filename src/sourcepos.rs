@@ -0,0 +1,227 @@
+//! Positions in sass source files.
+//!
+//! The parsers work on plain byte slices, so while a file is being
+//! parsed, the file is registered in a thread-local context.  The
+//! `position` parser uses that context to find where in the file a
+//! slice starts.
+use nom::IResult;
+use std::cell::RefCell;
+use std::cmp::min;
+use std::fmt;
+
+/// A position in a source file.
+///
+/// Lines and columns are counted from one.  A line of zero means the
+/// position is unknown, e.g. for items that was not parsed from a
+/// file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourcePos {
+    /// The name of the source file.
+    pub file: String,
+    /// The line number in the file.
+    pub line: usize,
+    /// The column in the line, counted in characters.
+    pub column: usize,
+    /// The text of the line, without the line break.
+    pub line_text: String,
+}
+
+impl SourcePos {
+    /// A position that is not known.
+    pub fn unknown() -> Self {
+        SourcePos {
+            file: String::new(),
+            line: 0,
+            column: 0,
+            line_text: String::new(),
+        }
+    }
+    /// True unless this is the `unknown` position.
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        write!(out, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// The line of an original source that a line of translated source
+/// was generated from.
+///
+/// Used for indented sass, which is translated to scss before it is
+/// parsed, so positions can refer to the text the user wrote.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineOrigin {
+    /// The text of the original line.
+    pub text: String,
+    /// Where runs of copied text start, as pairs of a column in the
+    /// translated line and the corresponding column in the original
+    /// line, both counted in characters from zero.  Ordered by the
+    /// translated column.
+    pub runs: Vec<(usize, usize)>,
+}
+
+impl LineOrigin {
+    /// The column in the original line corresponding to `column` in
+    /// the translated line.
+    fn column(&self, column: usize) -> usize {
+        let column = self.runs
+            .iter()
+            .rev()
+            .find(|&&(t, _)| t <= column)
+            .map(|&(t, o)| o + column - t)
+            .unwrap_or(0);
+        min(column, self.text.chars().count())
+    }
+}
+
+/// A source file being parsed.
+struct Source {
+    name: String,
+    data: Vec<u8>,
+    /// Start address of the data actually being parsed.
+    start: usize,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    /// The original lines of a translated source, or empty.
+    origins: Vec<LineOrigin>,
+}
+
+impl Source {
+    fn new(name: &str, data: &[u8], origins: Vec<LineOrigin>) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(data.iter()
+                               .enumerate()
+                               .filter(|&(_, c)| *c == b'\n')
+                               .map(|(i, _)| i + 1));
+        Source {
+            name: name.into(),
+            data: data.to_vec(),
+            start: data.as_ptr() as usize,
+            line_starts: line_starts,
+            origins: origins,
+        }
+    }
+
+    fn pos_of(&self, input: &[u8]) -> SourcePos {
+        let addr = input.as_ptr() as usize;
+        if addr < self.start || addr > self.start + self.data.len() {
+            return SourcePos::unknown();
+        }
+        let offset = addr - self.start;
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let line_start = self.line_starts[line];
+        let line_end = self.line_starts
+            .get(line + 1)
+            .map(|next| next - 1)
+            .unwrap_or_else(|| self.data.len());
+        let column = String::from_utf8_lossy(&self.data[line_start..offset])
+            .chars()
+            .count();
+        if !self.origins.is_empty() {
+            // Lines added after the last original line only close
+            // blocks, so they are placed at the end of the input.
+            let (origin, column) = match self.origins.get(line) {
+                Some(origin) => (origin, origin.column(column)),
+                None => {
+                    let last = self.origins.last().unwrap();
+                    (last, last.text.chars().count())
+                }
+            };
+            return SourcePos {
+                file: self.name.clone(),
+                line: min(line + 1, self.origins.len()),
+                column: column + 1,
+                line_text: origin.text.clone(),
+            };
+        }
+        SourcePos {
+            file: self.name.clone(),
+            line: line + 1,
+            column: column + 1,
+            line_text: String::from_utf8_lossy(&self.data[line_start..
+                                                           line_end])
+                .trim_end_matches('\r')
+                .into(),
+        }
+    }
+}
+
+thread_local!(static SOURCE: RefCell<Option<Source>> =
+              const { RefCell::new(None) });
+
+/// Call `f` with `data`, named `name`, registered as the source
+/// being parsed.
+pub fn with_source<T, F>(name: &str, data: &[u8], f: F) -> T
+    where F: FnOnce(&[u8]) -> T
+{
+    with_translated_source(name, data, vec![], f)
+}
+
+/// Call `f` with `data`, named `name`, registered as the source
+/// being parsed, where `data` was translated from an original source
+/// line by line.
+///
+/// Positions in `data` are reported as positions in the original
+/// source, as given by `origins`.
+pub fn with_translated_source<T, F>(name: &str,
+                                    data: &[u8],
+                                    origins: Vec<LineOrigin>,
+                                    f: F)
+                                    -> T
+    where F: FnOnce(&[u8]) -> T
+{
+    let source = Source::new(name, data, origins);
+    let outer = SOURCE.with(|s| s.borrow_mut().replace(source));
+    let result = f(data);
+    SOURCE.with(|s| *s.borrow_mut() = outer);
+    result
+}
+
+/// Get the position of the start of `input` in the source currently
+/// being parsed.
+pub fn pos_of(input: &[u8]) -> SourcePos {
+    SOURCE.with(|s| match *s.borrow() {
+                    Some(ref source) => source.pos_of(input),
+                    None => SourcePos::unknown(),
+                })
+}
+
+/// A parser that consumes nothing and returns the current position.
+pub fn position(input: &[u8]) -> IResult<&[u8], SourcePos> {
+    IResult::Done(input, pos_of(input))
+}
+
+#[test]
+fn test_position() {
+    let data = b"a {\n  b: c;\n}\n";
+    let pos = with_source("test.scss", data, |data| pos_of(&data[6..]));
+    assert_eq!(pos.to_string(), "test.scss:2:3");
+    assert_eq!(pos.line_text, "  b: c;");
+}
+
+#[test]
+fn test_translated_position() {
+    let origins = vec![LineOrigin { text: "a".into(), runs: vec![(0, 0)] },
+                       LineOrigin {
+                           text: "  +m".into(),
+                           runs: vec![(0, 0), (11, 3)],
+                       }];
+    let data = b"a {\n  @include m; }\n";
+    let pos = with_translated_source("test.sass", data, origins, |data| {
+        pos_of(&data[15..])
+    });
+    assert_eq!(pos.to_string(), "test.sass:2:4");
+    assert_eq!(pos.line_text, "  +m");
+}
+
+#[test]
+fn test_position_unknown() {
+    assert_eq!(pos_of(b"a"), SourcePos::unknown());
+}
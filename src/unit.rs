@@ -1,8 +1,10 @@
-//! The Unit enum defines css units
+//! The Unit enum defines css units, and a UnitSet combines units
+//! into the unit of a number.
 
-use num_rational::Rational;
+use num_rational::{BigRational, Rational};
 use num_traits::One;
 use std::fmt;
+use value::big;
 
 /// Units in css.
 ///
@@ -41,6 +43,8 @@ pub enum Unit {
     Dpi,
     Dpcm,
     Dppx,
+    // <flex> type
+    Fr,
     // Special units
     Percent,
     None,
@@ -61,19 +65,23 @@ impl Unit {
 
             Unit::Dpi | Unit::Dpcm | Unit::Dppx => "resolution",
 
+            Unit::Fr => "flex",
+
             Unit::Percent | Unit::None => "none",
         }
     }
 
-    /// Some of these are exact and correct, others are more arbitrary.
-    /// When comparing 10cm to 4in, these factors will give correct results.
-    /// When comparing rems to vw, who can say?
+    /// The size of this unit, in the base unit of its dimension.
+    ///
+    /// The base units are `mm`, `deg`, `s`, `Hz` and `dppx`.
+    /// The factors are exact, except for `rad`, which uses a rational
+    /// approximation of pi that is as exact as pi in a `f64`.
+    /// Relative units and percentages can only be converted to
+    /// themselves, so their factor is one.
     pub fn scale_factor(&self) -> Rational {
         match *self {
-            Unit::Em | Unit::Rem => Rational::new(10, 2),
-            Unit::Ex => Rational::new(10, 3),
-            Unit::Ch => Rational::new(10, 4),
-            Unit::Vw | Unit::Vh | Unit::Vmin | Unit::Vmax => Rational::one(),
+            Unit::Em | Unit::Ex | Unit::Ch | Unit::Rem | Unit::Vw |
+            Unit::Vh | Unit::Vmin | Unit::Vmax => Rational::one(),
             Unit::Cm => Rational::new(10, 1),
             Unit::Mm => Rational::one(),
             Unit::Q => Rational::new(1, 4),
@@ -82,10 +90,11 @@ impl Unit {
             Unit::Pc => Rational::new(254, 60),
             Unit::Px => Rational::new(254, 960),
 
-            Unit::Deg => Rational::new(360, 1),
-            Unit::Grad => Rational::new(400, 1),
-            Unit::Rad => Rational::new(62832, 10000), // approximate
-            Unit::Turn => Rational::one(),
+            Unit::Deg => Rational::one(),
+            Unit::Grad => Rational::new(9, 10),
+            // 180 / pi, with pi as 245850922 / 78256779.
+            Unit::Rad => Rational::new(180 * 78_256_779, 245_850_922),
+            Unit::Turn => Rational::new(360, 1),
 
             Unit::S => Rational::one(),
             Unit::Ms => Rational::new(1, 1000),
@@ -93,12 +102,170 @@ impl Unit {
             Unit::Hz => Rational::one(),
             Unit::Khz => Rational::new(1000, 1),
 
-            Unit::Dpi => Rational::new(96, 1),
-            Unit::Dpcm => Rational::new(9600, 254),
+            Unit::Dpi => Rational::new(1, 96),
+            Unit::Dpcm => Rational::new(254, 9600),
             Unit::Dppx => Rational::one(),
 
-            Unit::Percent => Rational::new(1, 100),
-            Unit::None => Rational::one(),
+            Unit::Fr => Rational::one(),
+
+            Unit::Percent | Unit::None => Rational::one(),
+        }
+    }
+
+    /// True if numbers of this unit can be converted to `other`.
+    ///
+    /// Units of the same kind can be converted, except for relative
+    /// lengths and percentages, that only matches themselves.
+    pub fn is_compatible(&self, other: &Unit) -> bool {
+        self == other ||
+        (self.dimension() == other.dimension() && self.is_absolute() &&
+         other.is_absolute())
+    }
+
+    fn is_absolute(&self) -> bool {
+        match *self {
+            Unit::Em | Unit::Ex | Unit::Ch | Unit::Rem | Unit::Vw |
+            Unit::Vh | Unit::Vmin | Unit::Vmax | Unit::Percent |
+            Unit::None => false,
+            _ => true,
+        }
+    }
+}
+
+/// The unit of a number, as numerator and denominator units.
+///
+/// A plain css unit is a single numerator, but arithmetic can create
+/// compound units like `px*px`, `px/em` or `1/s`.
+/// Compatible units in the numerator and denominator cancel out,
+/// so a `UnitSet` is kept simplified.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnitSet {
+    num: Vec<Unit>,
+    den: Vec<Unit>,
+}
+
+impl UnitSet {
+    /// The empty unit set of a unitless number.
+    pub fn scalar() -> Self {
+        UnitSet::default()
+    }
+
+    /// True for a unitless number.
+    pub fn is_none(&self) -> bool {
+        self.num.is_empty() && self.den.is_empty()
+    }
+
+    /// True if this is exactly the `%` unit.
+    pub fn is_percent(&self) -> bool {
+        self.single() == Some(&Unit::Percent)
+    }
+
+    /// Get the unit, if this is a single (non-compound) unit.
+    pub fn single(&self) -> Option<&Unit> {
+        if self.num.len() == 1 && self.den.is_empty() {
+            self.num.first()
+        } else {
+            None
+        }
+    }
+
+    /// True if this unit can be used in css, that is, it is not a
+    /// compound unit.
+    pub fn is_css(&self) -> bool {
+        self.den.is_empty() && self.num.len() < 2
+    }
+
+    /// Multiply by `other`.
+    ///
+    /// Returns the resulting unit, and a factor to multiply the
+    /// value with for conversion of units that cancel out.
+    pub fn mul(&self, other: &UnitSet) -> (UnitSet, BigRational) {
+        let mut num = self.num.clone();
+        num.extend(other.num.iter().cloned());
+        let mut den = self.den.clone();
+        den.extend(other.den.iter().cloned());
+        simplify(num, den)
+    }
+
+    /// Divide by `other`.
+    ///
+    /// Returns the resulting unit, and a factor to multiply the
+    /// value with for conversion of units that cancel out.
+    pub fn div(&self, other: &UnitSet) -> (UnitSet, BigRational) {
+        let mut num = self.num.clone();
+        num.extend(other.den.iter().cloned());
+        let mut den = self.den.clone();
+        den.extend(other.num.iter().cloned());
+        simplify(num, den)
+    }
+
+    /// Get the factor to convert a number in this unit to `other`.
+    ///
+    /// Returns `None` if the units are not compatible.
+    pub fn scale_to(&self, other: &UnitSet) -> Option<BigRational> {
+        Some(scale_units(&self.num, &other.num)? /
+             scale_units(&self.den, &other.den)?)
+    }
+}
+
+/// Get the factor for converting numbers from the units `from` to
+/// the units `to`, if they are compatible.
+fn scale_units(from: &[Unit], to: &[Unit]) -> Option<BigRational> {
+    if from.len() != to.len() {
+        return None;
+    }
+    let mut to = to.to_vec();
+    let mut factor = BigRational::one();
+    for f in from {
+        let i = to.iter()
+            .position(|t| t == f)
+            .or_else(|| to.iter().position(|t| f.is_compatible(t)))?;
+        let t = to.remove(i);
+        factor = factor * big(&f.scale_factor()) / big(&t.scale_factor());
+    }
+    Some(factor)
+}
+
+/// Cancel out compatible units in `num` and `den`.
+fn simplify(mut num: Vec<Unit>,
+            den: Vec<Unit>)
+            -> (UnitSet, BigRational) {
+    let mut factor = BigRational::one();
+    let mut rest = vec![];
+    for d in den {
+        let i = num.iter()
+            .position(|n| n == &d)
+            .or_else(|| num.iter().position(|n| n.is_compatible(&d)));
+        if let Some(i) = i {
+            let n = num.remove(i);
+            factor = factor * big(&n.scale_factor()) /
+                     big(&d.scale_factor());
+        } else {
+            rest.push(d);
+        }
+    }
+    (UnitSet { num: num, den: rest }, factor)
+}
+
+impl From<Unit> for UnitSet {
+    fn from(unit: Unit) -> Self {
+        match unit {
+            Unit::None => UnitSet::scalar(),
+            unit => UnitSet { num: vec![unit], den: vec![] },
+        }
+    }
+}
+
+impl fmt::Display for UnitSet {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        let join = |units: &[Unit]| {
+            units.iter().map(|u| u.to_string()).collect::<Vec<_>>().join("*")
+        };
+        match (self.num.len(), self.den.len()) {
+            (_, 0) => out.write_str(&join(&self.num)),
+            (0, 1) => write!(out, "{}^-1", self.den[0]),
+            (0, _) => write!(out, "({})^-1", join(&self.den)),
+            _ => write!(out, "{}/{}", join(&self.num), join(&self.den)),
         }
     }
 }
@@ -137,6 +304,8 @@ impl fmt::Display for Unit {
             Unit::Dpi => write!(out, "dpi"),
             Unit::Dpcm => write!(out, "dpcm"),
             Unit::Dppx => write!(out, "dppx"),
+            // <flex> type
+            Unit::Fr => write!(out, "fr"),
             // Special units
             Unit::Percent => write!(out, "%"),
             Unit::None => Ok(()),
@@ -157,7 +326,7 @@ named!(pub unit<&[u8], Unit>,
            value!(Unit::Vmax, tag!("vmax")) |
            value!(Unit::Cm, tag!("cm")) |
            value!(Unit::Mm, tag!("mm")) |
-           value!(Unit::Q, tag!("q")) |
+           value!(Unit::Q, alt!(tag!("q") | tag!("Q"))) |
            value!(Unit::In, tag!("in")) |
            value!(Unit::Pt, tag!("pt")) |
            value!(Unit::Pc, tag!("pc")) |
@@ -177,6 +346,80 @@ named!(pub unit<&[u8], Unit>,
            value!(Unit::Dpi, tag!("dpi")) |
            value!(Unit::Dpcm, tag!("dpcm")) |
            value!(Unit::Dppx, tag!("dppx")) |
+           // <flex> type
+           value!(Unit::Fr, tag!("fr")) |
            // Special units
            value!(Unit::Percent, tag!("%")) |
            value!(Unit::None, tag!(""))));
+
+#[cfg(test)]
+mod test {
+    use super::{Unit, UnitSet};
+    use num_rational::BigRational;
+
+    #[test]
+    fn mul_compound() {
+        let (unit, factor) = px().mul(&px());
+        assert_eq!((unit.to_string(), factor), ("px*px".into(), one()))
+    }
+
+    #[test]
+    fn div_compound() {
+        let (unit, factor) = px().div(&UnitSet::from(Unit::Em));
+        assert_eq!((unit.to_string(), factor), ("px/em".into(), one()))
+    }
+
+    #[test]
+    fn div_cancel() {
+        let (unit, factor) = px().div(&px());
+        assert_eq!((unit, factor), (UnitSet::scalar(), one()))
+    }
+
+    #[test]
+    fn div_convert() {
+        let (unit, factor) = UnitSet::from(Unit::Cm)
+            .div(&UnitSet::from(Unit::Mm));
+        assert_eq!((unit, factor), (UnitSet::scalar(), int(10)))
+    }
+
+    #[test]
+    fn inverse() {
+        let (unit, _) = UnitSet::scalar().div(&UnitSet::from(Unit::S));
+        assert_eq!(unit.to_string(), "s^-1")
+    }
+
+    #[test]
+    fn relative_units_does_not_convert() {
+        assert_eq!(px().scale_to(&UnitSet::from(Unit::Em)), None)
+    }
+
+    #[test]
+    fn convert_inches() {
+        assert_eq!(UnitSet::from(Unit::In).scale_to(&px()),
+                   Some(int(96)))
+    }
+
+    #[test]
+    fn convert_angles() {
+        assert_eq!(UnitSet::from(Unit::Turn).scale_to(&Unit::Grad.into()),
+                   Some(int(400)))
+    }
+
+    #[test]
+    fn convert_resolution() {
+        assert_eq!(UnitSet::from(Unit::Dppx).scale_to(&Unit::Dpi.into()),
+                   Some(int(96)))
+    }
+
+    fn px() -> UnitSet {
+        UnitSet::from(Unit::Px)
+    }
+
+    fn one() -> BigRational {
+        int(1)
+    }
+
+    fn int(v: isize) -> BigRational {
+        BigRational::from_integer(v.into())
+    }
+}
@@ -0,0 +1,300 @@
+//! Css calculations: `calc()`, `min()`, `max()` and `clamp()`.
+//!
+//! A calculation is simplified as far as possible when evaluated.
+//! If it can be computed to a single number, that number is the
+//! value of the calculation, otherwise the simplified calculation is
+//! kept and written as css.
+use error::Error;
+use nom::multispace;
+use num_traits::Zero;
+use output_style::Format;
+use parseutil::opt_spacelike;
+use std::cmp::Ordering;
+use std::fmt;
+use value::{Value, limit, single_value};
+use value::operator::Operator;
+use variablescope::Scope;
+
+/// The kind of a calculation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalcName {
+    Calc,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl CalcName {
+    /// Evaluate a calculation of this kind, as far as possible.
+    pub fn evaluate(&self,
+                    args: &[Value],
+                    scope: &dyn Scope)
+                    -> Result<Value, Error> {
+        let args = args.iter()
+            .map(|a| simplify(a, scope))
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = match *self {
+            CalcName::Calc => Some(args[0].clone()),
+            CalcName::Min => find_extreme(&args, Ordering::Less)?,
+            CalcName::Max => find_extreme(&args, Ordering::Greater)?,
+            CalcName::Clamp => {
+                match find_extreme(&args[..2], Ordering::Greater)? {
+                    Some(v) => {
+                        find_extreme(&[v, args[2].clone()], Ordering::Less)?
+                    }
+                    None => None,
+                }
+            }
+        };
+        Ok(match result {
+            Some(Value::Numeric(v, u, ..)) => {
+                Value::Numeric(v, u, false, true)
+            }
+            _ => {
+                let args = args.into_iter()
+                    .map(|a| match a {
+                             Value::Paren(v) => *v,
+                             a => a,
+                         })
+                    .collect();
+                Value::Calc(*self, args)
+            }
+        })
+    }
+
+    fn arity_ok(&self, n: usize) -> bool {
+        match *self {
+            CalcName::Calc => n == 1,
+            CalcName::Min | CalcName::Max => n > 0,
+            CalcName::Clamp => n == 3,
+        }
+    }
+}
+
+impl fmt::Display for CalcName {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        out.write_str(match *self {
+                          CalcName::Calc => "calc",
+                          CalcName::Min => "min",
+                          CalcName::Max => "max",
+                          CalcName::Clamp => "clamp",
+                      })
+    }
+}
+
+/// Write an argument of a calculation.
+///
+/// Operators in calculations are always surrounded by spaces.
+pub fn write_arg(arg: &Value,
+                 out: &mut fmt::Formatter,
+                 format: Format)
+                 -> fmt::Result {
+    match *arg {
+        Value::BinOp(ref a, ref op, ref b) => {
+            write_arg(a, out, format)?;
+            write!(out, " {} ", op)?;
+            write_arg(b, out, format)
+        }
+        Value::Div(ref a, ref b) => {
+            write_arg(a, out, format)?;
+            out.write_str(" / ")?;
+            write_arg(b, out, format)
+        }
+        Value::Paren(ref v) => {
+            out.write_str("(")?;
+            write_arg(v, out, format)?;
+            out.write_str(")")
+        }
+        ref v => write!(out, "{}", v.format(format)),
+    }
+}
+
+/// Evaluate a part of a calculation as far as possible.
+fn simplify(v: &Value, scope: &dyn Scope) -> Result<Value, Error> {
+    Ok(match *v {
+        Value::Paren(ref v) => {
+            match simplify(v, scope)? {
+                Value::Calc(CalcName::Calc, mut args) => {
+                    Value::Paren(Box::new(args.remove(0)))
+                }
+                v @ Value::Paren(..) |
+                v @ Value::Numeric(..) |
+                v @ Value::Calc(..) => v,
+                v => Value::Paren(Box::new(v)),
+            }
+        }
+        Value::BinOp(ref a, ref op, ref b) => {
+            let (a, b) = (simplify(a, scope)?, simplify(b, scope)?);
+            match (a, b) {
+                (a @ Value::Numeric(..), b @ Value::Numeric(..)) => {
+                    if *op == Operator::Plus || *op == Operator::Minus {
+                        check_units(&a, &b)?;
+                    }
+                    let precision = scope.get_precision();
+                    op.eval(a.clone(), b.clone(), precision)
+                        .unwrap_or_else(|_| {
+                            Value::BinOp(Box::new(a), op.clone(), Box::new(b))
+                        })
+                }
+                (a, b) => Value::BinOp(Box::new(a), op.clone(), Box::new(b)),
+            }
+        }
+        Value::Div(ref a, ref b) => {
+            let (a, b) = (simplify(a, scope)?, simplify(b, scope)?);
+            match (a, b) {
+                (Value::Numeric(ref a, ref au, ..),
+                 Value::Numeric(ref b, ref bu, ..)) if !b.is_zero() => {
+                    let (unit, factor) = au.div(bu);
+                    Value::Numeric(limit(a / b * factor), unit, false, true)
+                }
+                (a, b) => Value::Div(Box::new(a), Box::new(b)),
+            }
+        }
+        Value::Calc(ref name, ref args) => {
+            match name.evaluate(args, scope)? {
+                Value::Calc(CalcName::Calc, mut args) => {
+                    Value::Paren(Box::new(args.remove(0)))
+                }
+                v => v,
+            }
+        }
+        ref v => v.do_evaluate(scope, true)?,
+    })
+}
+
+/// Find the smallest or largest of some numbers, if they can be
+/// compared.
+fn find_extreme(args: &[Value],
+                pref: Ordering)
+                -> Result<Option<Value>, Error> {
+    let mut result: Option<&Value> = None;
+    for arg in args {
+        if let Some(r) = result {
+            check_units(r, arg)?;
+        }
+        result = match (result, arg) {
+            (None, &Value::Numeric(..)) => Some(arg),
+            (Some(&Value::Numeric(ref r, ref ru, ..)),
+             &Value::Numeric(ref v, ref vu, ..)) => {
+                match vu.scale_to(ru) {
+                    Some(ref f) if (v * f).cmp(r) == pref => Some(arg),
+                    Some(_) => result,
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        }
+    }
+    Ok(result.cloned())
+}
+
+/// Check that a unitless number is not combined with a number with a
+/// unit, which is an error in a calculation.
+fn check_units(a: &Value, b: &Value) -> Result<(), Error> {
+    match (a, b) {
+        (&Value::Numeric(_, ref au, ..), &Value::Numeric(_, ref bu, ..))
+            if au.is_none() != bu.is_none() => {
+            Err(Error::BadValue(format!("{} and {} are incompatible.", a, b)))
+        }
+        _ => Ok(()),
+    }
+}
+
+named!(pub calculation<Value>,
+       do_parse!(name: calc_name >>
+                 tag!("(") >> opt_spacelike >>
+                 args: separated_nonempty_list!(
+                     delimited!(opt_spacelike, tag!(","), opt_spacelike),
+                     calc_sum) >>
+                 opt_spacelike >> tag!(")") >>
+                 calc: expr_opt!(if name.arity_ok(args.len()) {
+                     Some(Value::Calc(name, args))
+                 } else {
+                     None
+                 }) >>
+                 (calc)));
+
+named!(calc_name<CalcName>,
+       alt_complete!(value!(CalcName::Calc, tag!("calc")) |
+                     value!(CalcName::Min, tag!("min")) |
+                     value!(CalcName::Max, tag!("max")) |
+                     value!(CalcName::Clamp, tag!("clamp"))));
+
+// In a calculation, + and - must be surrounded by whitespace.
+named!(calc_sum<Value>,
+       do_parse!(a: calc_product >>
+                 r: fold_many0!(
+                     do_parse!(multispace >>
+                               op: alt_complete!(
+                                   value!(Operator::Plus, tag!("+")) |
+                                   value!(Operator::Minus, tag!("-"))) >>
+                               multispace >>
+                               b: calc_product >>
+                               (op, b)),
+                     a,
+                     |a, (op, b)| Value::BinOp(Box::new(a), op, Box::new(b))) >>
+                 (r)));
+
+named!(calc_product<Value>,
+       do_parse!(a: calc_value >>
+                 r: fold_many0!(
+                     do_parse!(opt_spacelike >>
+                               op: alt_complete!(tag!("*") | tag!("/")) >>
+                               opt_spacelike >>
+                               b: calc_value >>
+                               (op, b)),
+                     a,
+                     |a, (op, b)| if op == b"*" {
+                         Value::BinOp(Box::new(a),
+                                      Operator::Multiply,
+                                      Box::new(b))
+                     } else {
+                         Value::Div(Box::new(a), Box::new(b))
+                     }) >>
+                 (r)));
+
+named!(calc_value<Value>,
+       alt_complete!(
+           map!(delimited!(terminated!(tag!("("), opt_spacelike),
+                           calc_sum,
+                           preceded!(opt_spacelike, tag!(")"))),
+                |v| Value::Paren(Box::new(v))) |
+           single_value));
+
+#[cfg(test)]
+mod test {
+    use variablescope::test::do_evaluate;
+
+    #[test]
+    fn simplify_calc() {
+        assert_eq!(do_evaluate(&[], b"calc(1px + 2px * 3);"), "7px")
+    }
+
+    #[test]
+    fn keep_incompatible() {
+        assert_eq!(do_evaluate(&[("w", "10px")], b"calc(100% - $w);"),
+                   "calc(100% - 10px)")
+    }
+
+    #[test]
+    fn keep_parens() {
+        assert_eq!(do_evaluate(&[], b"calc((100% - 10px) / 3);"),
+                   "calc((100% - 10px) / 3)")
+    }
+
+    #[test]
+    fn min_incompatible() {
+        assert_eq!(do_evaluate(&[], b"min(50%, 300px);"), "min(50%, 300px)")
+    }
+
+    #[test]
+    fn max_compatible() {
+        assert_eq!(do_evaluate(&[], b"max(1cm, 5mm, 0.5in);"), "0.5in")
+    }
+
+    #[test]
+    fn clamp_partly() {
+        assert_eq!(do_evaluate(&[], b"clamp(1rem, 2vw + 1rem, 3rem);"),
+                   "clamp(1rem, 2vw + 1rem, 3rem)")
+    }
+}
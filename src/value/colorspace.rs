@@ -0,0 +1,396 @@
+//! Css Color Level 4 color spaces.
+//!
+//! A color in one of these spaces is a `Value::Color4`, with three
+//! channels and an alpha value.  The channels are converted through
+//! the CIE XYZ (D65) space, and mapped into the srgb gamut (by
+//! reducing the oklch chroma) when a legacy rgb color is needed.
+use num_rational::Rational;
+use num_traits::One;
+use output_style::Format;
+use std::f64::consts::PI;
+use std::fmt;
+use super::{SafeArith, big, from_f64, rational2str, to_f64};
+
+/// A color space of a `Value::Color4`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Lightness (0 .. 100), a and b, in CIE Lab (D50).
+    Lab,
+    /// Lightness (0 .. 100), chroma and hue (degrees) in CIE Lab.
+    Lch,
+    /// Lightness (0 .. 1), a and b in Oklab.
+    Oklab,
+    /// Lightness (0 .. 1), chroma and hue (degrees) in Oklab.
+    Oklch,
+    /// Red, green and blue (0 .. 1) in the Display P3 gamut.
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// Find a color space by its css name.
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        match name {
+            "lab" => Some(ColorSpace::Lab),
+            "lch" => Some(ColorSpace::Lch),
+            "oklab" => Some(ColorSpace::Oklab),
+            "oklch" => Some(ColorSpace::Oklch),
+            "display-p3" => Some(ColorSpace::DisplayP3),
+            _ => None,
+        }
+    }
+
+    /// True if the third channel of this space is a hue.
+    pub fn is_polar(self) -> bool {
+        self == ColorSpace::Lch || self == ColorSpace::Oklch
+    }
+
+    /// The index and range of the lightness channel, if any.
+    pub fn lightness(self) -> Option<(usize, f64)> {
+        match self {
+            ColorSpace::Lab | ColorSpace::Lch => Some((0, 100.)),
+            ColorSpace::Oklab | ColorSpace::Oklch => Some((0, 1.)),
+            ColorSpace::DisplayP3 => None,
+        }
+    }
+
+    /// The value of each channel that a percentage of 100% means.
+    pub fn percent_ref(self) -> [f64; 3] {
+        match self {
+            ColorSpace::Lab => [100., 125., 125.],
+            ColorSpace::Lch => [100., 150., 360.],
+            ColorSpace::Oklab => [1., 0.4, 0.4],
+            ColorSpace::Oklch => [1., 0.4, 360.],
+            ColorSpace::DisplayP3 => [1., 1., 1.],
+        }
+    }
+
+    /// Convert channels in this space to another space.
+    pub fn convert(self, to: ColorSpace, c: [f64; 3]) -> [f64; 3] {
+        if self == to {
+            c
+        } else {
+            to.convert_xyz(self.to_xyz(c))
+        }
+    }
+
+    /// Convert channels in this space to srgb (0 .. 1), mapped into
+    /// the srgb gamut.
+    pub fn to_srgb(self, c: [f64; 3]) -> [f64; 3] {
+        gamut_map(self.convert(ColorSpace::Oklch, c))
+    }
+
+    /// Convert srgb channels (0 .. 1) to this space.
+    pub fn convert_srgb(self, rgb: [f64; 3]) -> [f64; 3] {
+        self.convert_xyz(mul(&SRGB_TO_XYZ, map(rgb, srgb_decode)))
+    }
+
+    fn to_xyz(self, c: [f64; 3]) -> [f64; 3] {
+        match self {
+            ColorSpace::Lab => mul(&D50_TO_D65, lab_to_xyz50(c)),
+            ColorSpace::Lch => ColorSpace::Lab.to_xyz(from_polar(c)),
+            ColorSpace::Oklab => {
+                let lms = map(mul(&OKLAB_TO_LMS, c), |v| v * v * v);
+                mul(&LMS_TO_XYZ, lms)
+            }
+            ColorSpace::Oklch => ColorSpace::Oklab.to_xyz(from_polar(c)),
+            ColorSpace::DisplayP3 => mul(&P3_TO_XYZ, map(c, srgb_decode)),
+        }
+    }
+
+    fn convert_xyz(self, xyz: [f64; 3]) -> [f64; 3] {
+        match self {
+            ColorSpace::Lab => xyz50_to_lab(mul(&D65_TO_D50, xyz)),
+            ColorSpace::Lch => to_polar(ColorSpace::Lab.convert_xyz(xyz)),
+            ColorSpace::Oklab => {
+                mul(&LMS_TO_OKLAB, map(mul(&XYZ_TO_LMS, xyz), f64::cbrt))
+            }
+            ColorSpace::Oklch => to_polar(ColorSpace::Oklab.convert_xyz(xyz)),
+            ColorSpace::DisplayP3 => map(mul(&XYZ_TO_P3, xyz), srgb_encode),
+        }
+    }
+
+    /// Write a color in this space as css.
+    pub fn write(self,
+                 out: &mut fmt::Formatter,
+                 c: &[Rational; 3],
+                 alpha: &Rational,
+                 format: Format)
+                 -> fmt::Result {
+        let short = format.is_compressed();
+        let num = |v: &Rational| {
+            rational2str(&big(v), false, short, format.precision)
+        };
+        let hundred = Rational::from_integer(100);
+        match self {
+            ColorSpace::DisplayP3 => {
+                write!(out,
+                       "color(display-p3 {} {} {}",
+                       num(&c[0]),
+                       num(&c[1]),
+                       num(&c[2]))?
+            }
+            _ => {
+                let l = if self.lightness() == Some((0, 1.)) {
+                    c[0].safe_mul(&hundred)
+                } else {
+                    c[0]
+                };
+                write!(out,
+                       "{}({}% {} {}{}",
+                       self,
+                       num(&l),
+                       num(&c[1]),
+                       num(&c[2]),
+                       if self.is_polar() { "deg" } else { "" })?
+            }
+        }
+        if *alpha < Rational::one() {
+            write!(out, " / {}", num(alpha))?;
+        }
+        out.write_str(")")
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        out.write_str(match *self {
+                          ColorSpace::Lab => "lab",
+                          ColorSpace::Lch => "lch",
+                          ColorSpace::Oklab => "oklab",
+                          ColorSpace::Oklch => "oklch",
+                          ColorSpace::DisplayP3 => "display-p3",
+                      })
+    }
+}
+
+/// Convert channels from `Rational` to `f64`.
+pub fn to_floats(c: &[Rational; 3]) -> [f64; 3] {
+    [to_f64(&c[0]), to_f64(&c[1]), to_f64(&c[2])]
+}
+
+/// Convert channels from `f64` to `Rational`.
+///
+/// NaN channels (e.g. the hue of a gray) are zero.
+pub fn from_floats(c: [f64; 3]) -> [Rational; 3] {
+    let r = |v: f64| from_f64(v).unwrap_or_else(|| Rational::from_integer(0));
+    [r(c[0]), r(c[1]), r(c[2])]
+}
+
+/// Map an oklch color into the srgb gamut, as in the css color 4
+/// specification, returning srgb channels (0 .. 1).
+///
+/// The chroma is reduced until the color is in gamut, or close
+/// enough that clipping it gives a visually similar color.
+fn gamut_map(lch: [f64; 3]) -> [f64; 3] {
+    const JND: f64 = 0.02;
+    const EPSILON: f64 = 0.0001;
+    if lch[0] >= 1. {
+        return [1., 1., 1.];
+    } else if lch[0] <= 0. {
+        return [0., 0., 0.];
+    }
+    let srgb = |c: [f64; 3]| {
+        map(mul(&XYZ_TO_SRGB, ColorSpace::Oklch.to_xyz(c)), srgb_encode)
+    };
+    let in_gamut = |rgb: [f64; 3]| {
+        rgb.iter().all(|v| *v >= -EPSILON && *v <= 1. + EPSILON)
+    };
+    let clip = |rgb: [f64; 3]| map(rgb, |v| v.clamp(0., 1.));
+    let delta = |rgb: [f64; 3], lch: [f64; 3]| {
+        let a = ColorSpace::Oklab.convert_srgb(rgb);
+        let b = from_polar(lch);
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) +
+         (a[2] - b[2]).powi(2))
+                .sqrt()
+    };
+    let mut current = lch;
+    if in_gamut(srgb(current)) {
+        return clip(srgb(current));
+    }
+    let mut clipped = clip(srgb(current));
+    if delta(clipped, current) < JND {
+        return clipped;
+    }
+    let (mut min, mut max) = (0., lch[1]);
+    let mut min_in_gamut = true;
+    while max - min > EPSILON {
+        current[1] = (min + max) / 2.;
+        if min_in_gamut && in_gamut(srgb(current)) {
+            min = current[1];
+            continue;
+        }
+        clipped = clip(srgb(current));
+        let e = delta(clipped, current);
+        if e < JND {
+            if JND - e < EPSILON {
+                break;
+            }
+            min_in_gamut = false;
+            min = current[1];
+        } else {
+            max = current[1];
+        }
+    }
+    clipped
+}
+
+fn to_polar(c: [f64; 3]) -> [f64; 3] {
+    let hue = c[2].atan2(c[1]) * 180. / PI;
+    [c[0], c[1].hypot(c[2]), if hue < 0. { hue + 360. } else { hue }]
+}
+
+fn from_polar(c: [f64; 3]) -> [f64; 3] {
+    let hue = c[2] * PI / 180.;
+    [c[0], c[1] * hue.cos(), c[1] * hue.sin()]
+}
+
+const KAPPA: f64 = 24389. / 27.;
+const EPSILON3: f64 = 216. / 24389.;
+const D50_WHITE: [f64; 3] = [0.3457 / 0.3585, 1., 0.2958 / 0.3585];
+
+fn xyz50_to_lab(xyz: [f64; 3]) -> [f64; 3] {
+    let f = |v: f64, w: f64| {
+        let v = v / w;
+        if v > EPSILON3 {
+            v.cbrt()
+        } else {
+            (KAPPA * v + 16.) / 116.
+        }
+    };
+    let fx = f(xyz[0], D50_WHITE[0]);
+    let fy = f(xyz[1], D50_WHITE[1]);
+    let fz = f(xyz[2], D50_WHITE[2]);
+    [116. * fy - 16., 500. * (fx - fy), 200. * (fy - fz)]
+}
+
+fn lab_to_xyz50(lab: [f64; 3]) -> [f64; 3] {
+    let fy = (lab[0] + 16.) / 116.;
+    let fx = lab[1] / 500. + fy;
+    let fz = fy - lab[2] / 200.;
+    let f = |v: f64| if v.powi(3) > EPSILON3 {
+        v.powi(3)
+    } else {
+        (116. * v - 16.) / KAPPA
+    };
+    let y = if lab[0] > KAPPA * EPSILON3 {
+        fy.powi(3)
+    } else {
+        lab[0] / KAPPA
+    };
+    [f(fx) * D50_WHITE[0], y * D50_WHITE[1], f(fz) * D50_WHITE[2]]
+}
+
+fn srgb_decode(v: f64) -> f64 {
+    if v.abs() <= 0.04045 {
+        v / 12.92
+    } else {
+        v.signum() * ((v.abs() + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn srgb_encode(v: f64) -> f64 {
+    if v.abs() > 0.0031308 {
+        v.signum() * (1.055 * v.abs().powf(1. / 2.4) - 0.055)
+    } else {
+        12.92 * v
+    }
+}
+
+fn map<F: Fn(f64) -> f64>(c: [f64; 3], f: F) -> [f64; 3] {
+    [f(c[0]), f(c[1]), f(c[2])]
+}
+
+fn mul(m: &[[f64; 3]; 3], c: [f64; 3]) -> [f64; 3] {
+    let row = |r: &[f64; 3]| r[0] * c[0] + r[1] * c[1] + r[2] * c[2];
+    [row(&m[0]), row(&m[1]), row(&m[2])]
+}
+
+// The matrices are from the sample code of the css color 4
+// specification.
+
+const SRGB_TO_XYZ: [[f64; 3]; 3] =
+    [[0.41239079926595934, 0.357584339383878, 0.1804807884018343],
+     [0.21263900587151027, 0.715168678767756, 0.07219231536073371],
+     [0.01933081871559182, 0.11919477979462598, 0.9505321522496607]];
+
+const XYZ_TO_SRGB: [[f64; 3]; 3] =
+    [[3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+     [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+     [0.05563007969699366, -0.20397695888897652, 1.0569715142428786]];
+
+const P3_TO_XYZ: [[f64; 3]; 3] =
+    [[0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+     [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+     [0.0, 0.04511338185890264, 1.043944368900976]];
+
+const XYZ_TO_P3: [[f64; 3]; 3] =
+    [[2.493496911941425, -0.9313836179191239, -0.40271078445071684],
+     [-0.8294889695615747, 1.7626640603183463, 0.023624685841943577],
+     [0.03584583024378447, -0.07617238926804182, 0.9568845240076872]];
+
+const D65_TO_D50: [[f64; 3]; 3] =
+    [[1.0479298208405488, 0.022946793341019088, -0.05019222954313557],
+     [0.029627815688159344, 0.990434484573249, -0.01707382502938514],
+     [-0.009243058152591178, 0.015055144896577895, 0.7518742899580008]];
+
+const D50_TO_D65: [[f64; 3]; 3] =
+    [[0.9554734527042182, -0.023098536874261423, 0.0632593086610217],
+     [-0.028369706963208136, 1.0099954580058226, 0.021041398966943008],
+     [0.012314001688319899, -0.020507696433477912, 1.3303659366080753]];
+
+const XYZ_TO_LMS: [[f64; 3]; 3] =
+    [[0.819022437996703, 0.3619062600528904, -0.1288737815209879],
+     [0.0329836539323885, 0.9292868615863434, 0.0361446663506424],
+     [0.0481771893596242, 0.2642395317527308, 0.6335478284694309]];
+
+const LMS_TO_XYZ: [[f64; 3]; 3] =
+    [[1.2268798758459243, -0.5578149944602171, 0.2813910456659647],
+     [-0.0405757452148008, 1.112286803280317, -0.0717110580655164],
+     [-0.0763729366746601, -0.4214933324022432, 1.5869240198367816]];
+
+const LMS_TO_OKLAB: [[f64; 3]; 3] =
+    [[0.210454268309314, 0.7936177747023054, -0.0040720430116193],
+     [1.977998532431168, -2.42859224204858, 0.450593709617411],
+     [0.0259040424655478, 0.7827717124575296, -0.8086757549230774]];
+
+const OKLAB_TO_LMS: [[f64; 3]; 3] =
+    [[1.0, 0.3963377773761749, 0.2158037573299305],
+     [1.0, -0.1055613458156586, -0.0638541728258133],
+     [1.0, -0.0894841775298119, -1.2914855480194092]];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: [f64; 3], b: [f64; 3]) -> bool {
+        (0..3).all(|i| (a[i] - b[i]).abs() < 1e-4)
+    }
+
+    #[test]
+    fn srgb_red_roundtrip() {
+        for space in &[ColorSpace::Lab,
+                       ColorSpace::Lch,
+                       ColorSpace::Oklab,
+                       ColorSpace::Oklch,
+                       ColorSpace::DisplayP3] {
+            let c = space.convert_srgb([1., 0., 0.]);
+            assert!(close(space.to_srgb(c), [1., 0., 0.]),
+                    "{}: {:?}",
+                    space,
+                    c);
+        }
+    }
+
+    #[test]
+    fn oklch_of_red() {
+        let c = ColorSpace::Oklch.convert_srgb([1., 0., 0.]);
+        assert!(close(c, [0.62796, 0.25768, 29.2339]), "{:?}", c);
+    }
+
+    #[test]
+    fn p3_red_is_mapped() {
+        let rgb = ColorSpace::DisplayP3.to_srgb([1., 0., 0.]);
+        assert!(rgb.iter().all(|v| *v >= 0. && *v <= 1.), "{:?}", rgb);
+        assert!(rgb[0] > 0.99 && rgb[1] < 0.1, "{:?}", rgb);
+    }
+}
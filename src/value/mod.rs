@@ -1,20 +1,32 @@
+mod calc;
+mod colorspace;
+mod number;
 mod operator;
 #[cfg(test)]
 mod tests;
 
-use self::operator::Operator;
+pub use self::calc::CalcName;
+pub use self::colorspace::{ColorSpace, from_floats, to_floats};
+use self::calc::calculation;
+pub use self::number::{SafeArith, big, big_to_f64, from_big, from_f64,
+                       limit, to_f64};
+use self::number::parse_decimal;
+use self::operator::{Operator, is_calc, undefined_operation};
 use colors::{name_to_rgb, rgb_to_name};
 use error::Error;
 use formalargs::{CallArgs, call_args};
-use functions::get_builtin_function;
+use functions::{SassFunction, get_builtin_function};
 use nom::multispace;
-use num_rational::Rational;
-use num_traits::{One, Signed, Zero};
+use num_bigint::BigInt;
+use num_rational::{BigRational, Rational};
+use num_traits::{One, Signed, ToPrimitive, Zero, pow};
+use ordermap::{self, OrderMap};
+use output_style::{Format, Formatted, OutputStyle};
 use parseutil::{is_name_char, name, opt_spacelike, spacelike2};
 use std::fmt;
-use std::str::{FromStr, from_utf8};
-use unit::{Unit, unit};
-use variablescope::Scope;
+use std::str::from_utf8;
+use unit::{UnitSet, unit};
+use variablescope::{Member, Scope, check_member};
 
 /// A sass value.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -22,19 +34,33 @@ pub enum Value {
     /// A call has a name and an argument (which may be multi).
     Call(String, CallArgs),
     /// Sometimes an actual division, sometimes "a/b".
-    /// In the later case, the booleans tell if there should be whitespace
-    /// before / after the slash.
-    Div(Box<Value>, Box<Value>, bool, bool),
+    ///
+    /// When evaluated, this is a division if either operand is
+    /// calculated (e.g. a variable or a function result) or the slash
+    /// is part of a parenthesized or arithmetic expression.
+    /// Otherwise, it evaluates to a slash-separated list.
+    Div(Box<Value>, Box<Value>),
     Literal(String, Quotes),
-    List(Vec<Value>, ListSeparator),
-    /// A Numeric value is a rational value with a Unit (which may be
-    /// Unit::None) and flags.
+    /// A list.  The boolean is true for a bracketed list, such as
+    /// `[full-start]`.
+    List(Vec<Value>, ListSeparator, bool),
+    /// A sass map, with keys in the order they were defined.
+    Map(OrderMap<Value, Value>),
+    /// The variable arguments of a function or mixin.
+    ///
+    /// Positional arguments are kept as a list, keyword arguments
+    /// that does not match any formal argument are kept in the map.
+    ArgList(Vec<Value>, OrderMap<Value, Value>),
+    /// A Numeric value is a rational value with a UnitSet (which may
+    /// be empty) and flags.
+    /// Results of arithmetic are rounded to 20 decimals.
+    /// There are no NaN or infinite numbers.
     ///
     /// The first flag is true for values with an explicit + sign.
     ///
     /// The second flag is true for calculated values and false for
     /// literal values.
-    Numeric(Rational, Unit, bool, bool),
+    Numeric(BigRational, UnitSet, bool, bool),
     /// "(a/b) and a/b differs semantically.  Parens means the value
     /// should be evaluated numerically if possible, without parens /
     /// is not allways division.
@@ -43,26 +69,41 @@ pub enum Value {
     /// Both a numerical and original string representation,
     /// since case and length should be preserved (#AbC vs #aabbcc).
     Color(Rational, Rational, Rational, Rational, Option<String>),
+    /// A color in a css color level 4 color space, such as
+    /// `oklch(70% 0.1 200deg)`, with three channels and alpha.
+    Color4(ColorSpace, [Rational; 3], Rational),
     Null,
     True,
     False,
+    /// A calculation, such as `calc(100% - 10px)`, that could not be
+    /// simplified to a single number.
+    Calc(CalcName, Vec<Value>),
+    /// A function value, as returned by `get-function`.
+    ///
+    /// The function is `None` for a plain css function.
+    Function(String, Option<SassFunction>),
     /// A binary operation, two operands and an operator.
     BinOp(Box<Value>, Operator, Box<Value>),
     UnaryOp(Operator, Box<Value>),
     Interpolation(Box<Value>),
 }
 
-/// The difference between a comma-separated and a
-/// whitespace-separated list.
+/// The difference between a comma-separated, a whitespace-separated
+/// and a slash-separated list.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ListSeparator {
     Comma,
     Space,
+    /// As in the `font: 12px/1.5` shorthand.
+    Slash,
 }
 
 impl Value {
     pub fn scalar(v: isize) -> Self {
-        Value::Numeric(Rational::from_integer(v), Unit::None, false, false)
+        Value::Numeric(BigRational::from_integer(v.into()),
+                       UnitSet::scalar(),
+                       false,
+                       false)
     }
     pub fn bool(v: bool) -> Self {
         if v { Value::True } else { Value::False }
@@ -88,12 +129,16 @@ impl Value {
 
     pub fn type_name(&self) -> &'static str {
         match *self {
-            Value::Color(..) => "color",
+            Value::Color(..) | Value::Color4(..) => "color",
             Value::Literal(..) => "string",
             Value::Numeric(..) => "number",
             Value::List(..) => "list",
+            Value::Map(..) => "map",
+            Value::ArgList(..) => "arglist",
             Value::True | Value::False => "bool",
             Value::Null => "null",
+            Value::Function(..) => "function",
+            Value::Calc(..) => "calculation",
             _ => "unknown",
         }
     }
@@ -117,115 +162,200 @@ impl Value {
     pub fn is_null(&self) -> bool {
         match *self {
             Value::Null => true,
-            Value::List(ref list, _) => list.iter().all(|v| v.is_null()),
+            Value::List(ref list, _, false) => {
+                list.iter().all(|v| v.is_null())
+            }
             _ => false,
         }
     }
 
+    /// Get the items to iterate over in an `@each` loop.
+    ///
+    /// The items of a list are its elements, the items of a map are
+    /// space-separated key-value pairs, and any other value is a
+    /// single item.
+    pub fn iter_items(self) -> Vec<Value> {
+        match self {
+            Value::List(v, ..) => v,
+            Value::ArgList(v, _) => v,
+            Value::Map(map) => {
+                map.into_iter()
+                    .map(|(k, v)| {
+                             Value::List(vec![k, v],
+                                         ListSeparator::Space,
+                                         false)
+                         })
+                    .collect()
+            }
+            v => vec![v],
+        }
+    }
+
+    /// Find a part of this value that has no css representation.
+    ///
+    /// That is a number with a compound unit, such as `px*px`, or a
+    /// function value.
+    pub fn invalid_css(&self) -> Option<&Value> {
+        match *self {
+            Value::Numeric(_, ref unit, ..) if !unit.is_css() => Some(self),
+            Value::Function(..) => Some(self),
+            Value::List(ref v, ..) |
+            Value::ArgList(ref v, _) => {
+                v.iter().filter_map(|v| v.invalid_css()).next()
+            }
+            Value::Div(ref a, ref b) => {
+                a.invalid_css().or_else(|| b.invalid_css())
+            }
+            _ => None,
+        }
+    }
+
     pub fn integer_value(&self) -> Result<isize, Error> {
         match self {
             &Value::Numeric(ref num, ..) if num.is_integer() => {
-                Ok(num.to_integer())
+                num.to_integer()
+                    .to_isize()
+                    .ok_or_else(|| Error::bad_value("integer", self))
             }
             v => Err(Error::bad_value("integer", v)),
         }
     }
 
-    pub fn evaluate(&self, scope: &Scope) -> Value {
+    pub fn evaluate(&self, scope: &dyn Scope) -> Result<Value, Error> {
         self.do_evaluate(scope, false)
     }
-    pub fn do_evaluate(&self, scope: &Scope, arithmetic: bool) -> Value {
-        match *self {
+    pub fn do_evaluate(&self,
+                       scope: &dyn Scope,
+                       arithmetic: bool)
+                       -> Result<Value, Error> {
+        Ok(match *self {
             Value::Literal(ref v, ref q) => {
                 Value::Literal(v.clone(), q.clone())
             }
-            Value::Paren(ref v) => v.do_evaluate(scope, true),
-            Value::Color(_, _, _, _, _) => self.clone(),
+            Value::Paren(ref v) => v.do_evaluate(scope, true)?,
+            Value::Color(_, _, _, _, _) | Value::Color4(..) => self.clone(),
             Value::Variable(ref name) => {
+                check_member(scope, name, Member::Variable)?;
                 let v = scope.get(name);
-                v.do_evaluate(scope, true)
+                v.do_evaluate(scope, true)?
             }
-            Value::List(ref v, ref s) => {
+            Value::List(ref v, ref s, b) => {
                 Value::List(v.iter()
                                 .map(|v| v.do_evaluate(scope, false))
-                                .collect::<Vec<_>>(),
-                            s.clone())
+                                .collect::<Result<Vec<_>, _>>()?,
+                            s.clone(),
+                            b)
+            }
+            Value::Map(ref m) => {
+                let mut result = OrderMap::new();
+                for &(ref k, ref v) in m.iter() {
+                    let k = k.do_evaluate(scope, false)?;
+                    if result.contains_key(&k) {
+                        return Err(Error::BadValue("Duplicate key.".into()));
+                    }
+                    result.insert(k, v.do_evaluate(scope, false)?);
+                }
+                Value::Map(result)
+            }
+            Value::ArgList(ref v, ref kw) => {
+                Value::ArgList(v.iter()
+                                   .map(|v| v.do_evaluate(scope, false))
+                                   .collect::<Result<_, _>>()?,
+                               kw.iter()
+                                   .map(|&(ref k, ref v)| {
+                                            Ok((k.clone(),
+                                                v.do_evaluate(scope,
+                                                              false)?))
+                                        })
+                                   .collect::<Result<_, Error>>()?)
             }
             Value::Call(ref name, ref args) => {
-                match scope.call_function(name, args) {
+                match scope.call_function(name, args)? {
                     Some(value) => value,
                     None => {
+                        check_member(scope, name, Member::Function)?;
                         if let Some(function) = get_builtin_function(name) {
-                            match function.call(scope, args) {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    panic!("Error in function {}: {:?}",
-                                           name, e)
-                                }
-                            }
+                            function.call(scope, args)?
                         } else {
-                            Value::Call(name.clone(), args.xyzzy(scope))
+                            Value::Call(name.clone(), args.xyzzy(scope)?)
                         }
                     }
                 }
             }
-            Value::Div(ref a, ref b, ref space1, ref space2) => {
+            Value::Div(ref a, ref b) => {
                 let (a, b) = {
-                    let aa = a.do_evaluate(scope, arithmetic);
+                    let aa = a.do_evaluate(scope, arithmetic)?;
                     let b =
-                        b.do_evaluate(scope, arithmetic || a.is_calculated());
+                        b.do_evaluate(scope, arithmetic || a.is_calculated())?;
                     if !arithmetic && b.is_calculated() && !a.is_calculated() {
-                        (a.do_evaluate(scope, true), b)
+                        (a.do_evaluate(scope, true)?, b)
                     } else {
                         (aa, b)
                     }
                 };
+                if is_calc(&a) || is_calc(&b) {
+                    return Err(undefined_operation(&a, "/", &b));
+                }
                 if arithmetic || a.is_calculated() || b.is_calculated() {
-                    match (&a, &b) {
-                        (&Value::Color(ref r, ref g, ref b, ref a, _),
-                         &Value::Numeric(ref n, Unit::None, ..)) => {
-                            Value::rgba(r / n, g / n, b / n, *a)
+                    match (a, b) {
+                        (Value::Color(ref r, ref g, ref b, ref a, _),
+                         Value::Numeric(ref n, ref u, ..))
+                            if u.is_none() && !n.is_zero() => {
+                            let n = from_big(n);
+                            Value::rgba(r.safe_div(&n)?,
+                                        g.safe_div(&n)?,
+                                        b.safe_div(&n)?,
+                                        *a)
                         }
-                        (&Value::Numeric(ref av, ref au, ..),
-                         &Value::Numeric(ref bv, ref bu, ..)) => {
-                            if bv.is_zero() {
-                                Value::Div(Box::new(a.clone()),
-                                           Box::new(b.clone()),
-                                           *space1,
-                                           *space2)
-                            } else if bu == &Unit::None {
-                                Value::Numeric(av / bv, au.clone(), false, true)
-                            } else if au == bu {
-                                Value::Numeric(av / bv, Unit::None, false, true)
-                            } else {
-                                Value::Div(Box::new(a.clone()),
-                                           Box::new(b.clone()),
+                        (Value::Numeric(ref av, ref au, ..),
+                         Value::Numeric(ref bv, ref bu, ..))
+                            if !bv.is_zero() => {
+                            let (unit, factor) = au.div(bu);
+                            Value::Numeric(limit(av / bv * factor),
+                                           unit,
                                            false,
-                                           false)
-                            }
-                        }
-                        (a, b) => {
-                            Value::Div(Box::new(a.clone()),
-                                       Box::new(b.clone()),
-                                       false,
-                                       false)
+                                           true)
                         }
+                        (a, b) => slash(a, b),
                     }
                 } else {
-                    Value::Div(Box::new(a), Box::new(b), *space1, *space2)
+                    slash(a, b)
                 }
             }
             Value::Numeric(ref v, ref u, ref sign, ref calc) => {
-                Value::Numeric(*v, u.clone(), *sign, arithmetic || *calc)
+                Value::Numeric(v.clone(),
+                               u.clone(),
+                               *sign,
+                               arithmetic || *calc)
             }
             Value::Null => Value::Null,
+            Value::Function(..) => self.clone(),
+            Value::Calc(ref name, ref args) => {
+                // A user-defined function may replace min, max or clamp.
+                let fname = name.to_string();
+                let user = *name != CalcName::Calc &&
+                           scope.get_function(&fname) !=
+                           get_builtin_function(&fname);
+                let call = if user {
+                    let args = args.iter().map(|a| (None, a.clone())).collect();
+                    scope.call_function(&fname, &CallArgs::new(args))?
+                } else {
+                    None
+                };
+                match call {
+                    Some(value) => value,
+                    None => name.evaluate(args, scope)?,
+                }
+            }
             Value::True => Value::True,
             Value::False => Value::False,
             Value::BinOp(ref a, ref op, ref b) => {
-                op.eval(a.do_evaluate(scope, true), b.do_evaluate(scope, true))
+                op.eval(a.do_evaluate(scope, true)?,
+                        b.do_evaluate(scope, true)?,
+                        scope.get_precision())?
             }
             Value::UnaryOp(ref op, ref v) => {
-                match (op.clone(), v.do_evaluate(scope, true)) {
+                match (op.clone(), v.do_evaluate(scope, true)?) {
                     (Operator::Not, Value::Numeric(v, ..)) => {
                         Value::bool(v.is_zero())
                     }
@@ -241,21 +371,39 @@ impl Value {
                 }
             }
             Value::Interpolation(ref v) => {
-                match without_quotes(v.do_evaluate(scope, true)) {
+                match without_quotes(v.do_evaluate(scope, true)?) {
                     Value::Null => Value::Null,
                     Value::Literal(s, _) => Value::Literal(s, Quotes::None),
-                    v => Value::Literal(format!("{}", v), Quotes::None),
+                    v => {
+                        let p = scope.get_precision();
+                        let s = v.format(Format::with_precision(p));
+                        Value::Literal(s.to_string(), Quotes::None)
+                    }
                 }
             }
+        })
+    }
+}
+
+/// A slash that is not a division separates two values.
+///
+/// A trailing slash (as in an url) is kept as it is.
+fn slash(a: Value, b: Value) -> Value {
+    match a {
+        a if b == Value::Null => Value::Div(Box::new(a), Box::new(b)),
+        Value::List(mut v, ListSeparator::Slash, false) => {
+            v.push(b);
+            Value::List(v, ListSeparator::Slash, false)
         }
+        a => Value::List(vec![a, b], ListSeparator::Slash, false),
     }
 }
 
 fn without_quotes(v: Value) -> Value {
     match v {
         Value::Literal(s, _) => Value::Literal(s, Quotes::None),
-        Value::List(list, s) => {
-            Value::List(list.into_iter().map(without_quotes).collect(), s)
+        Value::List(list, s, b) => {
+            Value::List(list.into_iter().map(without_quotes).collect(), s, b)
         }
         v => v,
     }
@@ -270,9 +418,36 @@ pub enum Quotes {
     None,
 }
 
+impl Value {
+    /// Get this value with a `Format` to write it in.
+    pub fn format<'a>(&'a self, format: Format) -> Formatted<'a, Value> {
+        Formatted {
+            value: self,
+            format: format,
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
-        match self {
+        let style = if out.alternate() {
+            OutputStyle::Compressed
+        } else {
+            OutputStyle::Normal
+        };
+        self.format(Format::from(style)).fmt(out)
+    }
+}
+
+impl<'a> fmt::Display for Formatted<'a, Value> {
+    fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+        let format = self.format;
+        let compressed = format.is_compressed();
+        let precision = format.precision;
+        // Parts of maps, calculations and function calls are written
+        // in the default style.
+        let inner = Format::with_precision(precision);
+        match self.value {
             &Value::Literal(ref s, ref q) => {
                 match *q {
                     Quotes::Double => {
@@ -303,17 +478,25 @@ impl fmt::Display for Value {
                 }
             }
             &Value::Numeric(ref v, ref u, ref with_sign, _) => {
-                let short = out.alternate();
-                write!(out, "{}{}", rational2str(v, *with_sign, short), u)
+                write!(out,
+                       "{}{}",
+                       rational2str(v, *with_sign, compressed, precision),
+                       u)
+            }
+            &Value::Color4(ref space, ref c, ref a) => {
+                space.write(out, c, a, format)
             }
             &Value::Color(ref r, ref g, ref b, ref a, ref s) => {
                 let r = r.round().to_integer() as u8;
                 let g = g.round().to_integer() as u8;
                 let b = b.round().to_integer() as u8;
-                if let Some(ref s) = *s {
+                if compressed && format.short_colors {
+                    let color = shortest_color(r, g, b, a, precision);
+                    out.write_str(&color)
+                } else if let Some(ref s) = *s {
                     write!(out, "{}", s)
                 } else if a >= &Rational::from_integer(1) {
-                    if out.alternate() {
+                    if compressed {
                         // E.g. #ff00cc can be written #f0c in css.
                         // 0xff / 17 = 0xf (since 17 = 0x11).
                         let hex = if r % 17 == 0 && g % 17 == 0 &&
@@ -339,71 +522,85 @@ impl fmt::Display for Value {
                 } else if a.is_zero() && r.is_zero() && g.is_zero() &&
                           b.is_zero() {
                     write!(out, "transparent")
-                } else if out.alternate() {
+                } else if compressed {
                     write!(out,
                            "rgba({},{},{},{})",
                            r,
                            g,
                            b,
-                           rational2str(a, false, false))
+                           rational2str(&big(a), false, false, precision))
                 } else {
                     write!(out,
                            "rgba({}, {}, {}, {})",
                            r,
                            g,
                            b,
-                           rational2str(a, false, false))
+                           rational2str(&big(a), false, false, precision))
                 }
             }
-            &Value::List(ref v, ref sep) => {
+            &Value::List(ref v, ref sep, bracketed) => {
                 let t = v.iter()
                     .filter(|v| !v.is_null())
-                    .map(|v| if out.alternate() {
-                             format!("{:#}", v)
-                         } else {
-                             format!("{}", v)
-                         })
+                    .map(|v| v.format(format).to_string())
                     .collect::<Vec<_>>()
                     .join(match *sep {
                               ListSeparator::Comma => {
-                                  if out.alternate() { "," } else { ", " }
+                                  if compressed { "," } else { ", " }
                               }
                               ListSeparator::Space => " ",
+                              ListSeparator::Slash => "/",
                           });
-                write!(out, "{}", t)
+                if bracketed {
+                    write!(out, "[{}]", t)
+                } else {
+                    write!(out, "{}", t)
+                }
             }
-            &Value::Div(ref a, ref b, s1, s2) => {
-                a.fmt(out)?;
-                if s1 {
-                    out.write_str(" ")?;
+            &Value::Map(ref map) => {
+                // This is not a valid css value, but is useful for
+                // inspecting and debugging.
+                out.write_str("(")?;
+                for (i, &(ref k, ref v)) in map.iter().enumerate() {
+                    if i > 0 {
+                        out.write_str(", ")?;
+                    }
+                    write!(out, "{}: {}", k.format(inner), v.format(inner))?;
                 }
+                out.write_str(")")
+            }
+            &Value::ArgList(ref v, _) => {
+                Value::List(v.clone(), ListSeparator::Comma, false)
+                    .format(format)
+                    .fmt(out)
+            }
+            &Value::Div(ref a, ref b) => {
+                a.format(format).fmt(out)?;
                 out.write_str("/")?;
-                if s2 {
-                    out.write_str(" ")?;
-                }
-                b.fmt(out)
+                b.format(format).fmt(out)
+            }
+            &Value::Call(ref name, ref arg) => {
+                write!(out, "{}({})", name, arg.format(inner))
             }
-            &Value::Call(ref name, ref arg) => write!(out, "{}({})", name, arg),
             &Value::BinOp(ref a, Operator::Plus, ref b) => {
                 // The plus operator is also a concat operator
-                a.fmt(out)?;
-                b.fmt(out)
+                a.format(format).fmt(out)?;
+                b.format(format).fmt(out)
             }
             &Value::BinOp(ref a, ref op, ref b) => {
-                a.fmt(out)?;
+                a.format(format).fmt(out)?;
                 op.fmt(out)?;
-                b.fmt(out)
+                b.format(format).fmt(out)
             }
             &Value::Paren(ref v) => {
                 out.write_str("(")?;
-                v.fmt(out)?;
+                v.format(format).fmt(out)?;
                 out.write_str(")")
             }
             &Value::True => write!(out, "true"),
             &Value::False => write!(out, "false"),
             &Value::UnaryOp(ref op, ref v) => {
                 op.fmt(out)?;
-                v.fmt(out)
+                v.format(format).fmt(out)
             }
             &Value::Variable(ref name) => {
                 // Output as source in case it was not evaluated.
@@ -415,6 +612,21 @@ impl fmt::Display for Value {
                 value.fmt(out)?;
                 out.write_str("}")
             }
+            &Value::Calc(ref name, ref args) => {
+                write!(out, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        out.write_str(", ")?;
+                    }
+                    calc::write_arg(arg, out, inner)?;
+                }
+                out.write_str(")")
+            }
+            &Value::Function(ref name, _) => {
+                // This is not a valid css value, but is useful for
+                // inspecting and debugging.
+                write!(out, "get-function(\"{}\")", name)
+            }
             &Value::Null => Ok(()),
         }
     }
@@ -432,7 +644,49 @@ impl PartialOrd for Value {
     }
 }
 
-fn rational2str(r: &Rational, with_sign: bool, skipzero: bool) -> String {
+/// The shortest css representation of a color.
+///
+/// The alpha value is written in hex only if it is exact.
+fn shortest_color(r: u8,
+                  g: u8,
+                  b: u8,
+                  a: &Rational,
+                  precision: usize)
+                  -> String {
+    let ff = Rational::from_integer(255);
+    let mut bytes = vec![r, g, b];
+    if *a < Rational::one() {
+        let a255 = a * ff;
+        if !a255.is_integer() {
+            return format!("rgba({},{},{},{})",
+                           r,
+                           g,
+                           b,
+                           rational2str(&big(a), false, true, precision));
+        }
+        bytes.push(a255.to_integer() as u8);
+    }
+    // E.g. #ff00cc can be written #f0c in css.
+    // 0xff / 17 = 0xf (since 17 = 0x11).
+    let hex = if bytes.iter().all(|v| v % 17 == 0) {
+        bytes.iter().map(|v| format!("{:x}", v / 17)).collect::<String>()
+    } else {
+        bytes.iter().map(|v| format!("{:02x}", v)).collect::<String>()
+    };
+    match rgb_to_name(r, g, b) {
+        Some(name) if bytes.len() == 3 && name.len() <= hex.len() + 1 => {
+            name.into()
+        }
+        _ => format!("#{}", hex),
+    }
+}
+
+/// Write a number with at most `precision` decimals.
+fn rational2str(r: &BigRational,
+                with_sign: bool,
+                skipzero: bool,
+                precision: usize)
+                -> String {
     if r.is_integer() {
         if with_sign {
             format!("{:+}", r.numer())
@@ -440,16 +694,25 @@ fn rational2str(r: &Rational, with_sign: bool, skipzero: bool) -> String {
             format!("{}", r.numer())
         }
     } else {
-        let prec = Rational::from_integer(100000);
-        let v = (r * prec).round() / prec;
-        let v = *v.numer() as f64 / *v.denom() as f64;
-        let mut result = if with_sign {
-            format!("{:+}", v)
-        } else {
-            format!("{}", v)
-        };
-        if skipzero && result.starts_with("0.") {
-            result.remove(0);
+        let scale = pow(BigInt::from(10), precision);
+        let scale = BigRational::from_integer(scale);
+        let digits = (r * scale).round().to_integer();
+        let mut result = format!("{:0>1$}", digits.abs(), precision + 1);
+        let point = result.len() - precision;
+        result.insert(point, '.');
+        let len = result.trim_end_matches('0').trim_end_matches('.').len();
+        result.truncate(len);
+        if digits.is_negative() {
+            result.insert(0, '-');
+        } else if with_sign {
+            result.insert(0, '+');
+        }
+        if skipzero {
+            if result.starts_with("0.") {
+                result.remove(0);
+            } else if result.starts_with("-0.") || result.starts_with("+0.") {
+                result.remove(1);
+            }
         }
         result
     }
@@ -466,19 +729,22 @@ named!(pub value_expression<&[u8], Value>,
            (if result.len() == 1 && trail.is_empty() {
                result.into_iter().next().unwrap()
            } else {
-               Value::List(result, ListSeparator::Comma)
+               Value::List(result, ListSeparator::Comma, false)
            })));
 
+// A trailing `...` marks a rest argument, it is not a list item.
 named!(pub space_list<&[u8], Value>,
        do_parse!(first: single_expression >>
                  list: fold_many0!(
-                     preceded!(opt!(multispace), single_expression),
+                     do_parse!(opt!(multispace) >> not!(tag!("...")) >>
+                               item: single_expression >>
+                               (item)),
                      vec![first],
                      |mut list: Vec<Value>, item| { list.push(item); list }) >>
                  (if list.len() == 1 {
                      list.into_iter().next().unwrap()
                  } else {
-                     Value::List(list, ListSeparator::Space)
+                     Value::List(list, ListSeparator::Space, false)
                  })));
 
 named!(pub single_expression<Value>,
@@ -541,21 +807,24 @@ named!(pub sum_expression<Value>,
 named!(term_value<Value>,
        do_parse!(a: single_value >>
                  r: fold_many0!(
-                     do_parse!(s1: opt!(multispace) >>
-                               op: alt_complete!(tag!("*") | tag!("/")) >>
-                               s2: opt!(multispace) >>
+                     do_parse!(opt!(multispace) >>
+                               op: alt_complete!(
+                                   value!(Some(Operator::Multiply),
+                                          tag!("*")) |
+                                   value!(Some(Operator::Modulo), tag!("%")) |
+                                   value!(None, tag!("/"))) >>
+                               opt!(multispace) >>
                                b: opt!(single_value) >>
-                               (s1.is_some(), op, s2.is_some(), b)),
+                               (op, b)),
                      a,
-                     |a, (s1, op, s2, b)| {
+                     |a, (op, b)| {
                          let b: Option<Value> = b;
                          let b = b.unwrap_or(Value::Null);
-                         if op == b"*" {
-                             Value::BinOp(Box::new(a),
-                                          Operator::Multiply,
-                                          Box::new(b))
-                         } else {
-                             Value::Div(Box::new(a), Box::new(b), s1, s2)
+                         match op {
+                             Some(op) => {
+                                 Value::BinOp(Box::new(a), op, Box::new(b))
+                             }
+                             None => Value::Div(Box::new(a), Box::new(b)),
                          }
                      }) >>
                  (r)));
@@ -570,13 +839,13 @@ named!(pub single_value<&[u8], Value>,
                      u: opt!(unit) >>
                      (Value::Numeric(
                          {
-                             let d = Rational::from_str(
-                                 from_utf8(r).unwrap()).unwrap() +
-                                 d.map(decimals_to_rational)
-                                 .unwrap_or_else(Rational::zero);
+                             let d = parse_decimal(
+                                 from_utf8(r).unwrap(),
+                                 d.map(|d| from_utf8(d).unwrap())
+                                     .unwrap_or(""));
                              if sign == Some(b"-") { -d } else { d }
                          },
-                         u.unwrap_or(Unit::None),
+                         u.map(UnitSet::from).unwrap_or_default(),
                          sign == Some(b"+"),
                          false))) |
            do_parse!(sign: opt!(alt!(tag!("-") | tag!("+"))) >>
@@ -585,13 +854,25 @@ named!(pub single_value<&[u8], Value>,
                      u: opt!(unit) >>
                      (Value::Numeric(
                          {
-                             let d = decimals_to_rational(d);
+                             let d = parse_decimal("",
+                                                   from_utf8(d).unwrap());
                              if sign == Some(b"-") { -d } else { d }
                          },
-                         u.unwrap_or(Unit::None),
+                         u.map(UnitSet::from).unwrap_or_default(),
                          sign == Some(b"+"),
                          false))) |
            variable |
+           do_parse!(tag!("#") >> r: hexchar2 >> g: hexchar2 >> b: hexchar2 >>
+                     a: hexchar2 >>
+                     (Value::Color(from_hex(r),
+                                   from_hex(g),
+                                   from_hex(b),
+                                   from_hex(a) / Rational::from_integer(255),
+                                   Some(format!("#{}{}{}{}",
+                                                from_utf8(r).unwrap(),
+                                                from_utf8(g).unwrap(),
+                                                from_utf8(b).unwrap(),
+                                                from_utf8(a).unwrap()))))) |
            do_parse!(tag!("#") >> r: hexchar2 >> g: hexchar2 >> b: hexchar2 >>
                      (Value::Color(from_hex(r),
                                    from_hex(g),
@@ -601,6 +882,17 @@ named!(pub single_value<&[u8], Value>,
                                                 from_utf8(r).unwrap(),
                                                 from_utf8(g).unwrap(),
                                                 from_utf8(b).unwrap()))))) |
+           do_parse!(tag!("#") >> r: hexchar >> g: hexchar >> b: hexchar >>
+                     a: hexchar >>
+                     (Value::Color(from_hex(r) * Rational::new(17, 1),
+                                   from_hex(g) * Rational::new(17, 1),
+                                   from_hex(b) * Rational::new(17, 1),
+                                   from_hex(a) / Rational::from_integer(15),
+                                   Some(format!("#{}{}{}{}",
+                                                from_utf8(r).unwrap(),
+                                                from_utf8(g).unwrap(),
+                                                from_utf8(b).unwrap(),
+                                                from_utf8(a).unwrap()))))) |
            do_parse!(tag!("#") >> r: hexchar >> g: hexchar >> b: hexchar >>
                      (Value::Color(from_hex(r) * Rational::new(17, 1),
                                    from_hex(g) * Rational::new(17, 1),
@@ -620,6 +912,8 @@ named!(pub single_value<&[u8], Value>,
                      opt_spacelike >>
                      v: single_value >>
                      (Value::UnaryOp(op, Box::new(v)))) |
+           calculation |
+           module_member |
            function_call |
            unquoted_literal |
            map!(tag!("\"\""),
@@ -628,23 +922,77 @@ named!(pub single_value<&[u8], Value>,
            map!(tag!("''"),
                 |_| Value::Literal("".into(), Quotes::Single)) |
            singlequoted_string |
+           map_literal |
+           bracketed_list |
            map!(delimited!(preceded!(tag!("("), opt_spacelike),
                            opt!(value_expression),
                            terminated!(opt_spacelike, tag!(")"))),
                 |val: Option<Value>| match val {
                     Some(v) => Value::Paren(Box::new(v)),
-                    None => Value::List(vec![], ListSeparator::Space),
+                    None => Value::List(vec![], ListSeparator::Space, false),
                 })));
 
+named!(bracketed_list<Value>,
+       map!(delimited!(preceded!(tag!("["), opt_spacelike),
+                       opt!(value_expression),
+                       terminated!(opt_spacelike, tag!("]"))),
+            |val: Option<Value>| match val {
+                Some(Value::List(v, s, false)) => Value::List(v, s, true),
+                Some(v) => Value::List(vec![v], ListSeparator::Space, true),
+                None => Value::List(vec![], ListSeparator::Space, true),
+            }));
+
+named!(map_literal<Value>,
+       do_parse!(tag!("(") >> opt_spacelike >>
+                 items: separated_nonempty_list!(
+                     delimited!(opt_spacelike, tag!(","), opt_spacelike),
+                     pair!(map_key,
+                           preceded!(delimited!(opt_spacelike,
+                                                tag!(":"),
+                                                opt_spacelike),
+                                     space_list))) >>
+                 opt!(delimited!(opt_spacelike, tag!(","), opt_spacelike)) >>
+                 opt_spacelike >> tag!(")") >>
+                 (Value::Map(ordermap::with_duplicates(items)))));
+
+// A map key is any single expression.  As an unquoted literal may
+// contain a colon, a plain name followed by a colon is handled first.
+named!(map_key<Value>,
+       alt_complete!(
+           map!(terminated!(name,
+                            peek!(preceded!(opt_spacelike, tag!(":")))),
+                literal_or_color) |
+           single_expression));
+
+fn literal_or_color(val: String) -> Value {
+    if let Some((r, g, b)) = name_to_rgb(&val) {
+        Value::Color(r, g, b, Rational::from_integer(1), Some(val))
+    } else {
+        Value::Literal(val, Quotes::None)
+    }
+}
+
 named!(variable<Value>,
        do_parse!(tag!("$") >>  name: name >> (Value::Variable(name))));
 
+// A variable or function call in a module, as `ns.$name` or
+// `ns.name(args)`.
+named!(module_member<Value>,
+       do_parse!(ns: name >> tag!(".") >>
+                 member: alt_complete!(
+                     map!(preceded!(tag!("$"), name),
+                          |n| Value::Variable(format!("{}.{}", ns, n))) |
+                     do_parse!(n: name >> args: call_args >>
+                               (Value::Call(format!("{}.{}", ns, n), args)))) >>
+                 (member)));
+
 named!(pub interpolation<Value>,
        map!(delimited!(tag!("#{"), value_expression, tag!("}")),
             |v| Value::Interpolation(Box::new(v))));
 
 named!(unquoted_literal<Value>,
-       do_parse!(t: alt!(interpolation | unquoted_literal_part) >>
+       do_parse!(not!(tag!("calc(")) >>
+                 t: alt!(interpolation | unquoted_literal_part) >>
                  first: expr_res!(ok_as_literal(t)) >>
                  all: fold_many0!(
                      alt!(interpolation | function_call |
@@ -672,8 +1020,11 @@ named!(pub extended_literal<Value>,
        map!(take_while1!(is_ext_str_char),
             |v| Value::Literal(from_utf8(v).unwrap().into(), Quotes::None)));
 
+// A `calc()` that is not a valid calculation is an error, not a
+// function call.
 named!(pub function_call<Value>,
-       do_parse!(name: name >> args: call_args >>
+       do_parse!(not!(tag!("calc(")) >>
+                 name: name >> args: call_args >>
                  (Value::Call(name, args))));
 
 fn is_ext_str_char(c: u8) -> bool {
@@ -687,10 +1038,8 @@ named!(unquoted_literal_part<Value>,
             |val: String| {
                 if val == "null" {
                     Value::Null
-                } else if let Some((r, g, b)) = name_to_rgb(&val) {
-                    Value::Color(r, g, b, Rational::from_integer(1), Some(val))
                 } else {
-                    Value::Literal(val, Quotes::None)
+                    literal_or_color(val)
                 }
             }));
 
@@ -698,7 +1047,7 @@ named!(unquoted_literal_part_part<String>,
        switch!(take_backslash,
                true => map!(take!(1),
                             |v| format!("\\{}", from_utf8(v).unwrap())) |
-               false => map!(is_not!("\\+*/=;,$(){{}}! \n\t'\"#"),
+               false => map!(is_not!("\\+*/=;,$(){{}}[]! \n\t'\"#"),
                              |v| from_utf8(v).unwrap().to_string())));
 
 named!(take_backslash<bool>,
@@ -748,11 +1097,6 @@ named!(nonempty_sqs_part<Value>,
             |s| Value::Literal(unescape(from_utf8(s).unwrap()),
                                Quotes::Single)));
 
-fn decimals_to_rational(d: &[u8]) -> Rational {
-    Rational::new(from_utf8(d).unwrap().parse().unwrap(),
-                  10_isize.pow(d.len() as u32))
-}
-
 named!(hexchar, recognize!(one_of!("0123456789ABCDEFabcdef")));
 
 named!(hexchar2,
@@ -0,0 +1,225 @@
+//! Arithmetic on sass numbers.
+//!
+//! A sass number is a `BigRational`, so arithmetic on numbers never
+//! overflows.  Results of arithmetic are rounded to `MAX_DECIMALS`
+//! decimals, so numbers do not grow without bound in loops.
+//! Color channels are `Rational` with `isize` numerator and
+//! denominator.  The plain arithmetic operators on those panic when
+//! an intermediate value does not fit, so the color functions use the
+//! operations of `SafeArith` instead.  They are exact when the result
+//! fits, and otherwise calculate the exact result with big integers
+//! and round it to the closest `Rational` that fits.
+use error::Error;
+use num_bigint::BigInt;
+use num_rational::{BigRational, Rational};
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, One,
+                 Signed, ToPrimitive, Zero, pow};
+
+/// Arithmetic that rounds instead of overflowing.
+pub trait SafeArith: Sized {
+    fn safe_add(&self, rhs: &Self) -> Self;
+    fn safe_sub(&self, rhs: &Self) -> Self;
+    fn safe_mul(&self, rhs: &Self) -> Self;
+    /// Divide by `rhs`.
+    ///
+    /// Division by zero is an error.
+    fn safe_div(&self, rhs: &Self) -> Result<Self, Error>;
+}
+
+impl SafeArith for Rational {
+    fn safe_add(&self, rhs: &Rational) -> Rational {
+        self.checked_add(rhs)
+            .unwrap_or_else(|| from_big(&(big(self) + big(rhs))))
+    }
+    fn safe_sub(&self, rhs: &Rational) -> Rational {
+        self.checked_sub(rhs)
+            .unwrap_or_else(|| from_big(&(big(self) - big(rhs))))
+    }
+    fn safe_mul(&self, rhs: &Rational) -> Rational {
+        self.checked_mul(rhs)
+            .unwrap_or_else(|| from_big(&(big(self) * big(rhs))))
+    }
+    fn safe_div(&self, rhs: &Rational) -> Result<Rational, Error> {
+        if rhs.is_zero() {
+            return Err(Error::BadValue("Division by zero.".into()));
+        }
+        Ok(self.checked_div(rhs)
+               .unwrap_or_else(|| from_big(&(big(self) / big(rhs)))))
+    }
+}
+
+/// Parse a decimal number, given the digits before and after the
+/// decimal point.
+pub fn parse_decimal(int: &str, frac: &str) -> BigRational {
+    let digits = |s: &str| s.parse().unwrap_or_else(|_| BigInt::zero());
+    let scale = pow(BigInt::from(10), frac.len());
+    BigRational::from_integer(digits(int)) +
+    BigRational::new(digits(frac), scale)
+}
+
+/// The number of decimals kept in the result of arithmetic.
+///
+/// This is far more than the default precision of 10.
+pub const MAX_DECIMALS: usize = 20;
+
+/// Round `value` to at most `MAX_DECIMALS` decimals.
+///
+/// Integers, and fractions with a denominator small enough, are kept
+/// exact.
+pub fn limit(value: BigRational) -> BigRational {
+    let scale = pow(BigInt::from(10), MAX_DECIMALS);
+    if *value.denom() <= scale {
+        value
+    } else {
+        let scale = BigRational::from_integer(scale);
+        (value * &scale).round() / scale
+    }
+}
+
+/// Convert a `Rational` to the closest `f64`.
+pub fn to_f64(r: &Rational) -> f64 {
+    *r.numer() as f64 / *r.denom() as f64
+}
+
+/// Convert a `f64` to the closest `Rational`.
+///
+/// Returns `None` for infinite and NaN values.
+pub fn from_f64(f: f64) -> Option<Rational> {
+    BigRational::from_float(f).map(|r| from_big(&r))
+}
+
+/// Convert a sass number to the closest `f64`.
+///
+/// Numbers too large for a `f64` are infinite.
+pub fn big_to_f64(r: &BigRational) -> f64 {
+    // Keep the 64 most significant bits of numerator and denominator.
+    let shift = |v: &BigInt| v.bits().saturating_sub(64);
+    let (sn, sd) = (shift(r.numer()), shift(r.denom()));
+    let n = (r.numer().clone() >> sn).to_f64().unwrap_or(0.);
+    let d = (r.denom().clone() >> sd).to_f64().unwrap_or(1.);
+    n / d * 2f64.powi(sn as i32 - sd as i32)
+}
+
+/// Convert a `Rational` to a sass number.
+pub fn big(r: &Rational) -> BigRational {
+    BigRational::new((*r.numer()).into(), (*r.denom()).into())
+}
+
+/// Round `value` to the closest `Rational`.
+///
+/// Values too large to represent are saturated to the largest
+/// representable value.  Otherwise, the result is the last
+/// convergent of the continued fraction of `value` that fits.
+pub fn from_big(value: &BigRational) -> Rational {
+    if let (Some(n), Some(d)) = (value.numer().to_isize(),
+                                 value.denom().to_isize()) {
+        return Rational::new(n, d);
+    }
+    let max = BigInt::from(isize::MAX);
+    let result = if value.abs() >= BigRational::from_integer(max.clone()) {
+        Rational::from_integer(isize::MAX)
+    } else {
+        let (mut h0, mut h1) = (BigInt::zero(), BigInt::one());
+        let (mut k0, mut k1) = (BigInt::one(), BigInt::zero());
+        let mut x = value.abs();
+        loop {
+            let a = x.trunc();
+            let h2 = a.numer() * &h1 + &h0;
+            let k2 = a.numer() * &k1 + &k0;
+            if h2 > max || k2 > max {
+                break;
+            }
+            h0 = ::std::mem::replace(&mut h1, h2);
+            k0 = ::std::mem::replace(&mut k1, k2);
+            let rest = x - a;
+            if rest.is_zero() {
+                break;
+            }
+            x = rest.recip();
+        }
+        Rational::new(h1.to_isize().unwrap(), k1.to_isize().unwrap())
+    };
+    if value.is_negative() { -result } else { result }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limit_keeps_short_fractions() {
+        let third = BigRational::new(BigInt::from(1), BigInt::from(3));
+        assert_eq!(limit(third.clone()), third);
+    }
+
+    #[test]
+    fn limit_rounds_long_fractions() {
+        let scale = pow(BigInt::from(10), MAX_DECIMALS);
+        let third = BigRational::new(BigInt::from(1), BigInt::from(3));
+        let tiny = BigRational::new(BigInt::from(1), scale.clone());
+        assert_eq!(limit(third * &tiny),
+                   BigRational::new(BigInt::from(0), scale));
+    }
+
+    #[test]
+    fn mul_overflow_rounds() {
+        let a = Rational::new(1, 3) * Rational::from_integer(100);
+        let b = Rational::new(11_111, 10_000);
+        let mut v = a;
+        for _ in 0..10 {
+            v = v.safe_mul(&b);
+        }
+        let f = *v.numer() as f64 / *v.denom() as f64;
+        assert!((f - 95.5895069).abs() < 1e-5, "{}", f);
+    }
+
+    #[test]
+    fn add_overflow_rounds() {
+        let a = Rational::new(1, isize::MAX - 1);
+        let b = Rational::new(1, isize::MAX - 2);
+        let sum = a.safe_add(&b);
+        let f = *sum.numer() as f64 / *sum.denom() as f64;
+        assert!((f - 2.0 / isize::MAX as f64).abs() < 1e-30, "{}", f);
+    }
+
+    #[test]
+    fn too_large_saturates() {
+        let a = Rational::from_integer(isize::MAX / 2);
+        assert_eq!(a.safe_mul(&Rational::from_integer(-3)),
+                   Rational::from_integer(-isize::MAX));
+    }
+
+    #[test]
+    fn float_roundtrip() {
+        assert_eq!(from_f64(2.25), Some(Rational::new(9, 4)));
+        let pi = from_f64(::std::f64::consts::PI).unwrap();
+        assert_eq!(to_f64(&pi), ::std::f64::consts::PI);
+        assert_eq!(from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn divide_by_zero() {
+        assert!(Rational::one().safe_div(&Rational::zero()).is_err());
+    }
+
+    #[test]
+    fn big_float() {
+        let int = |s: &str| BigRational::from_integer(s.parse().unwrap());
+        assert_eq!(big_to_f64(&int("100000000000000000000000000000")),
+                   1e29);
+        assert_eq!(big_to_f64(&(int("-1") / int("3"))), -1. / 3.);
+        assert_eq!(big_to_f64(&int(&format!("1{:0<400}", ""))),
+                   f64::INFINITY);
+    }
+
+    #[test]
+    fn parse_long_decimal() {
+        assert_eq!(parse_decimal("00000000000000000000012",
+                                 "500000000000000000000000"),
+                   big(&Rational::new(25, 2)));
+        assert_eq!(parse_decimal("99999999999999999999", ""),
+                   BigRational::from_integer("99999999999999999999"
+                                                 .parse()
+                                                 .unwrap()));
+    }
+}
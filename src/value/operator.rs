@@ -1,7 +1,12 @@
-use num_rational::Rational;
+use error::Error;
+use num_bigint::BigInt;
+use num_rational::{BigRational, Rational};
+use num_traits::{Signed, Zero, pow};
+use std::cmp::Ordering;
 use std::fmt;
-use unit::Unit;
-use value::{Quotes, Value};
+use unit::UnitSet;
+use output_style::Format;
+use value::{Quotes, SafeArith, Value, from_big, limit};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Operator {
@@ -17,39 +22,79 @@ pub enum Operator {
     Plus,
     Minus,
     Multiply,
+    Modulo,
 
     Not,
 }
 
 impl Operator {
-    pub fn eval(&self, a: Value, b: Value) -> Value {
-        match *self {
+    /// Evaluate this operator on `a` and `b`.
+    ///
+    /// Numbers are compared, and written when concatenated to
+    /// strings, with `precision` decimals.
+    pub fn eval(&self,
+                a: Value,
+                b: Value,
+                precision: usize)
+                -> Result<Value, Error> {
+        let format = Format::with_precision(precision);
+        if self.is_arithmetic() && (is_calc(&a) || is_calc(&b)) {
+            // A calculation can only be concatenated with a string.
+            match (self, &a, &b) {
+                (&Operator::Plus, &Value::Literal(..), _) |
+                (&Operator::Plus, _, &Value::Literal(..)) => (),
+                _ => {
+                    return Err(undefined_operation(&a,
+                                                   &self.to_string(),
+                                                   &b))
+                }
+            }
+        }
+        Ok(match *self {
             Operator::And => Value::bool(a.is_true() && b.is_true()),
             Operator::Or => if a.is_true() { a } else { b },
-            Operator::Equal => Value::bool(equal_values(&a, &b)),
-            Operator::NotEqual => Value::bool(!equal_values(&a, &b)),
-            Operator::Greater => Value::bool(a > b),
-            Operator::GreaterE => Value::bool(a >= b),
-            Operator::Lesser => Value::bool(a < b),
-            Operator::LesserE => Value::bool(a <= b),
+            Operator::Equal => Value::bool(equal_values(&a, &b, precision)),
+            Operator::NotEqual => Value::bool(!equal_values(&a, &b, precision)),
+            Operator::Greater => {
+                Value::bool(compare(&a, &b)? == Some(Ordering::Greater))
+            }
+            Operator::GreaterE => {
+                Value::bool(compare(&a, &b)?.map(Ordering::is_ge) ==
+                            Some(true))
+            }
+            Operator::Lesser => {
+                Value::bool(compare(&a, &b)? == Some(Ordering::Less))
+            }
+            Operator::LesserE => {
+                Value::bool(compare(&a, &b)?.map(Ordering::is_le) ==
+                            Some(true))
+            }
             Operator::Plus => {
                 match (a, b) {
-                    (Value::Color(r, g, b, a, _),
-                     Value::Numeric(n, Unit::None, ..)) => {
-                        Value::rgba(r + n, g + n, b + n, a)
+                    (Value::Color(r, g, b, a, _), Value::Numeric(n, u, ..))
+                        if u.is_none() => {
+                        let n = from_big(&n);
+                        Value::rgba(r.safe_add(&n),
+                                    g.safe_add(&n),
+                                    b.safe_add(&n),
+                                    a)
                     }
                     (Value::Color(ar, ag, ab, aa, _),
                      Value::Color(br, bg, bb, ba, _)) => {
                         // TODO Sum or average the alpha?
-                        Value::rgba(ar + br, ag + bg, ab + bb, aa + ba)
+                        Value::rgba(ar.safe_add(&br),
+                                    ag.safe_add(&bg),
+                                    ab.safe_add(&bb),
+                                    aa.safe_add(&ba))
                     }
                     (Value::Numeric(a, au, ..), Value::Numeric(b, bu, ..)) => {
-                        if au == bu || bu == Unit::None {
-                            Value::Numeric(a + b, au, false, true)
-                        } else if au == Unit::None {
-                            Value::Numeric(a + b, bu, false, true)
+                        if bu.is_none() {
+                            Value::Numeric(limit(a + b), au, false, true)
+                        } else if au.is_none() {
+                            Value::Numeric(limit(a + b), bu, false, true)
                         } else {
-                            Value::Literal(format!("{}{}", a, b), Quotes::None)
+                            let f = scale(&a, &au, &b, &bu)?;
+                            Value::Numeric(limit(a + b * f), au, false, true)
                         }
                     }
                     (Value::Literal(a, Quotes::None), Value::Literal(b, _)) => {
@@ -59,10 +104,10 @@ impl Operator {
                         Value::Literal(format!("{}{}", a, b), Quotes::Double)
                     }
                     (Value::Literal(a, q), b) => {
-                        Value::Literal(format!("{}{}", a, b), q)
+                        Value::Literal(format!("{}{}", a, b.format(format)), q)
                     }
                     (a, Value::Literal(b, q)) => {
-                        Value::Literal(format!("{}{}", a, b), q)
+                        Value::Literal(format!("{}{}", a.format(format), b), q)
                     }
                     (a, b) => {
                         Value::BinOp(Box::new(a), Operator::Plus, Box::new(b))
@@ -72,23 +117,38 @@ impl Operator {
             Operator::Minus => {
                 match (&a, &b) {
                     (&Value::Color(ref r, ref g, ref b, ref a, _),
-                     &Value::Numeric(ref n, Unit::None, ..)) => {
-                        Value::rgba(r - n, g - n, b - n, *a)
+                     &Value::Numeric(ref n, ref u, ..)) if u.is_none() => {
+                        let n = from_big(n);
+                        Value::rgba(r.safe_sub(&n),
+                                    g.safe_sub(&n),
+                                    b.safe_sub(&n),
+                                    *a)
                     }
                     (&Value::Color(ref ar, ref ag, ref ab, ref aa, _),
                      &Value::Color(ref br, ref bg, ref bb, ref ba, _)) => {
-                        Value::rgba(ar - br, ag - bg, ab - bb, avg(aa, ba))
+                        Value::rgba(ar.safe_sub(br),
+                                    ag.safe_sub(bg),
+                                    ab.safe_sub(bb),
+                                    avg(aa, ba))
                     }
                     (&Value::Numeric(ref av, ref au, ..),
                      &Value::Numeric(ref bv, ref bu, ..)) => {
-                        if au == bu || bu == &Unit::None {
-                            Value::Numeric(av - bv, au.clone(), false, true)
-                        } else if au == &Unit::None {
-                            Value::Numeric(av - bv, bu.clone(), false, true)
+                        if bu.is_none() {
+                            Value::Numeric(limit(av - bv),
+                                           au.clone(),
+                                           false,
+                                           true)
+                        } else if au.is_none() {
+                            Value::Numeric(limit(av - bv),
+                                           bu.clone(),
+                                           false,
+                                           true)
                         } else {
-                            Value::BinOp(Box::new(a.clone()),
-                                         Operator::Minus,
-                                         Box::new(b.clone()))
+                            let f = scale(av, au, bv, bu)?;
+                            Value::Numeric(limit(av - bv * f),
+                                           au.clone(),
+                                           false,
+                                           true)
                         }
                     }
                     _ => {
@@ -101,26 +161,123 @@ impl Operator {
             Operator::Multiply => {
                 if let (&Value::Numeric(ref a, ref au, ..),
                         &Value::Numeric(ref b, ref bu, ..)) = (&a, &b) {
-                    if bu == &Unit::None {
-                        Value::Numeric(a * b, au.clone(), false, true)
-                    } else if au == &Unit::None {
-                        Value::Numeric(a * b, bu.clone(), false, true)
-                    } else {
-                        Value::Literal(format!("{}*{}", a, b), Quotes::None)
-                    }
+                    let (unit, factor) = au.mul(bu);
+                    Value::Numeric(limit(a * b * factor), unit, false, true)
                 } else {
-                    Value::Literal(format!("{}*{}", a, b), Quotes::None)
+                    Value::Literal(format!("{}*{}",
+                                           a.format(format),
+                                           b.format(format)),
+                                   Quotes::None)
+                }
+            }
+            Operator::Modulo => {
+                match (&a, &b) {
+                    (&Value::Numeric(ref av, ref au, ..),
+                     &Value::Numeric(ref bv, ref bu, ..)) => {
+                        let (bv, unit) = if bu.is_none() {
+                            (bv.clone(), au.clone())
+                        } else if au.is_none() {
+                            (bv.clone(), bu.clone())
+                        } else {
+                            (bv * scale(av, au, bv, bu)?, au.clone())
+                        };
+                        if bv.is_zero() {
+                            return Err(Error::BadValue(format!(
+                                "{} % {} is undefined (modulo by zero).",
+                                a,
+                                b)));
+                        }
+                        // The result has the same sign as the divisor.
+                        let q = (av / &bv).floor();
+                        Value::Numeric(limit(av - bv * q), unit, false, true)
+                    }
+                    _ => {
+                        Value::Literal(format!("{}%{}",
+                                               a.format(format),
+                                               b.format(format)),
+                                       Quotes::None)
+                    }
                 }
             }
             Operator::Not => panic!("not is a unary operator only"),
-        }
+        })
         // Fallback, might be needed later:
         // Value::BinOp(Box::new(a), self.clone(), Box::new(b))
     }
+
+    fn is_arithmetic(&self) -> bool {
+        match *self {
+            Operator::Plus |
+            Operator::Minus |
+            Operator::Multiply |
+            Operator::Modulo => true,
+            _ => false,
+        }
+    }
+}
+
+/// True if `v` is a calculation that could not be simplified to a
+/// number, on which arithmetic is not allowed.
+pub fn is_calc(v: &Value) -> bool {
+    match *v {
+        Value::Calc(..) => true,
+        _ => false,
+    }
+}
+
+/// The error for an operation that is not defined for its operands.
+pub fn undefined_operation(a: &Value, op: &str, b: &Value) -> Error {
+    Error::BadValue(format!("Undefined operation \"{} {} {}\".", a, op, b))
+}
+
+/// Get the factor to convert the number `b` to the unit of `a`.
+///
+/// It is an error if the units are not compatible.
+fn scale(a: &BigRational,
+         au: &UnitSet,
+         b: &BigRational,
+         bu: &UnitSet)
+         -> Result<BigRational, Error> {
+    bu.scale_to(au).ok_or_else(|| {
+        Error::BadValue(format!("{} and {} have incompatible units.",
+                                Value::Numeric(a.clone(),
+                                               au.clone(),
+                                               false,
+                                               true),
+                                Value::Numeric(b.clone(),
+                                               bu.clone(),
+                                               false,
+                                               true)))
+    })
+}
+
+/// True if `a` and `b` are equal with `precision` decimals.
+fn fuzzy_eq(a: &BigRational, b: &BigRational, precision: usize) -> bool {
+    let epsilon = pow(BigInt::from(10), precision + 1);
+    a == b || (a - b).abs() < BigRational::new(BigInt::from(1), epsilon)
+}
+
+/// Compare two values for the relational operators.
+///
+/// Numbers are compared after converting to the same unit, a
+/// unitless number can be compared to any number.
+/// Other values are not ordered.
+fn compare(a: &Value, b: &Value) -> Result<Option<Ordering>, Error> {
+    match (a, b) {
+        (&Value::Numeric(ref a, ref au, ..),
+         &Value::Numeric(ref b, ref bu, ..)) => {
+            if au.is_none() || bu.is_none() {
+                Ok(a.partial_cmp(b))
+            } else {
+                Ok(a.partial_cmp(&(b * scale(a, au, b, bu)?)))
+            }
+        }
+        _ => Ok(None),
+    }
 }
 
 fn avg(a: &Rational, b: &Rational) -> Rational {
-    (a + b) * Rational::new(1, 2)
+    a.safe_add(b).safe_mul(&Rational::new(1, 2))
 }
 
 impl fmt::Display for Operator {
@@ -139,6 +296,7 @@ impl fmt::Display for Operator {
                    Operator::Plus => "+",
                    Operator::Minus => "-",
                    Operator::Multiply => "*",
+                   Operator::Modulo => "%",
                    Operator::Not => "not",
                })
     }
@@ -148,12 +306,21 @@ impl fmt::Display for Operator {
 ///
 /// Strings with equal content are considered equal, even if they have
 /// different quoting.
+/// Numbers are equal if they are equal when converted to the same
+/// unit.  Numbers with incompatible units, or with a unit and
+/// unitless, are not equal.
 ///
 /// Make this a separate function, so the rust == operator is still
 /// the strict derived version, for unit tests etc.
-fn equal_values(a: &Value, b: &Value) -> bool {
+fn equal_values(a: &Value, b: &Value, precision: usize) -> bool {
     match (a, b) {
         (&Value::Literal(ref a, _), &Value::Literal(ref b, _)) => a == b,
+        (&Value::Numeric(ref a, ref au, ..),
+         &Value::Numeric(ref b, ref bu, ..)) => {
+            bu.scale_to(au)
+                .map(|f| fuzzy_eq(a, &(b * f), precision))
+                .unwrap_or(false)
+        }
         (ref a, ref b) => a == b,
     }
 }
@@ -4,7 +4,7 @@ use nom::IResult::*;
 use num_rational::Rational;
 use num_traits::{One, Zero};
 use std::str::from_utf8;
-use unit::Unit;
+use unit::UnitSet;
 use variablescope::GlobalScope;
 
 mod parser_operations;
@@ -23,7 +23,10 @@ fn simple_number_neg() {
 #[test]
 fn simple_number_pos() {
     check_expr("+4;",
-               Value::Numeric(Rational::new(4, 1), Unit::None, true, false))
+               Value::Numeric(big(&Rational::new(4, 1)),
+                              UnitSet::scalar(),
+                              true,
+                              false))
 }
 
 #[test]
@@ -41,7 +44,10 @@ fn simple_number_onlydec_neg() {
 #[test]
 fn simple_number_onlydec_pos() {
     check_expr("+.34;",
-               Value::Numeric(Rational::new(34, 100), Unit::None, true, false))
+               Value::Numeric(big(&Rational::new(34, 100)),
+                              UnitSet::scalar(),
+                              true,
+                              false))
 }
 #[test]
 fn number_and_interpolation_makes_space_list() {
@@ -49,11 +55,15 @@ fn number_and_interpolation_makes_space_list() {
                Value::List(vec![number(12, 1),
                                 Value::Interpolation(
                                     Box::new(number(3, 1)))],
-                           ListSeparator::Space))
+                           ListSeparator::Space,
+                           false))
 }
 
 fn number(nom: isize, denom: isize) -> Value {
-    Value::Numeric(Rational::new(nom, denom), Unit::None, false, false)
+    Value::Numeric(big(&Rational::new(nom, denom)),
+                   UnitSet::scalar(),
+                   false,
+                   false)
 }
 
 #[test]
@@ -67,7 +77,8 @@ fn strings_misc_quotes() {
                Value::List(vec![Value::Literal("foo".into(), Quotes::None),
                                 Value::Literal("bar".into(), Quotes::Double),
                                 Value::Literal("baz".into(), Quotes::Single)],
-                           ListSeparator::Space))
+                           ListSeparator::Space,
+                           false))
 }
 
 #[test]
@@ -76,7 +87,8 @@ fn strings_escaped_quotes() {
                Value::List(vec![Value::Literal("b'a\"r".into(), Quotes::Double),
                                 Value::Literal("b'a\"z".into(), Quotes::Single)
                                 ],
-                           ListSeparator::Space))
+                           ListSeparator::Space,
+                           false))
 }
 
 #[test]
@@ -94,6 +106,11 @@ fn simple_value_variable() {
     check_expr("$red;", Value::Variable("red".into()))
 }
 
+#[test]
+fn module_variable() {
+    check_expr("ns.$red;", Value::Variable("ns.red".into()))
+}
+
 #[test]
 fn paren_literal() {
     check_expr("(rad);",
@@ -107,7 +124,8 @@ fn paren_multi() {
                Value::Paren(Box::new(Value::List(
                    vec![Value::Literal("rod".into(), Quotes::None),
                         Value::Literal("bloe".into(), Quotes::None)],
-                   ListSeparator::Space))))
+                   ListSeparator::Space,
+                   false))))
 }
 
 #[test]
@@ -116,7 +134,8 @@ fn paren_multi_comma() {
                Value::Paren(Box::new(Value::List(
                    vec![Value::Literal("rod".into(), Quotes::None),
                         Value::Literal("bloe".into(), Quotes::None)],
-                   ListSeparator::Comma))))
+                   ListSeparator::Comma,
+                   false))))
 }
 
 #[test]
@@ -124,7 +143,8 @@ fn multi_comma() {
     check_expr("rod, bloe;",
                Value::List(vec![Value::Literal("rod".into(), Quotes::None),
                                 Value::Literal("bloe".into(), Quotes::None)],
-                           ListSeparator::Comma))
+                           ListSeparator::Comma,
+                           false))
 }
 
 #[test]
@@ -133,7 +153,8 @@ fn paren_multi_comma_trailing() {
                Value::Paren(Box::new(Value::List(
                    vec![Value::Literal("rod".into(), Quotes::None),
                         Value::Literal("bloe".into(), Quotes::None)],
-                   ListSeparator::Comma))))
+                   ListSeparator::Comma,
+                   false))))
 }
 
 #[test]
@@ -141,7 +162,8 @@ fn multi_comma_trailing() {
     check_expr("rod, bloe, ;",
                Value::List(vec![Value::Literal("rod".into(), Quotes::None),
                                 Value::Literal("bloe".into(), Quotes::None)],
-                           ListSeparator::Comma))
+                           ListSeparator::Comma,
+                           false))
 }
 
 #[test]
@@ -156,28 +178,30 @@ fn call_one_arg() {
                            CallArgs::new(vec![(None, Value::scalar(17))])))
 }
 
+#[test]
+fn module_call() {
+    check_expr("math.div(17);",
+               Value::Call("math.div".to_string(),
+                           CallArgs::new(vec![(None, Value::scalar(17))])))
+}
+
 #[test]
 fn multi_expression() {
     check_expr("15/10 2 3;",
                Value::List(vec![Value::Div(Box::new(Value::scalar(15)),
-                                           Box::new(Value::scalar(10)),
-                                           false,
-                                           false),
+                                           Box::new(Value::scalar(10))),
                                 Value::scalar(2),
                                 Value::scalar(3)],
-                           ListSeparator::Space))
+                           ListSeparator::Space,
+                           false))
 }
 
 #[test]
 fn double_div() {
     check_expr("15/5/3;",
                Value::Div(Box::new(Value::Div(Box::new(Value::scalar(15)),
-                                              Box::new(Value::scalar(5)),
-                                              false,
-                                              false)),
-                          Box::new(Value::scalar(3)),
-                          false,
-                          false))
+                                              Box::new(Value::scalar(5)))),
+                          Box::new(Value::scalar(3))))
 }
 
 #[test]
@@ -200,6 +224,26 @@ fn color_long() {
                             Some("#AaBbCc".into())))
 }
 
+#[test]
+fn color_short_alpha() {
+    check_expr("#AbC8;",
+               Value::Color(Rational::new(170, 1),
+                            Rational::new(187, 1),
+                            Rational::new(204, 1),
+                            Rational::new(8, 15),
+                            Some("#AbC8".into())))
+}
+
+#[test]
+fn color_long_alpha() {
+    check_expr("#AaBbCc80;",
+               Value::Color(Rational::new(170, 1),
+                            Rational::new(187, 1),
+                            Rational::new(204, 1),
+                            Rational::new(128, 255),
+                            Some("#AaBbCc80".into())))
+}
+
 fn check_expr(expr: &str, value: Value) {
     assert_eq!(value_expression(expr.as_bytes()), Done(&b";"[..], value))
 }
@@ -211,8 +255,8 @@ fn parse_extended_literal() {
     if let &Done(ref rest, ref result) = &t {
         assert_eq!(rest, b";");
         println!("Got {:?}", result);
-        assert_eq!("http://).com/",
-                   format!("{}", result.evaluate(&GlobalScope::new())));
+        let value = result.evaluate(&GlobalScope::new()).unwrap();
+        assert_eq!("http://).com/", format!("{}", value));
     } else {
         assert_eq!(format!("{:?}", t), "Done")
     }
@@ -224,7 +268,7 @@ fn check_eval(expression: &str, expected: &str) {
     let (end, foo) = value_expression(expression.as_bytes()).unwrap();
     println!("Expression is: {:?}", foo);
     assert_eq!(Ok(";"), from_utf8(end));
-    let result = foo.evaluate(&mut scope);
+    let result = foo.evaluate(&mut scope).unwrap();
     println!(" ... evals to: {:?}", result);
     assert_eq!(format!("{}", result),
                expected)
@@ -1,10 +1,14 @@
 //! A scope is something that contains variable values.
 
 use super::SassItem;
+use error::Error;
 use formalargs::{CallArgs, FormalArgs};
 use functions::{SassFunction, get_builtin_function};
+use logger::{self, Log, Logger};
+use output_style::Format;
+use sourcepos::SourcePos;
 use std::collections::BTreeMap;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use value::Value;
 
 /// Variables, functions and mixins are defined in a `Scope`.
@@ -17,11 +21,34 @@ pub trait Scope {
     /// Define a variable with a value.
     ///
     /// The `$` sign is not included in `name`.
-    fn define(&mut self, name: &str, val: &Value);
-    fn define_default(&mut self, name: &str, val: &Value, global: bool);
+    fn define(&mut self, name: &str, val: &Value) -> Result<(), Error>;
+    fn define_default(&mut self,
+                      name: &str,
+                      val: &Value,
+                      global: bool)
+                      -> Result<(), Error>;
+    /// Define variables from a value, as in `@each $key, $value in $map`.
+    ///
+    /// With a single name, the entire value is assigned to it.
+    /// Otherwise the value is destructured, and any name without a
+    /// corresponding item gets a null value.
+    fn define_multi(&mut self,
+                    names: &[String],
+                    val: &Value)
+                    -> Result<(), Error> {
+        if names.len() == 1 {
+            self.define(&names[0], val)
+        } else {
+            let values = val.clone().iter_items();
+            for (i, name) in names.iter().enumerate() {
+                self.define(name, values.get(i).unwrap_or(&Value::Null))?;
+            }
+            Ok(())
+        }
+    }
     /// Define a variable in the global scope that is an ultimate
     /// parent of this scope.
-    fn define_global(&self, name: &str, val: &Value);
+    fn define_global(&self, name: &str, val: &Value) -> Result<(), Error>;
     /// Get the Value for a variable.
     fn get(&self, name: &str) -> Value;
     fn get_global(&self, name: &str) -> Value;
@@ -34,29 +61,79 @@ pub trait Scope {
 
     fn define_function(&mut self, name: &str, func: SassFunction);
     fn get_function(&self, name: &str) -> Option<&SassFunction>;
-    fn call_function(&self, name: &str, args: &CallArgs) -> Option<Value>;
+    /// Call a function defined in this scope.
+    ///
+    /// Returns `Ok(None)` if there is no such function.
+    fn call_function(&self,
+                     name: &str,
+                     args: &CallArgs)
+                     -> Result<Option<Value>, Error>;
+
+    /// Make a module available by a namespace, as by `@use`.
+    ///
+    /// Members of a module with the namespace `*` are available
+    /// without a namespace.
+    /// Only the global scope of a file can use modules, other scopes
+    /// ignores this.
+    fn define_module(&mut self, _namespace: &str, _module: Arc<Module>) {}
+    /// Get a module by its namespace.
+    fn get_module(&self, namespace: &str) -> Option<Arc<Module>>;
+    /// Find the used module providing the mixin `name`, and the name
+    /// of the mixin in that module.
+    fn get_mixin_module(&self, name: &str) -> Option<(Arc<Module>, String)>;
+    /// Forward the members of a module, as by `@forward`.
+    ///
+    /// Only the global scope of a file can forward modules, other
+    /// scopes ignores this.
+    fn forward_module(&mut self, _module: Arc<Module>, _filter: ForwardFilter) {
+    }
 
-    fn eval_body(&mut self, body: &[SassItem]) -> Option<Value>
+    /// Set the number of decimals numbers are compared and written
+    /// with, as given by the `Format` of the output.
+    ///
+    /// Only the global scope holds the precision, other scopes
+    /// ignores this.
+    fn set_precision(&mut self, _precision: usize) {}
+    /// Get the number of decimals numbers are compared and written
+    /// with.
+    fn get_precision(&self) -> usize;
+
+    /// Set the logger of `@debug` and `@warn` messages and
+    /// deprecation warnings.
+    ///
+    /// Only the global scope holds the logger, other scopes ignores
+    /// this.
+    fn set_log(&mut self, _log: Log) {}
+    /// Get the logger of `@debug` and `@warn` messages and
+    /// deprecation warnings.
+    fn get_log(&self) -> &Log;
+
+    /// Evaluate the body of a function.
+    ///
+    /// Returns the value of the first `@return` reached, if any.
+    fn eval_body(&mut self, body: &[SassItem]) -> Result<Option<Value>, Error>
         where Self: Sized
     {
         for b in body {
             let result = match *b {
-                SassItem::IfStatement(ref cond, ref do_if, ref do_else) => {
-                    if cond.evaluate(self).is_true() {
-                        self.eval_body(do_if)
+                SassItem::IfStatement(ref cond,
+                                      ref do_if,
+                                      ref do_else,
+                                      ref pos) => {
+                    let cond = cond.evaluate(self).map_err(|e| e.at(pos))?;
+                    if cond.is_true() {
+                        self.eval_body(do_if)?
                     } else {
-                        self.eval_body(do_else)
+                        self.eval_body(do_else)?
                     }
                 }
-                SassItem::Each(ref name, ref values, ref body) => {
-                    let values = match values.evaluate(self) {
-                        Value::List(v, _) => v,
-                        v => vec![v],
-                    };
-                    for value in values {
-                        self.define(name, &value);
-                        if let Some(r) = self.eval_body(body) {
-                            return Some(r);
+                SassItem::Each(ref names, ref values, ref body, ref pos) => {
+                    let values =
+                        values.evaluate(self).map_err(|e| e.at(pos))?;
+                    for value in values.iter_items() {
+                        self.define_multi(names, &value)?;
+                        if let Some(r) = self.eval_body(body)? {
+                            return Ok(Some(r));
                         }
                     }
                     None
@@ -68,13 +145,13 @@ pub trait Scope {
                     inclusive,
                     ref body,
                 } => {
-                    let from = from.evaluate(self).integer_value().unwrap();
-                    let to = to.evaluate(self).integer_value().unwrap();
+                    let from = from.evaluate(self)?.integer_value()?;
+                    let to = to.evaluate(self)?.integer_value()?;
                     let to = if inclusive { to + 1 } else { to };
                     for value in from..to {
-                        self.define(name, &Value::scalar(value));
-                        if let Some(r) = self.eval_body(body) {
-                            return Some(r);
+                        self.define(name, &Value::scalar(value))?;
+                        if let Some(r) = self.eval_body(body)? {
+                            return Ok(Some(r));
                         }
                     }
                     None
@@ -84,63 +161,91 @@ pub trait Scope {
                     ref val,
                     default,
                     global,
+                    ref pos,
                 } => {
-                    if default {
-                        self.define_default(name, val, global);
+                    let defined = if default {
+                        self.define_default(name, val, global)
                     } else if global {
-                        self.define_global(name, val);
+                        self.define_global(name, val)
                     } else {
-                        self.define(name, val);
-                    }
+                        self.define(name, val)
+                    };
+                    defined.map_err(|e| e.at(pos))?;
                     None
                 }
-                SassItem::Return(ref v) => Some(v.evaluate(self)),
+                SassItem::Return(ref v) => {
+                    // A slash in a returned value is a division.
+                    Some(v.do_evaluate(self, true)?)
+                }
                 SassItem::While(ref cond, ref body) => {
                     let mut scope = ScopeImpl::sub(self);
-                    while cond.evaluate(&scope).is_true() {
-                        if let Some(r) = scope.eval_body(body) {
-                            return Some(r);
+                    while cond.evaluate(&scope)?.is_true() {
+                        if let Some(r) = scope.eval_body(body)? {
+                            return Ok(Some(r));
                         }
                     }
                     None
                 }
-                SassItem::None => None,
-                ref x => {
-                    panic!("Not implemented in fuction: {:?}", x);
+                SassItem::Debug(ref value, ref pos) => {
+                    self.get_log().debug(&value.evaluate(self)?, pos);
+                    None
+                }
+                SassItem::Warn(ref value, ref pos) => {
+                    self.get_log().warn(&value.evaluate(self)?, pos);
+                    None
+                }
+                SassItem::Error(ref value, ref pos) => {
+                    let msg = logger::message(&value.evaluate(self)?);
+                    return Err(Error::eval(&msg, pos));
+                }
+                SassItem::None |
+                SassItem::Comment(_) => None,
+                _ => {
+                    return Err(Error::eval("Functions can only contain \
+                                            variable declarations and \
+                                            control directives.",
+                                           &SourcePos::unknown()));
                 }
             };
             if let Some(result) = result {
-                return Some(result);
+                return Ok(Some(result));
             }
         }
-        None
+        Ok(None)
     }
 }
 
 pub struct ScopeImpl<'a> {
-    parent: &'a Scope,
+    parent: &'a dyn Scope,
     variables: BTreeMap<String, Value>,
     mixins: BTreeMap<String, (FormalArgs, Vec<SassItem>)>,
     functions: BTreeMap<String, SassFunction>,
 }
 
 impl<'a> Scope for ScopeImpl<'a> {
-    fn define(&mut self, name: &str, val: &Value) {
-        let val = val.do_evaluate(self, true);
+    fn define(&mut self, name: &str, val: &Value) -> Result<(), Error> {
+        let val = val.do_evaluate(self, true)?;
         self.variables.insert(name.replace('-', "_"), val);
+        Ok(())
     }
-    fn define_default(&mut self, name: &str, val: &Value, global: bool) {
+    fn define_default(&mut self,
+                      name: &str,
+                      val: &Value,
+                      global: bool)
+                      -> Result<(), Error> {
         if self.get(name) == Value::Null {
             if global {
                 self.define_global(name, val)
             } else {
                 self.define(name, val)
             }
+        } else {
+            Ok(())
         }
     }
-    fn define_global(&self, name: &str, val: &Value) {
-        let val = val.do_evaluate(self, true);
-        self.parent.define_global(name, &val);
+    fn define_global(&self, name: &str, val: &Value) -> Result<(), Error> {
+        let val = val.do_evaluate(self, true)?;
+        self.parent.define_global(name, &val)
     }
     fn get_mixin(&self, name: &str) -> Option<(FormalArgs, Vec<SassItem>)> {
         self.mixins
@@ -175,18 +280,33 @@ impl<'a> Scope for ScopeImpl<'a> {
         }
         self.parent.get_function(&name)
     }
-    fn call_function(&self, name: &str, args: &CallArgs) -> Option<Value> {
+    fn call_function(&self,
+                     name: &str,
+                     args: &CallArgs)
+                     -> Result<Option<Value>, Error> {
         let name = name.replace('-', "_");
         if let Some(f) = self.functions.get(&name).cloned() {
-            return f.call(self, args).ok();
+            return f.call(self, args).map(Some);
         }
-        let a2 = args.xyzzy(self);
+        let a2 = args.xyzzy(self)?;
         self.parent.call_function(&name, &a2)
     }
+    fn get_module(&self, namespace: &str) -> Option<Arc<Module>> {
+        self.parent.get_module(namespace)
+    }
+    fn get_mixin_module(&self, name: &str) -> Option<(Arc<Module>, String)> {
+        self.parent.get_mixin_module(name)
+    }
+    fn get_precision(&self) -> usize {
+        self.parent.get_precision()
+    }
+    fn get_log(&self) -> &Log {
+        self.parent.get_log()
+    }
 }
 
 impl<'a> ScopeImpl<'a> {
-    pub fn sub(parent: &'a Scope) -> Self {
+    pub fn sub(parent: &'a dyn Scope) -> Self {
         ScopeImpl {
             parent: parent,
             variables: BTreeMap::new(),
@@ -205,6 +325,10 @@ pub struct GlobalScope {
     variables: Mutex<BTreeMap<String, Value>>,
     mixins: BTreeMap<String, (FormalArgs, Vec<SassItem>)>,
     functions: BTreeMap<String, SassFunction>,
+    modules: Vec<(String, Arc<Module>)>,
+    forwarded: Vec<(Arc<Module>, ForwardFilter)>,
+    precision: usize,
+    log: Log,
 }
 
 impl GlobalScope {
@@ -214,22 +338,104 @@ impl GlobalScope {
             variables: Mutex::new(BTreeMap::new()),
             mixins: BTreeMap::new(),
             functions: BTreeMap::new(),
+            modules: Vec::new(),
+            forwarded: Vec::new(),
+            precision: Format::default().precision,
+            log: Log::default(),
+        }
+    }
+
+    /// Send the `@debug` and `@warn` messages and deprecation
+    /// warnings of the compilation using this scope to `logger`.
+    ///
+    /// # Example
+    /// ```
+    /// use rsass::{FileContext, GlobalScope, Logger, OutputStyle, SourcePos,
+    ///             parse_scss_data};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Default)]
+    /// struct Collect(Mutex<Vec<String>>);
+    ///
+    /// impl Logger for Collect {
+    ///     fn debug(&self, msg: &str, _pos: &SourcePos) {
+    ///         self.0.lock().unwrap().push(msg.into());
+    ///     }
+    ///     fn warn(&self, _msg: &str, _pos: &SourcePos) {}
+    ///     fn deprecation(&self, _msg: &str) {}
+    /// }
+    ///
+    /// let logger = Arc::new(Collect::default());
+    /// let mut globals = GlobalScope::new();
+    /// globals.set_logger(logger.clone());
+    /// let items = parse_scss_data(b"@debug 1 + 2;\n").unwrap();
+    /// let css = OutputStyle::Normal
+    ///     .write_root(&items, &mut globals, FileContext::new())
+    ///     .unwrap();
+    /// assert_eq!(css, b"");
+    /// assert_eq!(*logger.0.lock().unwrap(), vec!["3".to_string()]);
+    /// ```
+    pub fn set_logger(&mut self, logger: Arc<dyn Logger>) {
+        self.log = Log::new(logger);
+    }
+
+    /// Find a member of a used module.
+    ///
+    /// A `name` with a namespace, as in `ns.name`, is looked up in
+    /// the module of that namespace.  Other names are looked up in
+    /// the modules used without namespace.
+    fn module_member<'a, T, F>(&'a self, name: &str, f: F) -> Option<T>
+        where F: Fn(&'a Arc<Module>, &str) -> Option<T>
+    {
+        match split_namespace(name) {
+            Some((ns, member)) => {
+                self.modules
+                    .iter()
+                    .rev()
+                    .find(|&&(ref n, _)| n == ns)
+                    .and_then(|&(_, ref m)| f(m, member))
+            }
+            None => {
+                self.modules
+                    .iter()
+                    .filter(|&&(ref n, _)| n == "*")
+                    .filter_map(|&(_, ref m)| f(m, name))
+                    .next()
+            }
         }
     }
 }
 
 impl Scope for GlobalScope {
-    fn define(&mut self, name: &str, val: &Value) {
+    fn define(&mut self, name: &str, val: &Value) -> Result<(), Error> {
         self.define_global(name, val)
     }
-    fn define_default(&mut self, name: &str, val: &Value, _global: bool) {
+    fn define_default(&mut self,
+                      name: &str,
+                      val: &Value,
+                      _global: bool)
+                      -> Result<(), Error> {
         if self.get(name) == Value::Null {
             self.define(name, val)
+        } else {
+            Ok(())
         }
     }
-    fn define_global(&self, name: &str, val: &Value) {
-        let val = val.do_evaluate(self, true);
-        self.variables.lock().unwrap().insert(name.replace('-', "_"), val);
+    fn define_global(&self, name: &str, val: &Value) -> Result<(), Error> {
+        let val = val.do_evaluate(self, true)?;
+        let name = name.replace('-', "_");
+        if let Some((ns, member)) = split_namespace(&name) {
+            return match self.get_module(ns) {
+                Some(module) => module.set_variable(member, val),
+                None => {
+                    Err(Error::BadValue(format!("There is no module with \
+                                                 the namespace \"{}\".",
+                                                ns)))
+                }
+            };
+        }
+        self.variables.lock().unwrap().insert(name, val);
+        Ok(())
     }
     fn get_mixin(&self, name: &str) -> Option<(FormalArgs, Vec<SassItem>)> {
         self.mixins.get(&name.replace('-', "_")).cloned()
@@ -239,11 +445,8 @@ impl Scope for GlobalScope {
     }
     fn get_global(&self, name: &str) -> Value {
         let name = name.replace('-', "_");
-        self.variables
-            .lock()
-            .unwrap()
-            .get(&name)
-            .cloned()
+        let value = self.variables.lock().unwrap().get(&name).cloned();
+        value.or_else(|| self.module_member(&name, |m, n| m.get_variable(n)))
             .unwrap_or(Value::Null)
     }
     fn define_mixin(&mut self,
@@ -261,17 +464,246 @@ impl Scope for GlobalScope {
         if let Some(f) = self.functions.get(&name) {
             return Some(f);
         }
-        get_builtin_function(&name)
+        self.module_member(&name, |m, n| m.get_function(n).map(|(f, _)| f))
+            .or_else(|| get_builtin_function(&name))
     }
-    fn call_function(&self, name: &str, args: &CallArgs) -> Option<Value> {
+    fn call_function(&self,
+                     name: &str,
+                     args: &CallArgs)
+                     -> Result<Option<Value>, Error> {
         let name = name.replace('-', "_");
         if let Some(f) = self.functions.get(&name).cloned() {
-            return f.call(self, args).ok();
+            return f.call(self, args).map(Some);
+        }
+        match self.module_member(&name, |m, n| m.get_function(n)) {
+            // Builtin functions need no module scope, and should use
+            // the precision and logger of this compilation.
+            Some((f, _)) if f.is_builtin() => f.call(self, args).map(Some),
+            Some((f, scope)) => f.call(scope, &args.xyzzy(self)?).map(Some),
+            None => Ok(None),
+        }
+    }
+    fn define_module(&mut self, namespace: &str, module: Arc<Module>) {
+        self.modules.push((namespace.replace('-', "_"), module));
+    }
+    fn get_module(&self, namespace: &str) -> Option<Arc<Module>> {
+        let namespace = namespace.replace('-', "_");
+        self.modules
+            .iter()
+            .rev()
+            .find(|&&(ref ns, _)| ns == &namespace)
+            .map(|&(_, ref m)| m.clone())
+    }
+    fn get_mixin_module(&self, name: &str) -> Option<(Arc<Module>, String)> {
+        let name = name.replace('-', "_");
+        self.module_member(&name, |m, n| {
+            m.get_mixin(n).map(|_| (m.clone(), n.to_string()))
+        })
+    }
+    fn forward_module(&mut self, module: Arc<Module>, filter: ForwardFilter) {
+        self.forwarded.push((module, filter));
+    }
+    fn set_precision(&mut self, precision: usize) {
+        self.precision = precision;
+    }
+    fn get_precision(&self) -> usize {
+        self.precision
+    }
+    fn set_log(&mut self, log: Log) {
+        self.log = log;
+    }
+    fn get_log(&self) -> &Log {
+        &self.log
+    }
+}
+
+/// A module, as loaded by `@use` or `@forward`.
+///
+/// The members of a module are the variables, mixins and functions
+/// defined in its global scope, and the members it forwards.
+/// Names starting with `-` or `_` are private to the module.
+pub struct Module {
+    scope: GlobalScope,
+}
+
+impl Module {
+    /// Create a module from the global scope of a loaded file.
+    pub fn new(scope: GlobalScope) -> Self {
+        Module { scope: scope }
+    }
+
+    /// Get the value of a public variable in this module.
+    pub fn get_variable(&self, name: &str) -> Option<Value> {
+        let name = name.replace('-', "_");
+        if is_private(&name) {
+            return None;
+        }
+        let value = self.scope.variables.lock().unwrap().get(&name).cloned();
+        value.or_else(|| self.forwarded(&name, true, |m, n| m.get_variable(n)))
+    }
+
+    /// Set the value of an existing public variable in this module.
+    pub fn set_variable(&self, name: &str, val: Value) -> Result<(), Error> {
+        let name = name.replace('-', "_");
+        if is_private(&name) {
+            return Err(Error::BadValue("Private members can't be accessed \
+                                        from outside their modules."
+                                           .into()));
+        }
+        if let Some(v) = self.scope.variables.lock().unwrap().get_mut(&name) {
+            *v = val;
+            return Ok(());
+        }
+        self.forwarded(&name, true, |m, n| m.set_variable(n, val.clone()).ok())
+            .ok_or_else(|| Error::BadValue("Undefined variable.".into()))
+    }
+
+    /// Get a public mixin of this module, together with the scope it
+    /// should be evaluated in.
+    pub fn get_mixin(&self,
+                     name: &str)
+                     -> Option<(FormalArgs, Vec<SassItem>, &dyn Scope)> {
+        let name = name.replace('-', "_");
+        if is_private(&name) {
+            return None;
+        }
+        match self.scope.mixins.get(&name) {
+            Some(&(ref args, ref body)) => {
+                Some((args.clone(), body.clone(), &self.scope))
+            }
+            None => self.forwarded(&name, false, |m, n| m.get_mixin(n)),
+        }
+    }
+
+    /// Get a public function of this module, together with the
+    /// scope it should be called in.
+    pub fn get_function(&self,
+                        name: &str)
+                        -> Option<(&SassFunction, &dyn Scope)> {
+        let name = name.replace('-', "_");
+        if is_private(&name) {
+            return None;
+        }
+        match self.scope.functions.get(&name) {
+            Some(f) => Some((f, &self.scope)),
+            None => self.forwarded(&name, false, |m, n| m.get_function(n)),
+        }
+    }
+
+    fn forwarded<'a, T, F>(&'a self,
+                           name: &str,
+                           variable: bool,
+                           f: F)
+                           -> Option<T>
+        where F: Fn(&'a Module, &str) -> Option<T>
+    {
+        self.scope
+            .forwarded
+            .iter()
+            .filter_map(|&(ref m, ref filter)| {
+                            filter.inner_name(name, variable)
+                                .and_then(|n| f(m, &n))
+                        })
+            .next()
+    }
+}
+
+/// Which members of a module are forwarded by a `@forward`, and how
+/// they are named.
+///
+/// Names in `show` and `hide` are the names as seen by users of the
+/// forwarding module.  Variable names are written with a `$`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForwardFilter {
+    pub prefix: Option<String>,
+    pub show: Option<Vec<String>>,
+    pub hide: Vec<String>,
+}
+
+impl ForwardFilter {
+    /// Get the name in the forwarded module of a member `name`, if
+    /// that member is forwarded.
+    fn inner_name(&self, name: &str, variable: bool) -> Option<String> {
+        let listed = if variable {
+            format!("${}", name)
+        } else {
+            name.to_string()
+        };
+        if let Some(ref show) = self.show {
+            if !show.contains(&listed) {
+                return None;
+            }
+        }
+        if self.hide.contains(&listed) {
+            return None;
+        }
+        match self.prefix {
+            Some(ref prefix) if name.starts_with(prefix.as_str()) => {
+                Some(name[prefix.len()..].to_string())
+            }
+            Some(_) => None,
+            None => Some(name.to_string()),
         }
-        None
     }
 }
 
+/// The kinds of members a module can have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Member {
+    Variable,
+    Function,
+    Mixin,
+}
+
+/// Check that a `name` with a namespace, as in `ns.name`, is a
+/// public member of the module used with that namespace.
+///
+/// Names without a namespace are not checked.
+pub fn check_member(scope: &dyn Scope,
+                    name: &str,
+                    kind: Member)
+                    -> Result<(), Error> {
+    let (ns, member) = match split_namespace(name) {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    let err = |msg: &str| Err(Error::eval(msg, &SourcePos::unknown()));
+    let module = match scope.get_module(ns) {
+        Some(module) => module,
+        None => {
+            return err(&format!("There is no module with the namespace \
+                                 \"{}\".",
+                                ns))
+        }
+    };
+    let member = member.replace('-', "_");
+    if is_private(&member) {
+        return err("Private members can't be accessed from outside \
+                    their modules.");
+    }
+    match kind {
+        Member::Variable if module.get_variable(&member).is_none() => {
+            err("Undefined variable.")
+        }
+        Member::Function if module.get_function(&member).is_none() => {
+            err("Undefined function.")
+        }
+        Member::Mixin if module.get_mixin(&member).is_none() => {
+            err("Undefined mixin.")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Split `ns.name` into namespace and name.
+fn split_namespace(name: &str) -> Option<(&str, &str)> {
+    name.find('.').map(|i| (&name[..i], &name[i + 1..]))
+}
+
+fn is_private(name: &str) -> bool {
+    name.starts_with('_')
+}
+
 #[cfg(test)]
 pub mod test {
     use std::str::from_utf8;
@@ -350,7 +782,7 @@ pub mod test {
 
     #[test]
     fn double_div_2() {
-        assert_eq!("15 / 3 / 5", do_evaluate(&[], b"15 / 3 / 5;"))
+        assert_eq!("15/3/5", do_evaluate(&[], b"15 / 3 / 5;"))
     }
 
     #[test]
@@ -578,10 +1010,10 @@ pub mod test {
             let val = format!("{};", val);
             let (end, value) = value_expression(val.as_bytes()).unwrap();
             assert_eq!(Ok(";"), from_utf8(end));
-            scope.define(name, &value);
+            scope.define(name, &value).unwrap();
         }
         let (end, foo) = value_expression(expression).unwrap();
         assert_eq!(Ok(";"), from_utf8(end));
-        format!("{}", foo.evaluate(&mut scope))
+        format!("{}", foo.evaluate(&mut scope).unwrap())
     }
 }
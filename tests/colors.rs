@@ -4,7 +4,7 @@
 //! try to implement that functionality without breaking those already
 //! added.
 extern crate rsass;
-use rsass::{OutputStyle, compile_scss};
+use rsass::{Format, OutputStyle, compile_scss};
 
 #[test]
 fn basic_4_0() {
@@ -160,9 +160,216 @@ fn scale_color() {
            color: #c899ff;\n  color: rgba(200, 205, 208, 0.7);\n}\n")
 }
 
+#[test]
+fn whiteness_and_blackness() {
+    check(b"p {\n  color: adjust-color(#102030, $whiteness: 10%);\n  \
+            color: change-color(#f00, $blackness: 50%);\n  \
+            color: scale-color(#808080, $whiteness: 50%);\n  \
+            color: change-color(#f00, $hue: 120, $whiteness: 20%);\n}\n",
+          "p {\n  color: #2a2d30;\n  color: maroon;\n  color: #999999;\n  \
+           color: #33ff33;\n}\n")
+}
+
+#[test]
+fn grayscale_and_alpha() {
+    check(b"p {\n  color: grayscale(#f00);\n  filter: grayscale(50%);\n  \
+            a: alpha(rgba(0, 0, 0, 0.3));\n  b: opacity(#fff);\n  \
+            filter: opacity(50%);\n  filter: alpha(opacity=50);\n}\n",
+          "p {\n  color: gray;\n  filter: grayscale(50%);\n  a: 0.3;\n  \
+           b: 1;\n  filter: opacity(50%);\n  filter: alpha(opacity=50);\n}\n")
+}
+
+#[test]
+fn adjust_color_out_of_range() {
+    check_err(b"p {\n  color: adjust-color(red, $red: 300);\n}\n",
+              "$red: Expected 300 to be within -255 and 255.\n")
+}
+
+#[test]
+fn change_color_out_of_range() {
+    check_err(b"p {\n  color: change-color(red, $lightness: 120%);\n}\n",
+              "$lightness: Expected 120% to be within 0% and 100%.\n")
+}
+
+#[test]
+fn scale_color_needs_percent() {
+    check_err(b"p {\n  color: scale-color(red, $alpha: 0.5);\n}\n",
+              "$alpha: Expected 0.5 to have unit \"%\".\n")
+}
+
+#[test]
+fn rgb_and_hsl_mixed() {
+    check_err(b"p {\n  color: adjust-color(red, $blue: 10, $hue: 10);\n}\n",
+              "RGB parameters may not be passed along with HSL parameters.\n")
+}
+
+#[test]
+fn hsl_and_hwb_mixed() {
+    check_err(b"p {\n  \
+                color: change-color(red, $lightness: 1%, $blackness: 2%);\n}\n",
+              "HSL parameters may not be passed along with HWB parameters.\n")
+}
+
+#[test]
+fn color_level_4_spaces() {
+    check(b"p {\n  a: lab(50% 40 59.5);\n  b: lch(50% 30 120deg / 0.5);\n  \
+            c: oklab(60% 0.1 -0.1);\n  d: oklch(0.7 0.1 200);\n  \
+            e: color(display-p3 1 0.5 0);\n}\n",
+          "p {\n  a: lab(50% 40 59.5);\n  b: lch(50% 30 120deg / 0.5);\n  \
+           c: oklab(60% 0.1 -0.1);\n  d: oklch(70% 0.1 200deg);\n  \
+           e: color(display-p3 1 0.5 0);\n}\n")
+}
+
+#[test]
+fn hwb() {
+    check(b"p {\n  a: hwb(120 20% 30%);\n  b: hwb(0.5turn 0% 0% / 0.2);\n  \
+            c: hwb(0, 60%, 60%);\n}\n",
+          "p {\n  a: #33b333;\n  b: rgba(0, 255, 255, 0.2);\n  c: gray;\n}\n")
+}
+
+#[test]
+fn color_level_4_adjust_in_space() {
+    check(b"p {\n  a: lighten(oklch(70% 0.1 200), 10%);\n  \
+            b: darken(lab(50% 40 59.5), 10%);\n  \
+            c: fade-out(oklch(70% 0.1 200), 0.25);\n  \
+            d: alpha(color(display-p3 1 0 0 / 40%));\n  \
+            e: mix(oklch(70% 0.1 200), oklch(50% 0.1 340));\n}\n",
+          "p {\n  a: oklch(80% 0.1 200deg);\n  b: lab(40% 40 59.5);\n  \
+           c: oklch(70% 0.1 200deg / 0.75);\n  d: 0.4;\n  \
+           e: oklch(60% 0.1 270deg);\n}\n")
+}
+
+#[test]
+fn color_level_4_legacy_function() {
+    check(b"p {\n  a: invert(oklab(100% 0 0));\n  \
+            b: ie-hex-str(oklch(100% 0 0));\n}\n",
+          "p {\n  a: oklab(0% 0 0);\n  b: #FFFFFFFF;\n}\n")
+}
+
+#[test]
+fn color_level_4_unknown_channels() {
+    check(b"p {\n  a: oklch(var(--l) 0.1 200);\n  b: color(srgb 1 0 0);\n  \
+            c: hwb(var(--h) 10% 20%);\n}\n",
+          "p {\n  a: oklch(var(--l) 0.1 200);\n  b: color(srgb 1 0 0);\n  \
+           c: hwb(var(--h) 10% 20%);\n}\n")
+}
+
+#[test]
+fn color_level_4_adjust_lightness() {
+    check(b"p {\n  a: adjust-color(lab(50% 20 -30), $lightness: 10%);\n  \
+            b: scale-color(oklch(40% 0.1 200), $lightness: 50%);\n  \
+            c: change-color(lch(50% 20 30), $lightness: 70%, \
+            $alpha: 0.5);\n}\n",
+          "p {\n  a: lab(60% 20 -30);\n  b: oklch(70% 0.1 200deg);\n  \
+           c: lch(70% 20 30deg / 0.5);\n}\n")
+}
+
+#[test]
+fn color_level_4_adjust_hue_and_chroma() {
+    check(b"p {\n  a: complement(lch(50% 30 270));\n  \
+            b: adjust-hue(oklch(60% 0.1 350), 30deg);\n  \
+            c: saturate(lch(50% 30 270), 10%);\n  \
+            d: desaturate(oklch(60% 0.1 20), 50%);\n  \
+            e: complement(lab(50% 20 -30));\n}\n",
+          "p {\n  a: lch(50% 30 90deg);\n  b: oklch(60% 0.1 20deg);\n  \
+           c: lch(50% 45 270deg);\n  d: oklch(60% 0 20deg);\n  \
+           e: lab(50% -20 30);\n}\n")
+}
+
+#[test]
+fn color_level_4_none_channels() {
+    check(b"p {\n  a: lab(none 0 0);\n  b: oklch(50% 0.1 none / 0.5);\n  \
+            c: lch(50% 20 30 / none);\n}\n",
+          "p {\n  a: lab(none 0 0);\n  b: oklch(50% 0.1 none / 0.5);\n  \
+           c: lch(50% 20 30 / none);\n}\n")
+}
+
+#[test]
+fn space_separated_rgb_and_hsl() {
+    check(b"p {\n  a: rgb(0 128 255 / 50%);\n  b: hsl(210deg 50% 40% / .5);\n  \
+            c: rgb(0 128 255);\n  d: hsl(0.5turn 50% 40%);\n  \
+            e: rgb(10, 20, 30, 0.5);\n}\n",
+          "p {\n  a: rgba(0, 128, 255, 0.5);\n  b: rgba(51, 102, 153, 0.5);\n  \
+           c: #0080ff;\n  d: #339999;\n  e: rgba(10, 20, 30, 0.5);\n}\n")
+}
+
+#[test]
+fn hue_angle_units() {
+    check(b"p {\n  a: hsl(0.25turn, 100%, 50%);\n  \
+            b: adjust-hue(red, 200grad);\n  \
+            c: change-color(red, $hue: 3.1415926536rad);\n}\n",
+          "p {\n  a: #80ff00;\n  b: cyan;\n  c: cyan;\n}\n")
+}
+
+#[test]
+fn hue_not_angle() {
+    check_err(b"p {\n  color: hsl(10px 50% 50%);\n}\n",
+              "$hue: Expected 10px to have an angle unit \
+               (deg, grad, rad, turn).")
+}
+
+#[test]
+fn css_only_color_arguments() {
+    check(b"p {\n  a: rgba(var(--brand), .5);\n  b: rgb(var(--r) 0 0 / .5);\n  \
+            c: rgba(#f00, var(--a));\n  \
+            d: hsl(calc(var(--h) + 10deg), 50%, 40%);\n  e: rgb(env(x));\n  \
+            f: rgb(10 20 30 / var(--a));\n}\n",
+          "p {\n  a: rgba(var(--brand), 0.5);\n  \
+           b: rgb(var(--r) 0 0 / 0.5);\n  c: rgba(#f00, var(--a));\n  \
+           d: hsl(calc(var(--h) + 10deg), 50%, 40%);\n  e: rgb(env(x));\n  \
+           f: rgb(10 20 30 / var(--a));\n}\n")
+}
+
+#[test]
+fn rgb_wrong_channel_count() {
+    check_err(b"p {\n  color: rgb(1 2);\n}\n",
+              "$channels: Expected 3 channels, got 2.\n")
+}
+
+#[test]
+fn hex_with_alpha() {
+    check(b"p {\n  a: #ff000080;\n  b: #F008;\n  c: alpha(#f008);\n  \
+            d: rgba(#ff000080, 1);\n}\n",
+          "p {\n  a: #ff000080;\n  b: #F008;\n  c: 0.5333333333;\n  \
+           d: red;\n}\n")
+}
+
+#[test]
+fn short_colors_compressed() {
+    let css = compile_scss(b"p {\n  a: #FFFFFF;\n  b: white;\n  c: #ff0000;\n  \
+                             d: rgba(0, 0, 0, 0);\n  e: #aabbcc88;\n  \
+                             f: rgba(255, 0, 0, 0.5);\n}\n",
+                           short_colors(OutputStyle::Compressed));
+    assert_eq!(String::from_utf8(css.unwrap()).unwrap(),
+               "p{a:#fff;b:#fff;c:red;d:#0000;e:#abc8;\
+                f:rgba(255,0,0,.5)}\n")
+}
+
+#[test]
+fn short_colors_only_compressed() {
+    let css = compile_scss(b"p {\n  a: #FFFFFF;\n}\n",
+                           short_colors(OutputStyle::Normal));
+    assert_eq!(String::from_utf8(css.unwrap()).unwrap(),
+               "p {\n  a: #FFFFFF;\n}\n")
+}
+
+fn short_colors(style: OutputStyle) -> Format {
+    Format {
+        style: style,
+        short_colors: true,
+        ..Format::default()
+    }
+}
+
 fn check(input: &[u8], expected: &str) {
     assert_eq!(compile_scss(input, OutputStyle::Normal)
                    .and_then(|s| Ok(String::from_utf8(s)?))
                    .unwrap(),
                expected);
 }
+
+fn check_err(input: &[u8], expected: &str) {
+    let err = compile_scss(input, OutputStyle::Normal).unwrap_err();
+    assert!(err.to_string().starts_with(expected), "{}", err);
+    assert!(err.to_string().contains("stdin 2:3"), "{}", err)
+}
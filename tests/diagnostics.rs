@@ -0,0 +1,137 @@
+//! Tests for `@debug`, `@warn` and `@error`.
+extern crate rsass;
+use rsass::{FileContext, GlobalScope, Logger, OutputStyle, SourcePos,
+            compile_scss, parse_scss_data};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn debug_and_warn() {
+    let (css, log) = logged(b"$x: 3;\n@debug $x * 2;\n\
+                              a {\n  @warn \"too #{$x}\";\n  b: c;\n}\n");
+    assert_eq!(css.unwrap(), "a {\n  b: c;\n}\n");
+    assert_eq!(log,
+               vec!["stdin:2 DEBUG: 6".to_string(),
+                    "stdin:4 WARNING: too 3".to_string()])
+}
+
+#[test]
+fn warn_in_function() {
+    let (css, log) = logged(b"@function f($x) {\n  \
+                              @if $x < 0 { @warn \"negative\"; }\n  \
+                              @return $x;\n}\n\
+                              a { b: f(-1); }\n");
+    assert_eq!(css.unwrap(), "a {\n  b: -1;\n}\n");
+    assert_eq!(log, vec!["stdin:2 WARNING: negative".to_string()])
+}
+
+#[test]
+fn deprecation_warning() {
+    let (css, log) = logged(b"a { b: unquote(17); }\n");
+    assert_eq!(css.unwrap(), "a {\n  b: 17;\n}\n");
+    assert_eq!(log,
+               vec!["DEPRECATION: Passing 17, a non-string value, \
+                     to unquote()"
+                            .to_string()])
+}
+
+#[test]
+fn deprecation_warning_once() {
+    let (css, log) = logged(b"@each $i in 1 2 3 {\n  \
+                              a { b: call(\"unquote\", \"x\"); }\n}\n");
+    assert_eq!(css.unwrap(), "a {\n  b: x;\n}\n\na {\n  b: x;\n}\n\n\
+                              a {\n  b: x;\n}\n");
+    assert_eq!(log.len(), 1, "{:?}", log)
+}
+
+#[test]
+fn deprecation_warning_in_module() {
+    let (css, log) = logged(b"@use \"sass:string\";\n\
+                              a { b: string.unquote(17); }\n");
+    assert_eq!(css.unwrap(), "a {\n  b: 17;\n}\n");
+    assert_eq!(log.len(), 1, "{:?}", log)
+}
+
+#[test]
+fn deprecation_warning_once_per_compilation() {
+    let logger = Arc::new(Collect::default());
+    let items = parse_scss_data(b"a { b: unquote(17); }\n").unwrap();
+    for _ in 0..2 {
+        let mut globals = GlobalScope::new();
+        globals.set_logger(logger.clone());
+        OutputStyle::Normal
+            .write_root(&items, &mut globals, FileContext::new())
+            .unwrap();
+    }
+    assert_eq!(logger.0.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn error_at_root() {
+    let err = compile_scss(b"$x: 3;\n@error \"Bad #{$x}\";\na { b: c; }\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert_eq!(err.to_string(),
+               "Bad 3\n  \u{2577}\n2 \u{2502} @error \"Bad #{$x}\";\n  \
+                \u{2502} ^\n  \u{2575}\n  stdin 2:1  root stylesheet")
+}
+
+#[test]
+fn error_in_function() {
+    let err = compile_scss(b"@function f($x) {\n  \
+                             @if $x < 0 {\n    \
+                             @error \"Negative: #{$x}\";\n  }\n  \
+                             @return $x;\n}\n\
+                             a { b: f(-1); }\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("Negative: -1\n"), "{}", err);
+    assert!(err.to_string().contains("stdin 3:5"), "{}", err)
+}
+
+#[test]
+fn error_in_mixin() {
+    let err = compile_scss(b"@mixin m {\n  @error oops;\n}\n\
+                             a { @include m; }\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("oops\n"), "{}", err);
+    assert!(err.to_string().contains("stdin 2:3"), "{}", err)
+}
+
+/// Compile `input`, and get the result and all logged messages.
+fn logged(input: &[u8]) -> (Result<String, String>, Vec<String>) {
+    let logger = Arc::new(Collect::default());
+    let mut globals = GlobalScope::new();
+    globals.set_logger(logger.clone());
+    let css = parse_scss_data(input)
+        .and_then(|items| {
+            OutputStyle::Normal.write_root(&items,
+                                           &mut globals,
+                                           FileContext::new())
+        })
+        .and_then(|s| Ok(String::from_utf8(s)?))
+        .map_err(|e| e.to_string());
+    let log = logger.0.lock().unwrap().clone();
+    (css, log)
+}
+
+#[derive(Default)]
+struct Collect(Mutex<Vec<String>>);
+
+impl Logger for Collect {
+    fn debug(&self, msg: &str, pos: &SourcePos) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("{}:{} DEBUG: {}", pos.file, pos.line, msg));
+    }
+    fn warn(&self, msg: &str, pos: &SourcePos) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("{}:{} WARNING: {}", pos.file, pos.line, msg));
+    }
+    fn deprecation(&self, msg: &str) {
+        self.0.lock().unwrap().push(format!("DEPRECATION: {}", msg));
+    }
+}
@@ -0,0 +1,131 @@
+//! Tests for error reporting.
+extern crate rsass;
+use rsass::{Error, OutputStyle, compile_scss, compile_scss_file};
+
+#[test]
+fn parse_error_position() {
+    match compile_scss(b"a {\n  b: c;\n}\nd { e: ; }\n",
+                       OutputStyle::Normal) {
+        Err(Error::Parse { pos, imported, .. }) => {
+            assert_eq!((pos.file.as_ref(), pos.line, pos.column),
+                       ("stdin", 4, 1));
+            assert_eq!(pos.line_text, "d { e: ; }");
+            assert!(imported.is_empty());
+        }
+        r => panic!("Expected parse error, got {:?}", r),
+    }
+}
+
+#[test]
+fn parse_error_display() {
+    let err = compile_scss(b"a {\n  b: c;\n}\nd { e: ; }\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert_eq!(err.to_string(),
+               "Failed to parse entire input\n  \
+                \u{2577}\n\
+                4 \u{2502} d { e: ; }\n  \
+                \u{2502} ^\n  \
+                \u{2575}\n  \
+                stdin 4:1  root stylesheet")
+}
+
+#[test]
+fn unexpected_end() {
+    let err = compile_scss(b"a {\n  b: c;\n", OutputStyle::Normal)
+        .unwrap_err();
+    assert_eq!(err.to_string(),
+               "Unexpected end of input\n  \
+                \u{2577}\n\
+                2 \u{2502}   b: c;\n  \
+                \u{2502}        ^\n  \
+                \u{2575}\n  \
+                stdin 2:8  root stylesheet")
+}
+
+#[test]
+fn parse_error_in_import() {
+    let err = compile_scss_file("tests/errors/main.scss".as_ref(),
+                                OutputStyle::Normal)
+        .unwrap_err();
+    assert_eq!(err.to_string(),
+               "Failed to parse entire input\n   \
+                \u{2577}\n\
+                10 \u{2502}   .inner { color: ; }\n   \
+                \u{2502}   ^\n   \
+                \u{2575}\n  \
+                tests/errors/_broken.scss 10:3  @import\n  \
+                tests/errors/main.scss 3:1      root stylesheet")
+}
+
+#[test]
+fn extend_error_position() {
+    let err = compile_scss(b"a {\n  b: c;\n}\n.d {\n  @extend .missing;\n}\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    match err {
+        Error::Eval { ref pos, .. } => {
+            assert_eq!((pos.line, pos.column), (5, 3));
+        }
+        ref e => panic!("Expected eval error, got {:?}", e),
+    }
+}
+
+#[test]
+fn variable_error_display() {
+    let err = compile_scss(b"$x: 1px + 1em;\n", OutputStyle::Normal)
+        .unwrap_err();
+    assert_eq!(err.to_string(),
+               "1px and 1em have incompatible units.\n  \
+                \u{2577}\n\
+                1 \u{2502} $x: 1px + 1em;\n  \
+                \u{2502}     ^\n  \
+                \u{2575}\n  \
+                stdin 1:5  root stylesheet")
+}
+
+#[test]
+fn condition_error_position() {
+    let err = compile_scss(b"a {\n  @if 1px + 1em {\n    b: c;\n  }\n}\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    match err {
+        Error::Eval { ref pos, .. } => {
+            assert_eq!((pos.line, pos.column), (2, 7));
+        }
+        ref e => panic!("Expected eval error, got {:?}", e),
+    }
+}
+
+#[test]
+fn each_error_position() {
+    let err = compile_scss(b"@each $i in 1px + 1em {\n  a { b: $i; }\n}\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    match err {
+        Error::Eval { ref pos, .. } => {
+            assert_eq!((pos.line, pos.column), (1, 13));
+        }
+        ref e => panic!("Expected eval error, got {:?}", e),
+    }
+}
+
+#[test]
+fn undefined_mixin_position() {
+    let err = compile_scss(b"a { b: c; }\n@include nosuch;\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    match err {
+        Error::Eval { ref msg, ref pos, .. } => {
+            assert_eq!(msg, "Undefined mixin.");
+            assert_eq!((pos.line, pos.column), (2, 1));
+        }
+        ref e => panic!("Expected eval error, got {:?}", e),
+    }
+}
+
+#[test]
+fn bad_value_display() {
+    let err = Error::BadValue("Something is wrong.".into());
+    assert_eq!(err.to_string(), "Something is wrong.");
+}
@@ -0,0 +1,108 @@
+//! Tests for `@extend` and placeholder selectors.
+//! Mostly based on the "extend-tests" in the sass specification.
+//! See https://github.com/sass/sass-spec for source material.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn basic_class() {
+    check(b".foo {a: b}\n.bar {@extend .foo}\n",
+          ".foo, .bar {\n  a: b;\n}\n")
+}
+
+#[test]
+fn compound_host() {
+    check(b"a.foo:hover {a: b}\n.bar {@extend .foo}\n",
+          "a.foo:hover, a.bar:hover {\n  a: b;\n}\n")
+}
+
+#[test]
+fn conflicting_elements_not_unified() {
+    check(b"a.foo {a: b}\nb {@extend .foo}\n", "a.foo {\n  a: b;\n}\n")
+}
+
+#[test]
+fn weave_parents() {
+    check(b".a .b {c: d}\n.x .y {@extend .b}\n",
+          ".a .b, .a .x .y, .x .a .y {\n  c: d;\n}\n")
+}
+
+#[test]
+fn chained_extends() {
+    check(b".a {x: y}\n.b {@extend .a}\n.c {@extend .b}\n",
+          ".a, .b, .c {\n  x: y;\n}\n")
+}
+
+#[test]
+fn placeholder_is_replaced() {
+    check(b"%button-base {padding: 1em}\n\
+            .btn {@extend %button-base; color: red}\n",
+          ".btn {\n  padding: 1em;\n}\n\n.btn {\n  color: red;\n}\n")
+}
+
+#[test]
+fn unused_placeholder_is_removed() {
+    check(b"%unused {a: b}\n%unused .x {c: d}\n.y {e: f}\n",
+          ".y {\n  e: f;\n}\n")
+}
+
+#[test]
+fn extend_from_mixin() {
+    check(b"%base {a: b}\n\
+            @mixin base {@extend %base}\n\
+            .foo {@include base}\n",
+          ".foo {\n  a: b;\n}\n")
+}
+
+#[test]
+fn extend_in_media() {
+    check(b"@media print {%base {a: b}\n.foo {@extend %base; c: d}}\n\
+            .bar {@extend %base; e: f}\n",
+          "@media print {\n  .foo, .bar {\n    a: b;\n  }\n  \
+           .foo {\n    c: d;\n  }\n}\n.bar {\n  e: f;\n}\n")
+}
+
+#[test]
+fn error_extend_across_media() {
+    let err = compile_scss(b"%base {a: b}\n\
+                             @media print {.foo {@extend %base; c: d}}\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string()
+                .starts_with("You may not @extend selectors across media \
+                              queries."),
+            "{}",
+            err)
+}
+
+#[test]
+fn compressed() {
+    assert_eq!(compile_scss(b"%b {a: b}\n.x {@extend %b}\n.y {@extend %b}",
+                            OutputStyle::Compressed)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               ".x,.y{a:b}\n")
+}
+
+#[test]
+fn optional_missing_target() {
+    check(b".foo {@extend .bar !optional; a: b}\n", ".foo {\n  a: b;\n}\n")
+}
+
+#[test]
+fn error_missing_target() {
+    assert!(compile_scss(b".foo {@extend .bar; a: b}\n", OutputStyle::Normal)
+                .is_err())
+}
+
+#[test]
+fn error_extend_outside_rule() {
+    assert!(compile_scss(b"@extend .foo;\n", OutputStyle::Normal).is_err())
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
@@ -0,0 +1,80 @@
+//! Tests for the list functions.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn nth_and_set_nth() {
+    check(b"$l: 10px 20px 30px;\na {\n  b: nth($l, 1);\n  \
+            c: nth($l, -1);\n  d: set-nth($l, -2, 0);\n  \
+            e: set-nth([a, b], 1, c);\n  f: nth((x: 1, y: 2), 2);\n}\n",
+          "a {\n  b: 10px;\n  c: 30px;\n  d: 10px 0 30px;\n  e: [c, b];\n  \
+           f: y 2;\n}\n")
+}
+
+#[test]
+fn join() {
+    check(b"a {\n  b: join(10px 20px, 30px 40px);\n  \
+            c: join((blue, red), (#abc, #def));\n  \
+            d: join(10px, 20px);\n  e: join(10px, 20px, comma);\n  \
+            f: join((blue, red), (#abc, #def), space);\n  \
+            g: join([10px], 20px);\n  \
+            h: join(10px, 20px, $bracketed: true);\n}\n",
+          "a {\n  b: 10px 20px 30px 40px;\n  c: blue, red, #abc, #def;\n  \
+           d: 10px 20px;\n  e: 10px, 20px;\n  \
+           f: blue red #abc #def;\n  g: [10px 20px];\n  \
+           h: [10px 20px];\n}\n")
+}
+
+#[test]
+fn zip() {
+    check(b"a {\n  b: zip(1px 1px 3px, solid dashed solid, red green blue);\n  \
+            c: length(zip(1 2 3, a b));\n}\n",
+          "a {\n  b: 1px solid red, 1px dashed green, 3px solid blue;\n  \
+           c: 2;\n}\n")
+}
+
+#[test]
+fn append_separator() {
+    check(b"a {\n  b: append(10px 20px, 30px, auto);\n  \
+            c: append((a, b), c, space);\n  \
+            d: append(a b, c, slash);\n}\n",
+          "a {\n  b: 10px 20px 30px;\n  c: a b c;\n  d: a/b/c;\n}\n")
+}
+
+#[test]
+fn index_out_of_range() {
+    check_err(b"a {\n  b: nth(a b c, 4);\n}\n",
+              "$n: Invalid index 4 for a list with 3 elements.\n")
+}
+
+#[test]
+fn negative_index_out_of_range() {
+    check_err(b"a {\n  b: set-nth(a b c, -4, d);\n}\n",
+              "$n: Invalid index -4 for a list with 3 elements.\n")
+}
+
+#[test]
+fn index_zero() {
+    check_err(b"a {\n  b: nth(a b c, 0);\n}\n",
+              "$n: List index may not be 0.\n")
+}
+
+#[test]
+fn bad_separator() {
+    check_err(b"a {\n  b: join(a, b, semicolon);\n}\n",
+              "$separator: Must be \"space\", \"comma\", \"slash\", \
+               or \"auto\".\n")
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
+
+fn check_err(input: &[u8], expected: &str) {
+    let err = compile_scss(input, OutputStyle::Normal).unwrap_err();
+    assert!(err.to_string().starts_with(expected), "{}", err);
+    assert!(err.to_string().contains("stdin 2:3"), "{}", err)
+}
@@ -0,0 +1,99 @@
+//! Tests for load paths and custom importers.
+extern crate rsass;
+use rsass::{Error, FileContext, GlobalScope, Importer, OutputStyle};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[test]
+fn load_paths_in_order() {
+    assert_eq!(compile_with_paths(&["tests/load_paths/vendor",
+                                    "tests/load_paths/other"]),
+               ".main{color:red;border:1px solid}\n");
+    assert_eq!(compile_with_paths(&["tests/load_paths/other",
+                                    "tests/load_paths/vendor"]),
+               ".main{color:blue;border:1px solid}\n");
+}
+
+#[test]
+fn relative_before_load_paths() {
+    let mut context = FileContext::new();
+    context.push_path("tests/load_paths/other".as_ref());
+    let (context, items) = context.load("tests/basic/14_imports/a.scss"
+                                            .as_ref())
+        .unwrap();
+    let css = OutputStyle::Compressed
+        .write_root(&items, &mut GlobalScope::new(), context)
+        .unwrap();
+    assert_eq!(String::from_utf8(css).unwrap(), "div span{moo:goo}\n")
+}
+
+/// An importer for sources in memory.
+#[derive(Debug)]
+struct MemImporter(BTreeMap<&'static str, &'static str>);
+
+impl Importer for MemImporter {
+    fn resolve(&self, base: &Path, url: &str) -> Option<PathBuf> {
+        let path = base.join(format!("_{}.scss", url));
+        if self.0.contains_key(path.to_str().unwrap()) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+    fn load(&self, path: &Path) -> Result<(Vec<u8>, PathBuf), Error> {
+        let src = self.0[path.to_str().unwrap()];
+        Ok((src.as_bytes().to_vec(), path.into()))
+    }
+}
+
+#[test]
+fn custom_importer() {
+    let mut files = BTreeMap::new();
+    files.insert("main.scss", "@import \"lib\";\na { b: $c; }\n");
+    files.insert("assets/_lib.scss", "@import \"more\";\n$c: d;\n");
+    files.insert("assets/_more.scss", "x { y: z; }\n");
+    let mut context = FileContext::with_importer(Arc::new(MemImporter(files)));
+    context.push_path("assets".as_ref());
+    let (context, items) = context.load("main.scss".as_ref()).unwrap();
+    let css = OutputStyle::Compressed
+        .write_root(&items, &mut GlobalScope::new(), context)
+        .unwrap();
+    assert_eq!(String::from_utf8(css).unwrap(), "x{y:z}a{b:d}\n")
+}
+
+#[test]
+fn custom_importer_error_position() {
+    let mut files = BTreeMap::new();
+    files.insert("main.scss", "@import \"lib\";\n");
+    files.insert("_lib.scss", "a {\n  b: ;\n}\n");
+    let context = FileContext::with_importer(Arc::new(MemImporter(files)));
+    let err = context.load("main.scss".as_ref())
+        .and_then(|(context, items)| {
+            OutputStyle::Compressed.write_root(&items,
+                                               &mut GlobalScope::new(),
+                                               context)
+        })
+        .unwrap_err();
+    assert!(err.to_string().contains("_lib.scss 1:1  @import\n"), "{}", err)
+}
+
+fn compile_with_paths(paths: &[&str]) -> String {
+    let mut context = FileContext::new();
+    for path in paths {
+        context.push_path(path.as_ref());
+    }
+    let (context, items) = context.load("tests/load_paths/main.scss"
+                                            .as_ref())
+        .unwrap();
+    let css = OutputStyle::Compressed
+        .write_root(&items, &mut GlobalScope::new(), context)
+        .unwrap();
+    String::from_utf8(css).unwrap()
+}
+
+#[test]
+fn file_context_is_send_and_sync() {
+    fn check<T: Send + Sync>(_: &T) {}
+    check(&FileContext::new());
+}
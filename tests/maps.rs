@@ -0,0 +1,72 @@
+//! Tests for sass maps.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn each_key_value() {
+    check(b"$sizes: (small: 10px, large: 20px);\n\
+            a {\n  @each $name, $size in $sizes {\n    \
+            width: $name $size;\n  }\n}\n",
+          "a {\n  width: small 10px;\n  width: large 20px;\n}\n")
+}
+
+#[test]
+fn each_destructure_list() {
+    check(b"x {\n  @each $name, $glyph in (a 1, b 2) {\n    \
+            content: $name $glyph;\n  }\n}\n",
+          "x {\n  content: a 1;\n  content: b 2;\n}\n")
+}
+
+#[test]
+fn each_destructure_missing_is_null() {
+    check(b"p {\n  @each $a, $b, $c in (x y, z w) {\n    \
+            v: $a $b $c;\n  }\n}\n",
+          "p {\n  v: x y;\n  v: z w;\n}\n")
+}
+
+#[test]
+fn nested_map() {
+    check(b"$theme: (colors: (primary: red, secondary: blue));\n\
+            a {color: map-get(map-get($theme, colors), secondary)}\n",
+          "a {\n  color: blue;\n}\n")
+}
+
+#[test]
+fn keywords() {
+    check(b"@function opts($args...) {\n  @return keywords($args);\n}\n\
+            a {b: map-get(opts($foo: 1, $bar: 2), bar);\n  \
+            c: length(opts($x: 1))}\n",
+          "a {\n  b: 2;\n  c: 1;\n}\n")
+}
+
+#[test]
+fn keywords_with_positional() {
+    check(b"@mixin m($args...) {\n  \
+            b: length($args);\n  c: map-keys(keywords($args));\n}\n\
+            a {@include m(1, 2, $x: 3)}\n",
+          "a {\n  b: 2;\n  c: x;\n}\n")
+}
+
+#[test]
+fn duplicate_key() {
+    check_err(b"$m: (a: 1, a: 2);\n", "Duplicate key.")
+}
+
+#[test]
+fn duplicate_evaluated_key() {
+    check_err(b"$x: a;\n$m: ($x: 1, a: 2);\n", "Duplicate key.")
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
+
+fn check_err(input: &[u8], expected: &str) {
+    let msg = compile_scss(input, OutputStyle::Normal)
+        .unwrap_err()
+        .to_string();
+    assert!(msg.starts_with(expected), "Unexpected error: {}", msg);
+}
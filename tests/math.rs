@@ -0,0 +1,101 @@
+//! Tests for the math functions.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn pow_sqrt_log() {
+    check(b"a {\n  b: pow(2, 10);\n  c: pow(2, 0.5);\n  d: sqrt(16);\n  \
+            e: log(100, 10);\n  f: log(1);\n}\n",
+          "a {\n  b: 1024;\n  c: 1.4142135624;\n  d: 4;\n  e: 2;\n  \
+           f: 0;\n}\n")
+}
+
+#[test]
+fn trig_with_angle_units() {
+    check(b"a {\n  b: sin(90deg);\n  c: cos(0.5turn);\n  d: tan(50grad);\n  \
+            e: sin(0);\n}\n",
+          "a {\n  b: 1;\n  c: -1;\n  d: 1;\n  e: 0;\n}\n")
+}
+
+#[test]
+fn inverse_trig_gives_degrees() {
+    check(b"a {\n  b: asin(1);\n  c: acos(0.5);\n  d: atan(1);\n  \
+            e: atan2(1px, -1px);\n}\n",
+          "a {\n  b: 90deg;\n  c: 60deg;\n  d: 45deg;\n  e: 135deg;\n}\n")
+}
+
+#[test]
+fn hypot_clamp_div() {
+    check(b"a {\n  b: hypot(3cm, 40mm);\n  c: clamp(1px, 5px, 3px);\n  \
+            d: clamp(1cm, 5mm, 3cm);\n  e: div(10px, 4);\n  \
+            f: div(1in, 2px);\n}\n",
+          "a {\n  b: 5cm;\n  c: 3px;\n  d: 1cm;\n  e: 2.5px;\n  f: 48;\n}\n")
+}
+
+#[test]
+fn unitless() {
+    check(b"a {\n  b: unitless(1);\n  c: unitless(1px);\n  \
+            d: unitless(2px / 1px);\n  e: is-unitless(1);\n  \
+            f: is-unitless(1px);\n}\n",
+          "a {\n  b: true;\n  c: false;\n  d: true;\n  e: true;\n  \
+           f: false;\n}\n")
+}
+
+#[test]
+fn math_module() {
+    check(b"@use \"sass:math\";\na {\n  b: math.pow(math.$e, 2);\n  \
+            c: math.sin(math.$pi / 6);\n  d: math.is-unitless(1px);\n}\n",
+          "a {\n  b: 7.3890560989;\n  c: 0.5;\n  d: false;\n}\n")
+}
+
+#[test]
+fn trig_needs_angle() {
+    check_err(b"a {\n  b: sin(1px);\n}\n",
+              "$number: Expected 1px to have an angle unit \
+               (deg, grad, rad, turn).\n")
+}
+
+#[test]
+fn pow_needs_unitless() {
+    check_err(b"a {\n  b: pow(2px, 2);\n}\n",
+              "$base: Expected 2px to have no units.\n")
+}
+
+#[test]
+fn nan_is_not_supported() {
+    check_err(b"a {\n  b: sqrt(-1);\n}\n", "NaN is not a supported number.\n");
+    check_err(b"a {\n  b: asin(2);\n}\n", "NaN is not a supported number.\n")
+}
+
+#[test]
+fn infinity_is_not_supported() {
+    check_err(b"a {\n  b: log(0);\n}\n",
+              "-Infinity is not a supported number.\n");
+    check_err(b"a {\n  b: pow(10, 400.5);\n}\n",
+              "Infinity is not a supported number.\n")
+}
+
+#[test]
+fn pow_with_integer_exponent_is_exact() {
+    check(b"a {\n  b: pow(10, 400) / pow(10, 398);\n  c: pow(0.5, -2);\n}\n",
+          "a {\n  b: 100;\n  c: 4;\n}\n")
+}
+
+#[test]
+fn hypot_incompatible_units() {
+    check_err(b"a {\n  b: hypot(1px, 1em);\n}\n",
+              "1px and 1em have incompatible units.\n")
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
+
+fn check_err(input: &[u8], expected: &str) {
+    let err = compile_scss(input, OutputStyle::Normal).unwrap_err();
+    assert!(err.to_string().starts_with(expected), "{}", err);
+    assert!(err.to_string().contains("stdin 2:3"), "{}", err)
+}
@@ -0,0 +1,88 @@
+//! Tests for `@media` rules, bubbling out of rules and merging.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn bubble_from_rule() {
+    check(b".card {\n  color: red;\n  @media (min-width: 600px) {\n    \
+            padding: 2em;\n  }\n}\n",
+          ".card {\n  color: red;\n}\n\
+           @media (min-width: 600px) {\n  .card {\n    padding: 2em;\n  \
+           }\n}\n")
+}
+
+#[test]
+fn bubble_with_nested_rule() {
+    check(b".a {\n  @media print {\n    .b { c: d; }\n  }\n}\n",
+          "@media print {\n  .a .b {\n    c: d;\n  }\n}\n")
+}
+
+#[test]
+fn merge_nested_media() {
+    check(b"@media screen {\n  .a {\n    @media (min-width: 1px) {\n      \
+            b: c;\n    }\n    d: e;\n  }\n}\n",
+          "@media screen {\n  .a {\n    d: e;\n  }\n}\n\
+           @media screen and (min-width: 1px) {\n  .a {\n    b: c;\n  \
+           }\n}\n")
+}
+
+#[test]
+fn merge_query_lists() {
+    check(b"@media screen, print {\n  @media (color) {\n    \
+            .b { c: d; }\n  }\n}\n",
+          "@media screen and (color), print and (color) {\n  .b {\n    \
+           c: d;\n  }\n}\n")
+}
+
+#[test]
+fn unmergeable_is_dropped() {
+    check(b"@media screen {\n  @media print {\n    .b { c: d; }\n  }\n  \
+            .e { f: g; }\n}\n",
+          "@media screen {\n  .e {\n    f: g;\n  }\n}\n")
+}
+
+#[test]
+fn not_type_with_features_is_kept_nested() {
+    check(b"@media not print {\n  .a {\n    @media (color) { b: c; }\n  \
+            }\n}\n",
+          "@media not print {\n  @media (color) {\n    .a {\n      \
+           b: c;\n    }\n  }\n}\n")
+}
+
+#[test]
+fn not_with_different_features_is_kept_nested() {
+    check(b"@media not screen and (a) {\n  \
+            @media not screen and (b) {\n    .d { e: f; }\n  }\n}\n",
+          "@media not screen and (a) {\n  @media not screen and (b) {\n    \
+           .d {\n      e: f;\n    }\n  }\n}\n")
+}
+
+#[test]
+fn feature_value_from_variable() {
+    check(b"$bp: 600px;\n\
+            @media screen and (max-width: $bp - 1) {\n  .a { b: c; }\n}\n",
+          "@media screen and (max-width: 599px) {\n  .a {\n    b: c;\n  \
+           }\n}\n")
+}
+
+#[test]
+fn interpolated_query() {
+    check(b"$q: \"print\";\n@media #{$q} {\n  .a { b: c; }\n}\n",
+          "@media print {\n  .a {\n    b: c;\n  }\n}\n")
+}
+
+#[test]
+fn compressed() {
+    assert_eq!(compile_scss(b".a {\n  @media screen and (min-width: 1px) \
+                              {\n    b: c;\n  }\n}\n",
+                            OutputStyle::Compressed)
+                   .unwrap(),
+               &b"@media screen and (min-width: 1px){.a{b:c}}\n"[..])
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
@@ -0,0 +1,90 @@
+//! Tests for function values, `get-function` and `call`.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn call_user_function() {
+    check(b"@function double($x) {\n  @return $x * 2;\n}\n\
+            @function map-list($list, $f) {\n  $result: ();\n  \
+            @each $item in $list {\n    \
+            $result: append($result, call($f, $item));\n  }\n  \
+            @return $result;\n}\n\
+            a {\n  b: map-list(1 2 3, get-function(double));\n}\n",
+          "a {\n  b: 2 4 6;\n}\n")
+}
+
+#[test]
+fn call_builtin_function() {
+    check(b"@use \"sass:math\";\na {\n  \
+            b: call(get-function(\"max\"), 1, 5, 3);\n  \
+            c: call(get-function(math.pow), 2, 3);\n  \
+            d: call(get-function(scale-color), #888, $lightness: 50%);\n}\n",
+          "a {\n  b: 5;\n  c: 8;\n  d: #c4c4c4;\n}\n")
+}
+
+#[test]
+fn call_css_function() {
+    check(b"a {\n  b: call(get-function(foo, $css: true), 1, 2);\n}\n",
+          "a {\n  b: foo(1, 2);\n}\n")
+}
+
+#[test]
+fn call_with_list_argument() {
+    check(b"a {\n  b: call(get-function(length), 1 2 3);\n  \
+            c: call(get-function(foo, $css: true), 1 2);\n}\n",
+          "a {\n  b: 3;\n  c: foo(1 2);\n}\n")
+}
+
+#[test]
+fn call_with_rest_arguments() {
+    check(b"@function f($args...) {\n  \
+            @return call(get-function(max), $args...);\n}\n\
+            $l: 1 7 2;\n$kw: (lightness: 50%);\n\
+            a {\n  b: f(1, 5, 3);\n  c: max($l...);\n  \
+            d: call(get-function(scale-color), #888, $kw...);\n}\n",
+          "a {\n  b: 5;\n  c: 7;\n  d: #c4c4c4;\n}\n")
+}
+
+#[test]
+fn builtin_function_value() {
+    check(b"a {\n  b: get-function(lighten) == get-function(lighten);\n  \
+            c: get-function(lighten) == get-function(darken);\n}\n",
+          "a {\n  b: true;\n  c: false;\n}\n")
+}
+
+#[test]
+fn function_value() {
+    check(b"@function f() {\n  @return 1;\n}\n\
+            a {\n  b: type-of(get-function(f));\n  \
+            c: get-function(f) == get-function(f);\n}\n",
+          "a {\n  b: function;\n  c: true;\n}\n")
+}
+
+#[test]
+fn function_not_found() {
+    let err = compile_scss(b"a {\n  b: get-function(nosuch);\n}\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("Function not found: nosuch\n"),
+            "{}",
+            err)
+}
+
+#[test]
+fn function_is_not_css() {
+    let err = compile_scss(b"a {\n  b: get-function(round);\n}\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string()
+                .starts_with("get-function(\"round\") isn't a valid CSS \
+                              value.\n"),
+            "{}",
+            err)
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
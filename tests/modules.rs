@@ -0,0 +1,155 @@
+//! Tests for the module system, `@use` and `@forward`.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss, compile_scss_file};
+
+#[test]
+fn use_forward_and_configure() {
+    assert_eq!(String::from_utf8(compile_scss_file("tests/modules/main.scss"
+                                                       .as_ref(),
+                                                   OutputStyle::Normal)
+                                     .unwrap())
+                   .unwrap(),
+               ".styled {\n  x: y;\n}\n\n\
+                a {\n  c: blue;\n  d: 6;\n  e: 101px;\n  \
+                border-radius: 3px;\n}\n")
+}
+
+#[test]
+fn builtin_modules() {
+    check(b"@use \"sass:math\";\n@use \"sass:map\";\n\
+            @use \"sass:string\" as str;\n\
+            $m: (a: 1, b: 2);\n\
+            a {\n  b: math.abs(-3px);\n  c: map.get($m, b);\n  \
+            d: str.to-upper-case(\"hi\");\n  e: math.$pi;\n}\n",
+          "a {\n  b: 3px;\n  c: 2;\n  d: \"HI\";\n  e: 3.1415926536;\n}\n")
+}
+
+#[test]
+fn builtin_module_without_namespace() {
+    check(b"@use \"sass:map\" as *;\na { b: get((x: y), x); }\n",
+          "a {\n  b: y;\n}\n")
+}
+
+#[test]
+fn unknown_builtin_module() {
+    let err = compile_scss(b"@use \"sass:nope\";\n", OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("Unknown built-in module sass:nope."),
+            "{}",
+            err)
+}
+
+#[test]
+fn private_members() {
+    check(b"@use \"tests/modules/config\";\na {\n  c: config.$pad;\n}\n",
+          "a {\n  c: 34px;\n}\n");
+    let private = "Private members can't be accessed from outside their \
+                   modules.";
+    check_err(b"@use \"tests/modules/config\";\n\
+                a {\n  b: config.$-secret;\n}\n",
+              private);
+    check_err(b"@use \"tests/modules/config\";\n\
+                a {\n  b: config.-hidden();\n}\n",
+              private);
+}
+
+#[test]
+fn unknown_members() {
+    check_err(b"@use \"sass:math\";\na {\n  b: math.$nope;\n}\n",
+              "Undefined variable.");
+    check_err(b"@use \"sass:math\";\na {\n  b: math.nope(1);\n}\n",
+              "Undefined function.");
+    check_err(b"@use \"sass:math\";\na {\n  @include math.nope;\n}\n",
+              "Undefined mixin.");
+    check_err(b"@use \"sass:math\";\n@include math.nope;\n",
+              "Undefined mixin.");
+    check_err(b"@use \"sass:meta\";\na {\n  b: meta.inspect(1);\n}\n",
+              "Undefined function.");
+    check_err(b"a {\n  b: nope.$x;\n}\n",
+              "There is no module with the namespace \"nope\".");
+}
+
+#[test]
+fn mixin_uses_module_scope() {
+    check(b"@use \"tests/modules/config\" as *;\n\
+            $color: green;\n\
+            a {\n  @include box(2px);\n  b: double(4);\n}\n",
+          "a {\n  width: 2px;\n  color: red;\n  padding: 17px;\n  b: 8;\n}\n")
+}
+
+#[test]
+fn forward_filters() {
+    check(b"@use \"tests/modules/index\";\na {\n  b: index.$color;\n}\n",
+          "a {\n  b: red;\n}\n");
+    for hidden in &["index.$btn-radius", "index.$radius", "index.$pad"] {
+        check_err(format!("@use \"tests/modules/index\";\n\
+                           a {{\n  b: {};\n}}\n",
+                          hidden)
+                      .as_bytes(),
+                  "Undefined variable.");
+    }
+}
+
+#[test]
+fn use_not_allowed_in_rule() {
+    let err = compile_scss(b"a {\n  @use \"sass:math\";\n}\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("This at-rule is not allowed here."),
+            "{}",
+            err)
+}
+
+#[test]
+fn duplicate_namespace() {
+    check_err(b"@use \"tests/modules/config\";\n\
+                @use \"tests/modules/config\";\n",
+              "There's already a module with namespace \"config\".");
+    check_err(b"@use \"sass:math\";\n@use \"tests/modules/config\" as math;\n",
+              "There's already a module with namespace \"math\".");
+}
+
+#[test]
+fn use_after_rules() {
+    check_err(b"a {\n  b: c;\n}\n@use \"sass:math\";\n",
+              "@use rules must be written before any other rules.");
+    check(b"// comment\n$x: 1;\n@use \"sass:math\";\na { b: math.abs($x); }\n",
+          "a {\n  b: 1;\n}\n");
+}
+
+#[test]
+fn assign_module_variable() {
+    check(b"@use \"tests/modules/config\";\n\
+            config.$color: green;\n\
+            a {\n  b: config.$color;\n  @include config.box;\n}\n",
+          "a {\n  b: green;\n  width: 10px;\n  color: green;\n  \
+           padding: 17px;\n}\n");
+    check(b"@use \"tests/modules/index\";\n\
+            a {\n  index.$color: blue;\n  b: index.$color;\n}\n",
+          "a {\n  b: blue;\n}\n");
+    check_err(b"@use \"tests/modules/config\";\nconfig.$nope: 1;\n",
+              "Undefined variable.");
+    check_err(b"@use \"tests/modules/config\";\nconfig.$-secret: 1;\n",
+              "Private members can't be accessed from outside their \
+               modules.");
+}
+
+#[test]
+fn error_in_used_module() {
+    let err = compile_scss(b"@use \"tests/modules/broken\";\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().contains("_broken.scss 1:1  @use\n"), "{}", err)
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
+
+fn check_err(input: &[u8], expected: &str) {
+    let err = compile_scss(input, OutputStyle::Normal).unwrap_err();
+    assert!(err.to_string().starts_with(expected), "{}", err)
+}
@@ -0,0 +1,43 @@
+//! These are from the `output_styles/nested/basic` directory in the
+//! sass specification.
+//! See https://github.com/sass/sass-spec for source material.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn t01_simple_css() {
+    check(b"a {\n  color: blue;\n}", "a {\n  color: blue; }\n")
+}
+
+#[test]
+fn t02_simple_nesting() {
+    check(b"div {\n  img {\n    border: 0px;\n  }\n}",
+          "div img {\n  border: 0px; }\n")
+}
+
+#[test]
+fn t03_nesting_with_properties() {
+    check(b"div {\n  a: b;\n  span {\n    c: d;\n    em { e: f; }\n  }\n  \
+            g: h;\n}\np {\n  q: r;\n}",
+          "div {\n  a: b;\n  g: h; }\n  div span {\n    c: d; }\n    \
+           div span em {\n      e: f; }\n\np {\n  q: r; }\n")
+}
+
+#[test]
+fn t04_comments() {
+    check(b"/* top */\na {\n  b: c;\n  /* inner */\n}",
+          "/* top */\na {\n  b: c;\n  /* inner */ }\n")
+}
+
+#[test]
+fn t05_media() {
+    check(b"@media print {\n  a { b: c; }\n  d { e: f; }\n}\n",
+          "@media print {\n  a {\n    b: c; }\n  d {\n    e: f; } }\n")
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Nested)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
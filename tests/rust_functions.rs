@@ -5,7 +5,7 @@ use std::sync::Arc;
 #[test]
 fn simple_value() {
     let mut scope = GlobalScope::new();
-    scope.define("color", &Value::black());
+    scope.define("color", &Value::black()).unwrap();
     let parsed = parse_scss_data(b"p { color: $color }").unwrap();
     let style = OutputStyle::Compressed;
     let file_context = FileContext::new();
@@ -45,12 +45,12 @@ fn function_with_args() {
                                                       Value::scalar(0))],
                                                 false,
                                                 Arc::new(|s| {
-        let half = Rational::new(1, 2);
+        let half = BigRational::new(1.into(), 2.into());
         match (s.get("a"), s.get("b")) {
             (Value::Numeric(a, au, ..), Value::Numeric(b, bu, ..)) => {
-                if au == bu || bu == Unit::None {
+                if au == bu || bu.is_none() {
                     Ok(Value::Numeric((a + b) * half, au, false, true))
-                } else if au == Unit::None {
+                } else if au.is_none() {
                     Ok(Value::Numeric((a + b) * half, bu, false, true))
                 } else {
                     Err(Error::BadArguments("Incopatible args".into()))
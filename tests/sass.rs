@@ -0,0 +1,90 @@
+//! Tests for the indented sass syntax.
+extern crate rsass;
+use rsass::{OutputStyle, compile_sass, compile_scss, compile_scss_file};
+
+#[test]
+fn nested_rules() {
+    check(b"nav\n  ul\n    margin: 0\n  a\n    color: red\n",
+          "nav ul {\n  margin: 0;\n}\nnav a {\n  color: red;\n}\n")
+}
+
+#[test]
+fn same_as_scss() {
+    let sass = b"$w: 10px\n\
+                 =box($x)\n  width: $x\n\n\
+                 .a,\n.b\n  +box($w)\n  &:hover\n    color: blue\n";
+    let scss = b"$w: 10px;\n\
+                 @mixin box($x) {\n  width: $x;\n}\n\n\
+                 .a,\n.b {\n  @include box($w);\n  \
+                 &:hover {\n    color: blue;\n  }\n}\n";
+    assert_eq!(compile_sass(sass, OutputStyle::Normal).unwrap(),
+               compile_scss(scss, OutputStyle::Normal).unwrap())
+}
+
+#[test]
+fn if_else() {
+    check(b"@if 1 > 2\n  a\n    b: c\n@else\n  a\n    b: d\n",
+          "a {\n  b: d;\n}\n")
+}
+
+#[test]
+fn comments() {
+    check(b"// silent\n  still silent\n/* loud\n   comment\na\n  b: c\n",
+          "/* loud\n   comment */\na {\n  b: c;\n}\n")
+}
+
+#[test]
+fn trailing_silent_comment() {
+    check(b"a\n  b: c // note\n  d: url(//e.f/g) // h\n",
+          "a {\n  b: c;\n  d: url(//e.f/g);\n}\n")
+}
+
+#[test]
+fn nested_properties() {
+    check(b"a\n  font:\n    family: serif\n    size: 2em\n",
+          "a {\n  font-family: serif;\n  font-size: 2em;\n}\n")
+}
+
+#[test]
+fn import_sass_from_scss() {
+    assert_eq!(String::from_utf8(compile_scss_file("tests/sass/main.scss"
+                                                       .as_ref(),
+                                                   OutputStyle::Normal)
+                                     .unwrap())
+                   .unwrap(),
+               ".box {\n  border-width: 2px;\n  border-style: solid;\n}\n")
+}
+
+#[test]
+fn error_position() {
+    let err = compile_sass(b"a\n  b: c\nd\n  e: )\n", OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().contains("stdin 3:1"), "{}", err)
+}
+
+#[test]
+fn error_snippet_shows_sass() {
+    let err = compile_sass(b"=m($x)\n  a: $x\np\n  +m(1px + 1em)\n",
+                           OutputStyle::Normal)
+        .unwrap_err();
+    assert_eq!(err.to_string(),
+               "1px and 1em have incompatible units.\n  \
+                \u{2577}\n\
+                4 \u{2502}   +m(1px + 1em)\n  \
+                \u{2502}   ^\n  \
+                \u{2575}\n  \
+                stdin 4:3  root stylesheet")
+}
+
+#[test]
+fn nested_pseudo_selector() {
+    check(b"a\n  :not(.b) .c\n    d: e\n",
+          "a :not(.b) .c {\n  d: e;\n}\n")
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_sass(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
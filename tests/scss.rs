@@ -1,6 +1,6 @@
 //! Tests from spec/scss
 extern crate rsass;
-use rsass::{OutputStyle, compile_scss};
+use rsass::{Format, OutputStyle, compile_scss};
 
 #[test]
 fn almost_ambiguous_nested_rules_and_declarations() {
@@ -325,10 +325,160 @@ fn null() {
 
 #[test]
 fn precision() {
-    check(b"div {\n  a: (20/3);\n  b: (5/2);\n  c: (9/3);\n  d: (20/-3);\n  \
-            e: (-5/2);\n  f: -(9/3);\n}",
-          "div {\n  a: 6.66667;\n  b: 2.5;\n  c: 3;\n  d: -6.66667;\n  \
-           e: -2.5;\n  f: -3;\n}\n")
+    // This spec test is written for a precision of 5.
+    let format = Format {
+        style: OutputStyle::Normal,
+        precision: 5,
+        short_colors: false,
+    };
+    let css = compile_scss(b"div {\n  a: (20/3);\n  b: (5/2);\n  c: (9/3);\n  \
+                             d: (20/-3);\n  e: (-5/2);\n  f: -(9/3);\n  \
+                             g: (1/3) == 0.333333;\n}",
+                           format);
+    assert_eq!(String::from_utf8(css.unwrap()).unwrap(),
+               "div {\n  a: 6.66667;\n  b: 2.5;\n  c: 3;\n  d: -6.66667;\n  \
+                e: -2.5;\n  f: -3;\n  g: true;\n}\n")
+}
+
+#[test]
+fn default_precision() {
+    check(b"div {\n  a: (20/3);\n  b: (1/3) == 0.33333333333;\n  \
+            c: (1/3) == 0.333333333;\n}",
+          "div {\n  a: 6.6666666667;\n  b: true;\n  c: false;\n}\n")
+}
+
+#[test]
+fn calculations() {
+    check(b"$gutter: 10px;\ndiv {\n  a: calc(100% - #{$gutter});\n  \
+            b: calc(100% - $gutter);\n  c: calc(1px + 2px);\n  \
+            d: min(50%, 300px);\n  e: max(1px, $gutter);\n  \
+            f: clamp(1rem, 2vw + 1rem, 3rem);\n  \
+            g: calc(var(--x) * 2);\n}",
+          "div {\n  a: calc(100% - 10px);\n  b: calc(100% - 10px);\n  \
+           c: 3px;\n  d: min(50%, 300px);\n  e: 10px;\n  \
+           f: clamp(1rem, 2vw + 1rem, 3rem);\n  g: calc(var(--x) * 2);\n}\n")
+}
+
+#[test]
+fn calculation_operations() {
+    for &(input, op) in &[(&b"1 + calc(1px + 1em)"[..], "1 + calc(1px + 1em)"),
+                          (b"calc(1px + 1em) * 2", "calc(1px + 1em) * 2"),
+                          (b"calc(1px + 1em) - 1", "calc(1px + 1em) - 1"),
+                          (b"calc(1px + 1em) / 2", "calc(1px + 1em) / 2")] {
+        let mut scss = b"a {\n  b: ".to_vec();
+        scss.extend(input);
+        scss.extend(b";\n}\n");
+        let err = compile_scss(&scss, OutputStyle::Normal).unwrap_err();
+        assert!(err.to_string()
+                    .starts_with(&format!("Undefined operation \"{}\".", op)),
+                "{}",
+                err)
+    }
+    check(b"a {\n  b: \"x\" + calc(1px + 1em);\n}\n",
+          "a {\n  b: \"xcalc(1px + 1em)\";\n}\n")
+}
+
+#[test]
+fn calculation_unitless_and_unit_are_incompatible() {
+    for &(input, msg) in &[(&b"min(1, 2px)"[..], "1 and 2px"),
+                           (b"max(1px, 2)", "1px and 2"),
+                           (b"calc(1px + 2)", "1px and 2")] {
+        let mut scss = b"a {\n  b: ".to_vec();
+        scss.extend(input);
+        scss.extend(b";\n}\n");
+        let err = compile_scss(&scss, OutputStyle::Normal).unwrap_err();
+        assert!(err.to_string()
+                    .starts_with(&format!("{} are incompatible.", msg)),
+                "{}",
+                err)
+    }
+    check(b"a {\n  b: calc(2 * 3px);\n  c: min(1, 2);\n}\n",
+          "a {\n  b: 6px;\n  c: 1;\n}\n")
+}
+
+#[test]
+fn calculation_needs_whitespace() {
+    assert!(compile_scss(b"a {\n  b: calc(1px+2px);\n}\n",
+                         OutputStyle::Normal)
+                .is_err())
+}
+
+#[test]
+fn user_function_replaces_calculation() {
+    check(b"@function min($a, $b) {\n  @return $a + $b;\n}\n\
+            a {\n  b: min(1px, 2px);\n  c: max(1px, 2px);\n}\n",
+          "a {\n  b: 3px;\n  c: 2px;\n}\n")
+}
+
+#[test]
+fn modulo() {
+    check(b"div {\n  a: 7 % 3;\n  b: -5 % 3;\n  c: 5 % -3;\n  \
+            d: 10px % 3;\n  e: 7.5 % 2;\n  f: 1in % 5px;\n}",
+          "div {\n  a: 1;\n  b: 1;\n  c: -1;\n  d: 1px;\n  e: 1.5;\n  \
+           f: 0.0104166667in;\n}\n")
+}
+
+#[test]
+fn slash_or_division() {
+    check(b"$w: 16px;\n@function half($x) {\n  @return $x / 2;\n}\n\
+            div {\n  a: 12px/1.5;\n  b: $w/2;\n  c: (16px/2);\n  \
+            d: half(16px);\n  e: 15 / 3 / 5;\n  f: 1/2, 3/4;\n  \
+            font: italic 12px/1.5 serif;\n}",
+          "div {\n  a: 12px/1.5;\n  b: 8px;\n  c: 8px;\n  d: 8px;\n  \
+           e: 15/3/5;\n  f: 1/2, 3/4;\n  font: italic 12px/1.5 serif;\n}\n")
+}
+
+#[test]
+fn bracketed_lists() {
+    check(b"$lines: [full-start] 1fr [main-start] 2fr [main-end full-end];\n\
+            div {\n  grid-template-columns: $lines;\n  \
+            a: length([a b c]);\n  b: [];\n  c: append([a], b);\n  \
+            d: [a b] == (a b);\n  e: is-bracketed($lines);\n  \
+            f: list-separator($lines);\n}",
+          "div {\n  grid-template-columns: [full-start] 1fr [main-start] \
+           2fr [main-end full-end];\n  a: 3;\n  b: [];\n  c: [a b];\n  \
+           d: false;\n  e: false;\n  f: space;\n}\n")
+}
+
+#[test]
+fn slash_list_separator() {
+    check(b"$area: a/b/c;\ndiv {\n  a: list-separator($area);\n  \
+            b: length($area);\n  c: nth($area, 2);\n  \
+            d: list-separator(12px/1.5);\n}",
+          "div {\n  a: slash;\n  b: 3;\n  c: b;\n  d: space;\n}\n")
+}
+
+#[test]
+fn no_overflow() {
+    check(b"$x: percentage(1/3) * 1.1111;\ndiv {\n  \
+            a: $x * 1.1111 * 1.1111 * 1.1111 * 1.1111 * 1.1111 * 1.1111 * \
+            1.1111;\n  \
+            b: (1/3) + (1/7) + (1/11) + (1/13) + (1/17) + (1/19) + (1/23) + \
+            (1/29) + (1/31) + (1/37) + (1/41) + (1/43) + (1/47);\n  \
+            c: 0.1234567890123456789012345;\n}",
+          "div {\n  a: 77.4290491487%;\n  b: 0.961646517;\n  \
+           c: 0.123456789;\n}\n")
+}
+
+#[test]
+fn large_numbers_are_exact() {
+    check(b"div {\n  a: pow(10, 30);\n  \
+            b: 99999999999999999999 * 99999999999999999999;\n  \
+            c: 123456789012345678901234567890px + 1px;\n  \
+            d: pow(10, 400) / pow(10, 399);\n  e: pow(2, -3);\n}",
+          "div {\n  a: 1000000000000000000000000000000;\n  \
+           b: 9999999999999999999800000000000000000001;\n  \
+           c: 123456789012345678901234567891px;\n  d: 10;\n  \
+           e: 0.125;\n}\n")
+}
+
+#[test]
+fn fractions_do_not_grow_in_loops() {
+    // Each iteration would make an exact fraction longer, and the
+    // compile time grow faster than linear.
+    check(b"$x: 1;\n@for $i from 1 through 1200 {\n  \
+            $x: $x * 1.0001 + 0.3333 !global;\n}\ndiv {\n  a: $x;\n}",
+          "div {\n  a: 426.0519502898;\n}\n")
 }
 
 #[test]
@@ -0,0 +1,65 @@
+//! Tests for source map generation.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss_file_with_sourcemap,
+            compile_scss_with_sourcemap};
+
+#[test]
+fn rule_and_properties() {
+    let (css, map) = compile_scss_with_sourcemap(b"a {\n  b: c;\n\n  d: e;\n}\n",
+                                                 OutputStyle::Normal)
+        .unwrap();
+    assert_eq!(String::from_utf8(css).unwrap(),
+               "a {\n  b: c;\n  d: e;\n}\n");
+    assert_eq!(map.lookup(0, 0), Some(("stdin", 1, 1)));
+    assert_eq!(map.lookup(1, 2), Some(("stdin", 2, 3)));
+    assert_eq!(map.lookup(2, 2), Some(("stdin", 4, 3)));
+    assert_eq!(map.encoded_mappings(), "AAAA;EACE;EAEA");
+}
+
+#[test]
+fn nested_rule() {
+    let (_, map) = compile_scss_with_sourcemap(b"a {\n  b {c: d}\n}\n",
+                                               OutputStyle::Normal)
+        .unwrap();
+    assert_eq!(map.lookup(0, 0), Some(("stdin", 2, 3)));
+    assert_eq!(map.lookup(1, 2), Some(("stdin", 2, 6)));
+}
+
+#[test]
+fn compressed() {
+    let (css, map) = compile_scss_with_sourcemap(b"a {\n  b: c;\n  d: e;\n}\n",
+                                                 OutputStyle::Compressed)
+        .unwrap();
+    assert_eq!(String::from_utf8(css).unwrap(), "a{b:c;d:e}\n");
+    assert_eq!(map.lookup(0, 2), Some(("stdin", 2, 3)));
+    assert_eq!(map.lookup(0, 6), Some(("stdin", 3, 3)));
+}
+
+#[test]
+fn mixin_expansion() {
+    let (_, map) = compile_scss_with_sourcemap(b"@mixin m {\n  x: y;\n}\n\
+                                                 a {\n  @include m;\n}\n",
+                                               OutputStyle::Normal)
+        .unwrap();
+    assert_eq!(map.lookup(0, 0), Some(("stdin", 4, 1)));
+    assert_eq!(map.lookup(1, 2), Some(("stdin", 2, 3)));
+}
+
+#[test]
+fn imported_files() {
+    let (_, map) =
+        compile_scss_file_with_sourcemap("tests/basic/14_imports/input.scss"
+                                             .as_ref(),
+                                         OutputStyle::Normal)
+            .unwrap();
+    assert_eq!(map.sources(),
+               &["tests/basic/14_imports/a.scss",
+                 "tests/basic/14_imports/input.scss",
+                 "tests/basic/14_imports/../14_imports/b.scss",
+                 "tests/basic/14_imports/../14_imports/d.scss",
+                 "tests/basic/14_imports/sub/c.scss"]);
+    assert_eq!(map.lookup(1, 2),
+               Some(("tests/basic/14_imports/a.scss", 3, 5)));
+    assert_eq!(map.lookup(12, 2),
+               Some(("tests/basic/14_imports/../14_imports/b.scss", 2, 3)));
+}
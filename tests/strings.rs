@@ -0,0 +1,63 @@
+//! Tests for the string functions.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn str_slice() {
+    check(b"a {\n  b: str-slice(\"abcd\", 2, 3);\n  \
+            c: str-slice(\"abcd\", 2);\n  d: str-slice(\"abcd\", -3, -2);\n  \
+            e: str-slice(\"abcd\", 3, 2);\n  f: str-slice(\"abcd\", 2, 10);\n  \
+            g: str-slice(abcd, -10);\n  h: str-slice(\"abcd\", -1);\n}\n",
+          "a {\n  b: \"bc\";\n  c: \"bcd\";\n  d: \"bc\";\n  e: \"\";\n  \
+           f: \"bcd\";\n  g: abcd;\n  h: \"d\";\n}\n")
+}
+
+#[test]
+fn str_slice_code_points() {
+    check("a {\n  b: str-slice(\"Déjà vu\", 2, 4);\n  \
+           c: str-slice(\"Déjà vu\", -5, -3);\n}\n"
+              .as_bytes(),
+          "@charset \"UTF-8\";\na {\n  b: \"éjà\";\n  c: \"jà \";\n}\n")
+}
+
+#[test]
+fn bem_name() {
+    check(b"@function bem($block, $element) {\n  \
+            @return str-insert($element, $block + \"__\", 1);\n}\n\
+            a {\n  b: bem(card, title);\n  \
+            c: str-index(\"card__title\", \"__\");\n}\n",
+          "a {\n  b: card__title;\n  c: 5;\n}\n")
+}
+
+#[test]
+fn unique_id() {
+    check(b"$a: unique-id();\n$b: unique-id();\n\
+            a {\n  b: type-of($a);\n  c: $a == $b;\n  \
+            d: str-length($a);\n  e: str-slice($a, 1, 1);\n}\n",
+          "a {\n  b: string;\n  c: false;\n  d: 9;\n  e: u;\n}\n")
+}
+
+#[test]
+fn index_not_int() {
+    check_err(b"a {\n  b: str-insert(abcd, e, 1.5);\n}\n",
+              "$index: 1.5 is not an int.\n")
+}
+
+#[test]
+fn bad_string() {
+    check_err(b"a {\n  b: str-slice(12, 1);\n}\n",
+              "expected string, got number = 12\n")
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}
+
+fn check_err(input: &[u8], expected: &str) {
+    let err = compile_scss(input, OutputStyle::Normal).unwrap_err();
+    assert!(err.to_string().starts_with(expected), "{}", err);
+    assert!(err.to_string().contains("stdin 2:3"), "{}", err)
+}
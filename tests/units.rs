@@ -0,0 +1,106 @@
+//! Tests for arithmetic on numbers with units.
+extern crate rsass;
+use rsass::{OutputStyle, compile_scss};
+
+#[test]
+fn multiply_and_cancel() {
+    check(b"a {\n  b: 10px * 2px / 4px;\n  c: (10px / 2em) * 3em;\n}\n",
+          "a {\n  b: 5px;\n  c: 15px;\n}\n")
+}
+
+#[test]
+fn divide_and_convert() {
+    check(b"a {\n  b: (1cm / 5mm);\n  c: (2in / 1in);\n}\n",
+          "a {\n  b: 2;\n  c: 2;\n}\n")
+}
+
+#[test]
+fn add_and_convert() {
+    check(b"a {\n  b: 1cm + 10mm;\n  c: 1s - 500ms;\n}\n",
+          "a {\n  b: 2cm;\n  c: 0.5s;\n}\n")
+}
+
+#[test]
+fn compound_unit_name() {
+    check(b"a {\n  b: unit(10px / 2em);\n  c: unit(2px * 3px);\n  \
+            d: unit(1 / 2s);\n}\n",
+          "a {\n  b: \"px/em\";\n  c: \"px*px\";\n  d: \"s^-1\";\n}\n")
+}
+
+#[test]
+fn compound_unit_in_variable() {
+    check(b"$area: 2px * 3px;\na {\n  b: $area / 2px;\n}\n",
+          "a {\n  b: 3px;\n}\n")
+}
+
+#[test]
+fn compound_unit_in_css() {
+    let err = compile_scss(b"a {\n  b: 2px * 3px;\n}\n", OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("6px*px isn't a valid CSS value.\n"),
+            "{}",
+            err);
+    assert!(err.to_string().contains("stdin 2:3"), "{}", err)
+}
+
+#[test]
+fn add_exact_conversion() {
+    check(b"a {\n  b: 1in + 96px;\n  c: 1turn + 90deg;\n  \
+            d: 1dppx + 96dpi;\n}\n",
+          "a {\n  b: 2in;\n  c: 1.25turn;\n  d: 2dppx;\n}\n")
+}
+
+#[test]
+fn add_radians() {
+    check(b"a {\n  b: 0deg + 1rad;\n  c: 0rad + 180deg;\n}\n",
+          "a {\n  b: 57.2957795131deg;\n  c: 3.1415926536rad;\n}\n")
+}
+
+#[test]
+fn quarter_millimeters() {
+    check(b"a {\n  b: 1mm + 4Q;\n  c: 1mm + 4q;\n}\n",
+          "a {\n  b: 2mm;\n  c: 2mm;\n}\n")
+}
+
+#[test]
+fn compare_with_conversion() {
+    check(b"a {\n  b: 1in == 96px;\n  c: 1cm > 9mm;\n  d: 1 == 1px;\n  \
+            e: 1px == 1em;\n  f: 100ms <= 0.1s;\n}\n",
+          "a {\n  b: true;\n  c: true;\n  d: false;\n  e: false;\n  \
+           f: true;\n}\n")
+}
+
+#[test]
+fn comparable() {
+    check(b"a {\n  b: comparable(2px, 1in);\n  c: comparable(1em, 1px);\n  \
+            d: comparable(3, 1px);\n  e: comparable(1s, 1Hz);\n}\n",
+          "a {\n  b: true;\n  c: false;\n  d: true;\n  e: false;\n}\n")
+}
+
+#[test]
+fn add_incompatible_units() {
+    let err = compile_scss(b"a {\n  b: 1em + 2px;\n}\n", OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string()
+                .starts_with("1em and 2px have incompatible units.\n"),
+            "{}",
+            err);
+    assert!(err.to_string().contains("stdin 2:3"), "{}", err)
+}
+
+#[test]
+fn compare_incompatible_units() {
+    let err = compile_scss(b"a {\n  b: 1s < 2px;\n}\n", OutputStyle::Normal)
+        .unwrap_err();
+    assert!(err.to_string()
+                .starts_with("1s and 2px have incompatible units.\n"),
+            "{}",
+            err)
+}
+
+fn check(input: &[u8], expected: &str) {
+    assert_eq!(compile_scss(input, OutputStyle::Normal)
+                   .and_then(|s| Ok(String::from_utf8(s)?))
+                   .unwrap(),
+               expected);
+}